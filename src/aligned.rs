@@ -0,0 +1,94 @@
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// Buffer alignment for device I/O: a page, which covers 512 and 4096-byte
+/// sectors as FILE_FLAG_NO_BUFFERING requires. A `Vec<u8>` only promises 1.
+pub const IO_ALIGN: usize = 4096;
+
+/// Zeroed heap buffer whose first byte sits on an `align` boundary.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+// the buffer owns its allocation exclusively, like a Vec<u8>
+unsafe impl Send for AlignedBuf {}
+
+impl AlignedBuf {
+    /// `len` zero bytes aligned to `align` (a power of two). Fails instead of
+    /// aborting when the allocation can't be made.
+    pub fn new(len: usize, align: usize) -> io::Result<Self> {
+        let layout = Layout::from_size_align(len.max(1), align).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("bad buffer layout ({} bytes, align {}): {}", len, align, e),
+            )
+        })?;
+
+        let ptr = NonNull::new(unsafe { alloc_zeroed(layout) }).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("could not allocate {} aligned bytes", len),
+            )
+        })?;
+
+        Ok(AlignedBuf { ptr, len, layout })
+    }
+
+    /// An aligned copy of `data`.
+    pub fn copy_of(data: &[u8]) -> io::Result<Self> {
+        let mut buf = Self::new(data.len(), IO_ALIGN)?;
+        buf.copy_from_slice(data);
+        Ok(buf)
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_start_on_the_requested_boundary() {
+        let cases = [
+            (1, 512),
+            (4097, IO_ALIGN),
+            (3 << 20, 1 << 16),
+            (0, IO_ALIGN),
+        ];
+        for (len, align) in cases {
+            let buf = AlignedBuf::new(len, align).unwrap();
+            assert_eq!(buf.as_ptr() as usize % align, 0);
+            assert_eq!(buf.len(), len);
+            assert!(buf.iter().all(|&b| b == 0));
+        }
+
+        let copy = AlignedBuf::copy_of(b"wipe").unwrap();
+        assert_eq!(copy.as_ptr() as usize % IO_ALIGN, 0);
+        assert_eq!(&copy[..], b"wipe");
+
+        assert!(AlignedBuf::new(4096, 3).is_err());
+    }
+}
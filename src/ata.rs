@@ -0,0 +1,194 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::aligned::{AlignedBuf, IO_ALIGN};
+
+/// ATA IDENTIFY DEVICE returns 256 little-endian words.
+pub const IDENTIFY_LEN: usize = 512;
+
+// word 69: additional supported features
+const WORD_ADDITIONAL_SUPPORTED: usize = 69;
+const BIT_DRAT: u16 = 1 << 14;
+const BIT_RZAT: u16 = 1 << 5;
+// word 169: DATA SET MANAGEMENT support
+const WORD_DSM: usize = 169;
+const BIT_TRIM: u16 = 1 << 0;
+
+// SMART READ DATA: a 512-byte page whose attribute table starts at byte 2
+const SMART_TABLE_OFFSET: usize = 2;
+const SMART_ENTRY_LEN: usize = 12;
+const SMART_MAX_ENTRIES: usize = 30;
+
+/// What the drive promises about reads of trimmed blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrimSupport {
+    pub trim: bool,
+    /// Deterministic read after TRIM
+    pub drat: bool,
+    /// Read zeros after TRIM
+    pub rzat: bool,
+}
+
+impl TrimSupport {
+    /// Only DRAT + RZAT drives let us check that trimmed data is gone.
+    pub fn verifiable(&self) -> bool {
+        self.trim && self.drat && self.rzat
+    }
+}
+
+/// Decode TRIM / DRAT / RZAT bits from raw IDENTIFY DEVICE data.
+pub fn decode_trim_support(identify: &[u8]) -> io::Result<TrimSupport> {
+    if identify.len() < IDENTIFY_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "IDENTIFY DEVICE data is shorter than 512 bytes",
+        ));
+    }
+
+    let word = |n: usize| u16::from_le_bytes([identify[n * 2], identify[n * 2 + 1]]);
+    let additional = word(WORD_ADDITIONAL_SUPPORTED);
+
+    Ok(TrimSupport {
+        trim: word(WORD_DSM) & BIT_TRIM != 0,
+        drat: additional & BIT_DRAT != 0,
+        rzat: additional & BIT_RZAT != 0,
+    })
+}
+
+/// `count` sector-aligned offsets spread evenly over `size` bytes.
+pub fn sample_offsets(size: u64, count: u64, sector_size: u64) -> Vec<u64> {
+    let sectors = size / sector_size;
+    if sectors == 0 || count == 0 {
+        return Vec::new();
+    }
+
+    let count = count.min(sectors);
+    let step = sectors / count;
+
+    (0..count).map(|i| i * step * sector_size).collect()
+}
+
+/// Read one sector at each offset; return the offsets that are not all zeros.
+pub fn nonzero_samples<R: Read + Seek>(
+    dev: &mut R,
+    offsets: &[u64],
+    sector_size: usize,
+) -> io::Result<Vec<u64>> {
+    let mut buf = AlignedBuf::new(sector_size, IO_ALIGN)?;
+    let mut dirty = Vec::new();
+
+    for &off in offsets {
+        dev.seek(SeekFrom::Start(off))?;
+        dev.read_exact(&mut buf)?;
+        if buf.iter().any(|&b| b != 0) {
+            dirty.push(off);
+        }
+    }
+
+    Ok(dirty)
+}
+
+/// One row of the SMART attribute table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmartAttribute {
+    pub id: u8,
+    /// Normalized value (usually 100 or 200 when healthy, falling toward the threshold)
+    pub current: u8,
+    /// 48-bit vendor raw value (counts for most attributes that matter here)
+    pub raw: u64,
+}
+
+/// Decode the attribute table of a SMART READ DATA page; unused slots (id 0) are skipped.
+pub fn parse_smart_attributes(data: &[u8]) -> io::Result<Vec<SmartAttribute>> {
+    if data.len() < IDENTIFY_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "SMART data is shorter than 512 bytes",
+        ));
+    }
+
+    let attributes = (0..SMART_MAX_ENTRIES)
+        .map(|i| &data[SMART_TABLE_OFFSET + i * SMART_ENTRY_LEN..][..SMART_ENTRY_LEN])
+        .filter(|e| e[0] != 0)
+        .map(|e| {
+            let mut raw = [0u8; 8];
+            raw[..6].copy_from_slice(&e[5..11]);
+            SmartAttribute {
+                id: e[0],
+                current: e[3],
+                raw: u64::from_le_bytes(raw),
+            }
+        })
+        .collect();
+
+    Ok(attributes)
+}
+
+/// Common name of a SMART attribute id, as most tools show it.
+pub fn smart_attribute_name(id: u8) -> &'static str {
+    match id {
+        1 => "Raw read error rate",
+        5 => "Reallocated sectors",
+        9 => "Power-on hours",
+        10 => "Spin retry count",
+        12 => "Power cycle count",
+        187 => "Reported uncorrectable",
+        188 => "Command timeout",
+        194 => "Temperature",
+        196 => "Reallocation events",
+        197 => "Pending sectors",
+        198 => "Offline uncorrectable",
+        199 => "UDMA CRC errors",
+        _ => "Vendor specific",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn identify(word69: u16, word169: u16) -> Vec<u8> {
+        let mut data = vec![0u8; IDENTIFY_LEN];
+        data[69 * 2..][..2].copy_from_slice(&word69.to_le_bytes());
+        data[169 * 2..][..2].copy_from_slice(&word169.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn drat_and_rzat_bits_are_decoded() {
+        let full = decode_trim_support(&identify(0x4020, 0x0001)).unwrap();
+        assert_eq!(
+            full,
+            TrimSupport {
+                trim: true,
+                drat: true,
+                rzat: true
+            }
+        );
+        assert!(full.verifiable());
+
+        // DRAT alone promises the same data, not zeros
+        let drat_only = decode_trim_support(&identify(0x4000, 0x0001)).unwrap();
+        assert!(drat_only.drat && !drat_only.rzat && !drat_only.verifiable());
+
+        let no_trim = decode_trim_support(&identify(0x4020, 0)).unwrap();
+        assert!(!no_trim.verifiable());
+
+        assert!(decode_trim_support(&[0u8; 100]).is_err());
+    }
+
+    #[test]
+    fn sampled_reads_name_the_sectors_that_are_not_zero() {
+        let mut dev = Cursor::new(vec![0u8; 64 * 512]);
+        dev.get_mut()[40 * 512 + 7] = 0x5A;
+
+        let offsets = sample_offsets(64 * 512, 8, 512);
+        assert_eq!(offsets.len(), 8);
+        assert!(offsets.iter().all(|o| o % 512 == 0 && *o < 64 * 512));
+
+        let dirty = nonzero_samples(&mut dev, &offsets, 512).unwrap();
+        assert_eq!(dirty, [40 * 512]);
+        let clean = nonzero_samples(&mut dev, &[0, 512], 512).unwrap();
+        assert!(clean.is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often progress is saved when --checkpoint-every isn't given.
+pub const DEFAULT_SAVE_EVERY: Duration = Duration::from_secs(60);
+
+/// --checkpoint-file for one file target: where the wipe got to, keyed by
+/// the target path and its size so a stale record is never reused.
+#[derive(Clone, Debug)]
+pub struct CheckpointFile {
+    pub path: PathBuf,
+    pub target: String,
+    pub size: u64,
+    /// Mode name, pass count, seed and nonce of the run: resuming with other
+    /// settings would leave two different wipes on the media
+    pub mode: String,
+    pub passes: u32,
+    pub seed: Option<u64>,
+    pub nonce: Option<u64>,
+    /// How often progress is saved within a pass
+    pub save_every: Duration,
+}
+
+/// What a checkpoint file records.
+#[derive(Clone, Debug, PartialEq)]
+struct Saved {
+    target: String,
+    size: u64,
+    mode: String,
+    passes: u32,
+    seed: Option<u64>,
+    nonce: Option<u64>,
+    pass: u32,
+    offset: u64,
+}
+
+impl CheckpointFile {
+    /// Record that `pass` has been written up to absolute byte `offset`.
+    pub fn save(&self, pass: u32, offset: u64) -> io::Result<()> {
+        let text = format!(
+            "target={}\nsize={}\nmode={}\npasses={}\nseed={}\nnonce={}\npass={}\noffset={}\n",
+            self.target,
+            self.size,
+            self.mode,
+            self.passes,
+            optional(self.seed),
+            optional(self.nonce),
+            pass,
+            offset
+        );
+
+        // write then rename so a crash never leaves a half-written record
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// (pass, offset) saved for this target, or None if there is nothing to resume.
+    pub fn load(&self) -> io::Result<Option<(u32, u64)>> {
+        let saved = match self.read()? {
+            Some(s) if s.target == self.target => s,
+            _ => return Ok(None),
+        };
+
+        if saved.size != self.size {
+            println!(
+                "Warning: '{}' changed size since the checkpoint ({} -> {} bytes); starting over.",
+                self.target, saved.size, self.size
+            );
+            return Ok(None);
+        }
+        if (&saved.mode, saved.passes, saved.seed, saved.nonce)
+            != (&self.mode, self.passes, self.seed, self.nonce)
+        {
+            println!(
+                "Warning: the checkpoint for '{}' is from another wipe ({}, {} passes); starting over.",
+                self.target, saved.mode, saved.passes
+            );
+            return Ok(None);
+        }
+
+        Ok(Some((saved.pass, saved.offset)))
+    }
+
+    /// (seed, nonce) of a saved run of the same mode and passes on this
+    /// target, so a resumed wipe keeps writing the stream it started.
+    pub fn saved_stream(&self) -> io::Result<Option<(Option<u64>, Option<u64>)>> {
+        Ok(self
+            .read()?
+            .filter(|s| {
+                (&s.target, s.size, &s.mode, s.passes)
+                    == (&self.target, self.size, &self.mode, self.passes)
+            })
+            .map(|s| (s.seed, s.nonce)))
+    }
+
+    /// Forget the record once the wipe has finished.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn read(&self) -> io::Result<Option<Saved>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => parse_checkpoint(&text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// "" for None, so an unseeded run is recorded as such.
+fn optional(value: Option<u64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Parse the "key=value" lines written by `save`.
+fn parse_checkpoint(text: &str) -> io::Result<Saved> {
+    let mut target = None;
+    let mut size = None;
+    let mut mode = None;
+    let mut passes = None;
+    let mut seed = None;
+    let mut nonce = None;
+    let mut pass = None;
+    let mut offset = None;
+
+    for line in text.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        match key {
+            "target" => target = Some(value.to_string()),
+            "size" => size = value.parse().ok(),
+            "mode" => mode = Some(value.to_string()),
+            "passes" => passes = value.parse().ok(),
+            "seed" => seed = Some(value.parse().ok()),
+            "nonce" => nonce = Some(value.parse().ok()),
+            "pass" => pass = value.parse().ok(),
+            "offset" => offset = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    match (target, size, mode, passes, seed, nonce, pass, offset) {
+        (
+            Some(target),
+            Some(size),
+            Some(mode),
+            Some(passes),
+            Some(seed),
+            Some(nonce),
+            Some(pass),
+            Some(offset),
+        ) => Ok(Saved {
+            target,
+            size,
+            mode,
+            passes,
+            seed,
+            nonce,
+            pass,
+            offset,
+        }),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "checkpoint file is incomplete or corrupt",
+        )),
+    }
+}
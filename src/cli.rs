@@ -1,25 +1,323 @@
-use clap::{Parser};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 
-use crate::wipe::WipeMode;
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Parser, Debug)]
+use wipecore::identity::{expand_phrase, DiskIdentity};
+use wipecore::job::Job;
+use wipecore::notify::parse_http_url;
+use wipecore::preset::{load_preset, presets_path, Preset};
+use wipecore::util::{estimate_seconds, format_eta, parse_duration, parse_size};
+use wipecore::win::current_username;
+use wipecore::wipe::{effective_passes, pass_count_reason, OnVerifyFail, Units, WipeMode};
+
+/// Write rate assumed when showing how long an excessive --passes would take.
+const ESTIMATE_RATE_MB_S: f64 = 150.0;
+
+#[derive(Parser, Debug, Clone)]
 #[command(
     name = "WipeCore",
     version,
-    about = "Simple file / disk wiper for Windows"
+    about = "Simple file / disk wiper for Windows",
+    after_help = "Environment: WIPECORE_MODE, WIPECORE_PASSES and WIPECORE_BUFFER set defaults\nfor --mode, --passes and --buffer-size. Command-line flags override the environment,\nwhich overrides --preset."
 )]
 pub struct Args {
-    /// Target file to wipe (ignored in disk modes)
-    pub target: Option<String>,
+    /// Target file(s) to wipe (ignored in disk modes)
+    pub targets: Vec<String>,
+
+    /// Wipe what a symlink/junction target points to instead of refusing it
+    #[arg(long)]
+    pub follow_reparse: bool,
+
+    /// Attach .vhd/.vhdx targets as virtual disks and wipe the disk they present
+    #[arg(long)]
+    pub inside_image: bool,
+
+    /// Wipe all \\.\PhysicalDriveN targets at once, one thread and progress row per disk
+    #[arg(long, conflicts_with = "inside_image")]
+    pub multi_disk_parallel: bool,
+
+    /// Run the wipe jobs listed in a JSON job file, one after another
+    #[arg(long, value_name = "PATH")]
+    pub job: Option<PathBuf>,
+
+    /// Where --job writes per-job results as JSON (default: <job>.results.json)
+    #[arg(long, value_name = "PATH", requires = "job")]
+    pub job_results: Option<PathBuf>,
+
+    /// Read more targets, one per line, from a file or `-` for stdin
+    #[arg(long, value_name = "PATH|-")]
+    pub devices_file: Option<String>,
+
+    /// Apply flags saved with --save-preset (flags given here and WIPECORE_* still win)
+    #[arg(long, value_name = "NAME")]
+    pub preset: Option<String>,
 
-    /// Wipe mode: zeros | random 
+    /// Save the resolved wipe flags as a preset in %APPDATA%\wipecore\presets.toml and exit
+    #[arg(long, value_name = "NAME")]
+    pub save_preset: Option<String>,
+
+    /// Wipe mode (see --list-modes); secureflip always runs at least 2 passes
     #[arg(long, value_enum, default_value_t = WipeMode::Zeros)]
     pub mode: WipeMode,
 
     /// Number of overwrite passes
-    #[arg(long, default_value_t = 1)]
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
     pub passes: u32,
 
+    /// Refuse --passes above this unless --allow-excessive-passes is given
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    pub max_passes: u32,
+
+    /// Run more passes than --max-passes allows
+    #[arg(long)]
+    pub allow_excessive_passes: bool,
+
+    /// Seed random passes so they can be regenerated later (pass N uses SEED+N)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Bytes per write / read chunk, e.g. 8M (default) or 64M
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "8M")]
+    pub buffer_size: u64,
+
+    /// Open disks with FILE_FLAG_NO_BUFFERING so writes bypass the system cache
+    /// (--wipe-disk / --wipe-volume)
+    #[arg(long)]
+    pub no_buffering: bool,
+
+    /// Split each buffer into device writes of this size, e.g. 1M or 256K (sector multiple)
+    #[arg(long, value_name = "BYTES", value_parser = parse_size)]
+    pub io_size: Option<u64>,
+
+    /// Start wiping at this offset (see --units)
+    #[arg(long)]
+    pub offset: Option<u64>,
+
+    /// Wipe only this much from --offset (see --units)
+    #[arg(long)]
+    pub length: Option<u64>,
+
+    /// Write each pass from the end of the target back to the start, so a failing tail goes first
+    #[arg(long, conflicts_with = "checkpoint_file")]
+    pub reverse: bool,
+
+    /// Units for --offset / --length: bytes, or sectors of the device's sector size
+    #[arg(long, value_enum, default_value_t = Units::Bytes)]
+    pub units: Units,
+
+    /// Refuse a raw device wipe unless --buffer-size/--io-size/--offset/--length are whole sectors
+    #[arg(long)]
+    pub strict_sector_size: bool,
+
+    /// Flush writes to media every N bytes during a pass, e.g. 1G (default: only at pass end)
+    #[arg(long, value_name = "BYTES", value_parser = parse_size)]
+    pub sync_every: Option<u64>,
+
+    /// Abort if sustained throughput drops below this many MB/s
+    #[arg(long, value_name = "MB/s")]
+    pub min_throughput: Option<f64>,
+
+    /// How long to wait before --min-throughput starts checking, e.g. 30s, 5m
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "30s")]
+    pub min_throughput_grace: Duration,
+
+    /// Base the ETA on the last BYTES written, e.g. 2G, so it adapts when an SSD cache runs out
+    #[arg(long, value_name = "BYTES", value_parser = parse_size)]
+    pub eta_window: Option<u64>,
+
+    /// Print a timestamped absolute byte offset this often during each pass, e.g. 1m
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub checkpoint_every: Option<Duration>,
+
+    /// Save file wipe progress here (every --checkpoint-every, default 1m) and resume from it
+    /// after an interruption
+    #[arg(long)]
+    pub checkpoint_file: Option<PathBuf>,
+
+    /// Hash a file's contents before and after the wipe and require them to differ
+    #[arg(long)]
+    pub checksum_before_after: bool,
+
+    /// Record each finished file here and skip it (same path, size and mtime) on a rerun
+    #[arg(long, value_name = "STATE")]
+    pub resume: Option<PathBuf>,
+
+    /// Read the target back after wiping and check the final pattern
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Read everything back and count every byte that isn't the final pattern
+    #[arg(long)]
+    pub pattern_verify: bool,
+
+    /// Spot-check N sectors spread across the target for the final pattern (uses --seed)
+    #[arg(long, value_name = "N")]
+    pub verify_samples: Option<u64>,
+
+    /// Before a disk wipe, write and read back sectors out to the last one to catch fake capacity
+    #[arg(long)]
+    pub probe_addressing: bool,
+
+    /// Turn a disk's write cache off during the wipe so verify reads the media; restored afterwards
+    #[arg(long)]
+    pub disable_write_cache: bool,
+
+    /// Write a plain-text wipe report to this path (with --verify it includes the post-wipe SHA-256)
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Write reports and job results into this directory, named by target and start time
+    #[arg(long, value_name = "DIR", conflicts_with = "report")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Run this cmd command after each successful wipe; WIPECORE_TARGET, WIPECORE_STATUS
+    /// and WIPECORE_ERROR describe the outcome
+    #[arg(long, value_name = "COMMAND")]
+    pub exec_after: Option<String>,
+
+    /// Run --exec-after after failed wipes too
+    #[arg(long, requires = "exec_after")]
+    pub exec_always: bool,
+
+    /// POST a JSON summary (target, status, bytes, elapsed, operator, hostname) here
+    /// when each target finishes or fails; http:// only
+    #[arg(long, value_name = "URL", value_parser = parse_notify_url)]
+    pub notify_url: Option<String>,
+
+    /// Disk wipes: add SMART attributes read before and after the wipe to --report (ATA disks)
+    #[arg(long, requires = "report")]
+    pub report_include_smart: bool,
+
+    /// Verify each fixed-pattern pass right after it is written
+    #[arg(long)]
+    pub verify_each: bool,
+
+    /// What to do when a pass fails --verify-each
+    #[arg(long, value_enum, default_value_t = OnVerifyFail::Abort)]
+    pub on_verify_fail: OnVerifyFail,
+
+    /// Show what would be wiped (selection, confirmation, passes) without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// After a disk wipe, TRIM the whole disk and (on DRAT/RZAT drives) sample-check it reads zeros
+    #[arg(long)]
+    pub trim_then_verify_unreadable: bool,
+
+    /// Disk zero wipes: let SCSI/SAS disks zero themselves with WRITE SAME (falls back to streaming)
+    #[arg(long)]
+    pub write_same: bool,
+
+    /// After the wipe, write a "WIPED BY WIPECORE" banner into the first sector
+    #[arg(long)]
+    pub stamp: bool,
+
+    /// After a successful file wipe, truncate the file to 0 bytes (the path is kept)
+    #[arg(long, conflicts_with = "stamp")]
+    pub truncate: bool,
+
+    /// After a file wipe, set its created/modified/accessed times to 2000-01-01 00:00:00 UTC
+    #[arg(long)]
+    pub scrub_timestamps: bool,
+
+    /// After a disk wipe, write a blank MBR (no partitions) so the disk shows as initialized
+    #[arg(long, conflicts_with = "stamp")]
+    pub reinit_mbr: bool,
+
+    /// Allow --reinit-mbr on disks larger than MBR can address (2 TiB at 512-byte sectors)
+    #[arg(long, requires = "reinit_mbr")]
+    pub force_mbr: bool,
+
+    /// Operator name recorded in the report and stamp (default: Windows user name)
+    #[arg(long)]
+    pub operator: Option<String>,
+
+    /// Describe each wipe mode and exit
+    #[arg(long)]
+    pub list_modes: bool,
+
+    /// Measure read throughput of a file or \\.\PhysicalDriveN (never writes)
+    #[arg(long, value_name = "DEVICE")]
+    pub read_bench: Option<String>,
+
+    /// How much --read-bench reads, e.g. 512M, 4G
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "1G")]
+    pub read_bench_size: u64,
+
+    /// Check a file or \\.\PhysicalDriveN still holds the final pattern of --mode/--passes,
+    /// listing mismatched ranges (never writes)
+    #[arg(long, value_name = "DEVICE|FILE")]
+    pub verify_only: Option<String>,
+
+    /// Nonce of the wipe being checked by --verify-only, as printed in its report (hex)
+    #[arg(long, value_name = "HEX", requires = "verify_only", value_parser = parse_nonce)]
+    pub nonce: Option<u64>,
+
+    /// Print how long a wipe of --size at --rate would take and exit (no writes)
+    #[arg(long, requires_all = ["size", "rate"])]
+    pub estimate: bool,
+
+    /// Size for --estimate, e.g. 2T, or \\.\PhysicalDriveN to use its length
+    #[arg(long, value_name = "BYTES|DEVICE", requires = "estimate")]
+    pub size: Option<String>,
+
+    /// Sustained write rate for --estimate, in MB/s
+    #[arg(long, value_name = "MB/s", requires = "estimate")]
+    pub rate: Option<f64>,
+
+    /// Don't drive the Windows Terminal tab progress indicator
+    #[arg(long)]
+    pub no_osc: bool,
+
+    /// Don't show progress in the console window title
+    #[arg(long)]
+    pub no_title: bool,
+
+    /// One progress line for the whole wipe (overall pass and percent), no per-pass banners
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Run at below-normal CPU and low I/O priority so the machine stays responsive (slower)
+    #[arg(long)]
+    pub low_priority: bool,
+
+    /// Show model, serial, firmware, bus and geometry before the wipe phrase
+    #[arg(long)]
+    pub interactive_confirm_details: bool,
+
+    /// Phrase to type before a disk wipe, with {index}, {serial}, {model}, {size_gb},
+    /// e.g. ERASE-{serial} (default: WIPE-DISK-{index})
+    #[arg(long, value_name = "FMT", value_parser = parse_phrase_format)]
+    pub confirm_phrase_format: Option<String>,
+
+    /// After the disk phrase, also type a random 4-character code generated for this run
+    #[arg(long)]
+    pub confirm_checksum: bool,
+
+    /// Write at most this many bytes per target, e.g. 100M (testing only, NOT a full wipe)
+    #[arg(long, hide = true, value_name = "BYTES", value_parser = parse_size)]
+    pub limit_bytes: Option<u64>,
+
+    /// Make writes to these byte offsets of a file target fail, e.g. 4096,1M (testing only)
+    #[arg(long, hide = true, value_name = "OFFSETS", value_delimiter = ',', value_parser = parse_size)]
+    pub simulate_bad_sectors: Vec<u64>,
+
+    /// Print the IOCTL codes the tool uses and exit (developer aid)
+    #[arg(long, hide = true)]
+    pub dump_ctl_codes: bool,
+
+    /// Wipe the disk with this WWN (NAA or EUI-64 identifier), whatever its index
+    #[arg(long, value_name = "WWN", conflicts_with = "device_guid")]
+    pub device_wwn: Option<String>,
+
+    /// Wipe the disk with this 16-byte device GUID (e.g. an NVMe NGUID)
+    #[arg(long, value_name = "GUID")]
+    pub device_guid: Option<String>,
+
     /// Show info for \\.\PhysicalDriveN
     #[arg(long)]
     pub disk: Option<u32>,
@@ -28,15 +326,378 @@ pub struct Args {
     #[arg(long)]
     pub list_disks: bool,
 
+    /// List physical disks without the system-disk detection messages
+    #[arg(long, visible_alias = "dry-list")]
+    pub quiet_list: bool,
+
     /// System disk index (override auto-detection)
     #[arg(long)]
     pub system_disk: Option<u32>,
 
+    /// Show which disks are protected (system/boot/pagefile) and why
+    #[arg(long)]
+    pub show_protected: bool,
+
+    /// Locate pagefile.sys/hiberfil.sys and explain how to clear them
+    #[arg(long)]
+    pub wipe_pagefile: bool,
+
     /// Interactive disk wipe (non-system disks only)
     #[arg(long)]
     pub wipe_disk: bool,
+
+    /// Wipe every extent of a volume such as E:, on each disk it spans
+    #[arg(
+        long,
+        value_name = "VOLUME",
+        visible_alias = "volume",
+        conflicts_with_all = [
+            "offset",
+            "length",
+            "limit_bytes",
+            "stamp",
+            "reinit_mbr",
+            "pattern_verify",
+            "verify_samples",
+            "probe_addressing",
+            "trim_then_verify_unreadable",
+            "write_same",
+            "report_include_smart",
+            "interactive_confirm_details",
+        ]
+    )]
+    pub wipe_volume: Option<String>,
+
+    /// Overwrite only the free space of a volume such as C:, leaving its files intact
+    #[arg(long, value_name = "VOLUME")]
+    pub wipe_free: Option<String>,
+
+    /// Overwrite only the NTFS/FAT/exFAT metadata of a volume such as E:
+    /// (file data is NOT erased)
+    #[arg(long, value_name = "VOLUME")]
+    pub wipe_fs_metadata: Option<String>,
+
+    /// Refuse to wipe disks larger than --safe-limit
+    #[arg(long)]
+    pub safe_mode: bool,
+
+    /// Safe mode size limit, e.g. 4T or 500G
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "4T")]
+    pub safe_limit: u64,
+
+    /// Allow wiping disks above the safe mode limit
+    #[arg(long)]
+    pub allow_large: bool,
+
+    /// Never wipe the disk with this serial number (repeatable)
+    #[arg(long, value_name = "SERIAL")]
+    pub exclude_serial: Vec<String>,
+
+    /// Clear the sectors between the MBR and the first partition (use with --disk N)
+    #[arg(long, requires = "disk")]
+    pub wipe_mbr_gap: bool,
 }
 
 pub fn parse_args() -> Args {
-    Args::parse()
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let preset = match args.preset.clone() {
+        Some(name) => presets_path()
+            .and_then(|path| load_preset(&path, &name))
+            .map(Some)
+            .map_err(|e| format!("--preset {}: {}", name, e)),
+        None => Ok(None),
+    };
+    let defaults = preset.and_then(|preset| {
+        let get = |key: &str| env::var(key).ok();
+        apply_defaults(&mut args, &matches, preset.as_ref(), get)
+    });
+    if let Err(msg) = defaults {
+        Args::command().error(ErrorKind::InvalidValue, msg).exit();
+    }
+
+    let passes = effective_passes(args.mode, args.passes);
+    if passes != args.passes {
+        println!(
+            "Note: --mode {:?} {}; running {} instead of {}.",
+            args.mode,
+            pass_count_reason(args.mode),
+            passes,
+            args.passes
+        );
+        args.passes = passes;
+    }
+
+    if let Err(msg) = check_pass_count(&args) {
+        Args::command().error(ErrorKind::InvalidValue, msg).exit();
+    }
+
+    if args.operator.is_none() {
+        args.operator = current_username();
+    }
+
+    args
+}
+
+/// Reject formats with unknown or unclosed placeholders before any disk is opened.
+fn parse_nonce(s: &str) -> Result<u64, String> {
+    let hex = s.trim().trim_start_matches("0x");
+    u64::from_str_radix(hex, 16).map_err(|_| format!("'{}' is not a hex nonce", s))
+}
+
+fn parse_phrase_format(s: &str) -> Result<String, String> {
+    let sample = DiskIdentity {
+        model: Some(String::new()),
+        serial: Some(String::new()),
+        ..Default::default()
+    };
+    if s.trim().is_empty() {
+        return Err("the phrase format is empty".to_string());
+    }
+    expand_phrase(s, 0, &sample, 0)?;
+    Ok(s.to_string())
+}
+
+fn parse_notify_url(s: &str) -> Result<String, String> {
+    parse_http_url(s)?;
+    Ok(s.to_string())
+}
+
+/// One job's settings: the command line plus the job's own overrides.
+pub fn job_args(job: &Job, args: &Args) -> Args {
+    let mut a = args.clone();
+    a.mode = job.mode.unwrap_or(a.mode);
+    a.passes = effective_passes(a.mode, job.passes.unwrap_or(a.passes));
+    a.verify = job.verify.unwrap_or(a.verify);
+    a.report = None;
+    a
+}
+
+/// A typo like --passes 1000000 would run for years; show the scale and refuse.
+pub fn check_pass_count(args: &Args) -> Result<(), String> {
+    if args.passes <= args.max_passes || args.allow_excessive_passes {
+        return Ok(());
+    }
+
+    Err(format!(
+        "--passes {} is above --max-passes {}; at {} MB/s that is {} (h:m:s) per TiB. \
+         Pass --allow-excessive-passes if this is intended.",
+        args.passes,
+        args.max_passes,
+        ESTIMATE_RATE_MB_S,
+        format_eta(estimate_seconds(1 << 40, args.passes, ESTIMATE_RATE_MB_S))
+    ))
+}
+
+/// Fill in what the command line left out: the --preset first, then the
+/// WIPECORE_* environment on top, so flags > environment > preset.
+fn apply_defaults(
+    args: &mut Args,
+    matches: &ArgMatches,
+    preset: Option<&Preset>,
+    get: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    if let Some(preset) = preset {
+        apply_preset(args, matches, preset)?;
+    }
+    apply_env_defaults(args, matches, get)
+}
+
+/// WIPECORE_MODE / WIPECORE_PASSES / WIPECORE_BUFFER fill in any of
+/// --mode / --passes / --buffer-size that were not given on the command line.
+fn apply_env_defaults(
+    args: &mut Args,
+    matches: &ArgMatches,
+    get: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    if let Some(v) = get("WIPECORE_MODE").filter(|_| !from_cli("mode")) {
+        args.mode = WipeMode::from_str(v.trim(), true)
+            .map_err(|_| format!("WIPECORE_MODE: unknown mode '{}'", v))?;
+    }
+
+    if let Some(v) = get("WIPECORE_PASSES").filter(|_| !from_cli("passes")) {
+        args.passes = v
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&p: &u32| p > 0)
+            .ok_or_else(|| format!("WIPECORE_PASSES: '{}' is not a positive number", v))?;
+    }
+
+    if let Some(v) = get("WIPECORE_BUFFER").filter(|_| !from_cli("buffer_size")) {
+        args.buffer_size = parse_size(&v).map_err(|e| format!("WIPECORE_BUFFER: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Wipe-related flags as --save-preset stores them, keyed by flag id.
+pub fn preset_values(args: &Args) -> Preset {
+    let mut values = vec![
+        ("mode", value_name(&args.mode)),
+        ("passes", args.passes.to_string()),
+        ("buffer_size", args.buffer_size.to_string()),
+        ("verify", args.verify.to_string()),
+        ("pattern_verify", args.pattern_verify.to_string()),
+        ("verify_each", args.verify_each.to_string()),
+        ("on_verify_fail", value_name(&args.on_verify_fail)),
+        (
+            "trim_then_verify_unreadable",
+            args.trim_then_verify_unreadable.to_string(),
+        ),
+        ("stamp", args.stamp.to_string()),
+        ("safe_mode", args.safe_mode.to_string()),
+        ("safe_limit", args.safe_limit.to_string()),
+    ];
+    if let Some(n) = args.io_size {
+        values.push(("io_size", n.to_string()));
+    }
+    if let Some(n) = args.sync_every {
+        values.push(("sync_every", n.to_string()));
+    }
+
+    values
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+}
+
+fn value_name<T: ValueEnum>(v: &T) -> String {
+    v.to_possible_value()
+        .map(|p| p.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Fill in every preset flag that was not given on the command line.
+fn apply_preset(args: &mut Args, matches: &ArgMatches, preset: &Preset) -> Result<(), String> {
+    let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let flag = |v: &str| {
+        v.parse::<bool>()
+            .map_err(|_| format!("preset value '{}' is not true/false", v))
+    };
+
+    for (key, value) in preset.iter().filter(|(k, _)| !from_cli(k)) {
+        match key.as_str() {
+            "mode" => {
+                args.mode = WipeMode::from_str(value, true)
+                    .map_err(|_| format!("preset: unknown mode '{}'", value))?
+            }
+            "passes" => {
+                args.passes = value
+                    .parse()
+                    .ok()
+                    .filter(|&p: &u32| p > 0)
+                    .ok_or_else(|| format!("preset: '{}' is not a positive number", value))?
+            }
+            "buffer_size" => args.buffer_size = parse_size(value)?,
+            "io_size" => args.io_size = Some(parse_size(value)?),
+            "sync_every" => args.sync_every = Some(parse_size(value)?),
+            "verify" => args.verify = flag(value)?,
+            "pattern_verify" => args.pattern_verify = flag(value)?,
+            "verify_each" => args.verify_each = flag(value)?,
+            "on_verify_fail" => {
+                args.on_verify_fail = OnVerifyFail::from_str(value, true)
+                    .map_err(|_| format!("preset: unknown on_verify_fail '{}'", value))?
+            }
+            "trim_then_verify_unreadable" => args.trim_then_verify_unreadable = flag(value)?,
+            "stamp" => args.stamp = flag(value)?,
+            "safe_mode" => args.safe_mode = flag(value)?,
+            "safe_limit" => args.safe_limit = parse_size(value)?,
+            other => return Err(format!("preset: unknown setting '{}'", other)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(argv: &[&str]) -> (Args, ArgMatches) {
+        let matches = Args::command()
+            .try_get_matches_from(argv)
+            .expect("valid arguments");
+        let args = Args::from_arg_matches(&matches).expect("valid arguments");
+        (args, matches)
+    }
+
+    fn preset(pairs: &[(&str, &str)]) -> Preset {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn flags_beat_environment_beats_preset() {
+        let (mut args, matches) = parse(&["wipecore", "--buffer-size", "1M"]);
+        let saved = preset(&[("mode", "zeros"), ("passes", "3"), ("buffer_size", "4M")]);
+        let env = |key: &str| (key == "WIPECORE_PASSES").then(|| "5".to_string());
+
+        apply_defaults(&mut args, &matches, Some(&saved), env).unwrap();
+
+        assert!(matches!(args.mode, WipeMode::Zeros));
+        assert_eq!(args.passes, 5);
+        assert_eq!(args.buffer_size, 1024 * 1024);
+    }
+
+    #[test]
+    fn zero_passes_is_rejected_everywhere() {
+        let cli = Args::command().try_get_matches_from(["wipecore", "--passes", "0"]);
+        assert!(cli.is_err());
+
+        let (mut args, matches) = parse(&["wipecore"]);
+        let env = |key: &str| (key == "WIPECORE_PASSES").then(|| "0".to_string());
+        assert!(apply_env_defaults(&mut args, &matches, env).is_err());
+
+        let saved = preset(&[("passes", "0")]);
+        assert!(apply_preset(&mut args, &matches, &saved).is_err());
+    }
+
+    fn job(passes: u32) -> Job {
+        Job {
+            target: "image.bin".to_string(),
+            mode: Some(WipeMode::Random),
+            passes: Some(passes),
+            verify: None,
+            report: None,
+        }
+    }
+
+    #[test]
+    fn pass_count_above_the_cap_needs_the_override() {
+        let (args, _) = parse(&["wipecore", "--passes", "1001"]);
+        let err = check_pass_count(&args).unwrap_err();
+        assert!(err.contains("--allow-excessive-passes"));
+
+        let (args, _) = parse(&["wipecore", "--passes", "1001", "--allow-excessive-passes"]);
+        assert!(check_pass_count(&args).is_ok());
+    }
+
+    #[test]
+    fn job_passes_are_held_to_the_same_cap() {
+        let (args, _) = parse(&["wipecore", "--max-passes", "10"]);
+        assert!(check_pass_count(&job_args(&job(10), &args)).is_ok());
+        assert!(check_pass_count(&job_args(&job(11), &args)).is_err());
+
+        let (args, _) = parse(&["wipecore", "--max-passes", "10", "--allow-excessive-passes"]);
+        assert_eq!(job_args(&job(11), &args).passes, 11);
+        assert!(check_pass_count(&job_args(&job(11), &args)).is_ok());
+    }
+
+    #[test]
+    fn jobs_raise_secureflip_like_the_engine() {
+        let (args, _) = parse(&["wipecore"]);
+        let job = Job {
+            mode: Some(WipeMode::Secureflip),
+            ..job(1)
+        };
+        let a = job_args(&job, &args);
+        assert_eq!(a.passes, effective_passes(WipeMode::Secureflip, 1));
+        assert_eq!(a.passes, 2);
+    }
 }
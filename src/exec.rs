@@ -0,0 +1,61 @@
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::process::{Command, ExitStatus};
+
+use crate::report::Report;
+
+/// --exec-after: a command run through `cmd /C` once a target is done.
+#[derive(Clone, Debug)]
+pub struct ExecAfter {
+    pub command: String,
+    /// Also run after a failed wipe (--exec-always)
+    pub always: bool,
+}
+
+impl ExecAfter {
+    /// Run the command for `target` (`error` is None on success) and record
+    /// its exit status in `report`. Skipped for failures unless `always`.
+    pub fn run(&self, target: &str, error: Option<&str>, report: &mut Report) {
+        if error.is_some() && !self.always {
+            println!("Note: --exec-after skipped, the wipe of {} failed.", target);
+            return;
+        }
+
+        println!("[*] Running --exec-after for {}", target);
+        let outcome = match run_command(&self.command, target, error) {
+            Ok(status) if status.success() => "exit code 0".to_string(),
+            Ok(status) => match status.code() {
+                Some(code) => format!("FAILED (exit code {})", code),
+                None => "FAILED (no exit code)".to_string(),
+            },
+            Err(e) => format!("FAILED (could not start: {})", e),
+        };
+        if outcome.starts_with("FAILED") {
+            eprintln!("Warning: --exec-after for {}: {}", target, outcome);
+        }
+
+        report.add(
+            "Post-wipe command",
+            format!("{} -> {}", self.command, outcome),
+        );
+    }
+}
+
+/// The target and outcome only ever travel as environment variables, never
+/// spliced into the command line, so a file name can't inject cmd syntax.
+fn run_command(command: &str, target: &str, error: Option<&str>) -> io::Result<ExitStatus> {
+    let status = if error.is_some() {
+        "failed"
+    } else {
+        "completed"
+    };
+
+    Command::new("cmd")
+        .arg("/C")
+        // handed to cmd verbatim; Rust's quoting rules don't match cmd's
+        .raw_arg(command)
+        .env("WIPECORE_TARGET", target)
+        .env("WIPECORE_STATUS", status)
+        .env("WIPECORE_ERROR", error.unwrap_or(""))
+        .status()
+}
@@ -0,0 +1,513 @@
+use std::io;
+
+/// Boot sector bytes needed to identify the filesystem.
+pub const BOOT_SECTOR_LEN: usize = 512;
+
+/// Filesystems whose metadata --wipe-fs-metadata knows how to find.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FsKind {
+    Ntfs,
+    Fat,
+    ExFat,
+}
+
+/// A byte range of the volume, relative to its first sector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetaRegion {
+    pub offset: u64,
+    pub len: u64,
+    pub what: &'static str,
+}
+
+/// Metadata layout read from a boot sector.
+#[derive(Clone, Debug)]
+pub struct FsLayout {
+    pub kind: FsKind,
+    pub regions: Vec<MetaRegion>,
+    /// (offset, len) to read next: NTFS $MFT record 0, exFAT root directory
+    pub follow_up: Option<(u64, u64)>,
+    cluster_size: u64,
+    /// exFAT: byte offset of cluster 2
+    heap_offset: u64,
+}
+
+fn le(b: &[u8], at: usize, n: usize) -> u64 {
+    let mut v = [0u8; 8];
+    v[..n].copy_from_slice(&b[at..at + n]);
+    u64::from_le_bytes(v)
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Identify NTFS / FAT12/16/32 / exFAT from sector 0 of a volume of `volume_len`
+/// bytes and list the metadata regions that can be located from it alone.
+pub fn parse_boot_sector(boot: &[u8], volume_len: u64) -> io::Result<FsLayout> {
+    if boot.len() < BOOT_SECTOR_LEN {
+        return Err(invalid("boot sector is shorter than 512 bytes"));
+    }
+
+    match &boot[3..11] {
+        b"NTFS    " => parse_ntfs(boot, volume_len),
+        b"EXFAT   " => parse_exfat(boot),
+        _ if &boot[0x36..0x39] == b"FAT" || &boot[0x52..0x57] == b"FAT32" => parse_fat(boot),
+        _ => Err(invalid("not an NTFS, FAT or exFAT boot sector")),
+    }
+}
+
+fn parse_ntfs(boot: &[u8], volume_len: u64) -> io::Result<FsLayout> {
+    let bps = le(boot, 0x0B, 2);
+    let spc = boot[0x0D] as u64;
+    // values above 128 encode 2^(256 - n) sectors per cluster
+    let cluster = if spc > 128 {
+        bps << (256 - spc)
+    } else {
+        bps * spc
+    };
+    if bps == 0 || cluster == 0 {
+        return Err(invalid(
+            "NTFS boot sector has a zero sector or cluster size",
+        ));
+    }
+
+    let total_sectors = le(boot, 0x28, 8);
+    let mft = le(boot, 0x30, 8) * cluster;
+    let mirror = le(boot, 0x38, 8) * cluster;
+    let per_record = boot[0x40] as i8;
+    let record = if per_record > 0 {
+        per_record as u64 * cluster
+    } else {
+        1u64 << (-(per_record as i32))
+    };
+
+    // the backup boot sector sits just past the sectors NTFS reports
+    let backup = (total_sectors * bps).min(volume_len.saturating_sub(bps));
+
+    Ok(FsLayout {
+        kind: FsKind::Ntfs,
+        regions: vec![
+            MetaRegion {
+                offset: 0,
+                len: bps,
+                what: "boot sector",
+            },
+            MetaRegion {
+                offset: backup,
+                len: bps,
+                what: "backup boot sector",
+            },
+            MetaRegion {
+                offset: mirror,
+                len: 4 * record,
+                what: "$MFTMirr",
+            },
+        ],
+        follow_up: Some((mft, record)),
+        cluster_size: cluster,
+        heap_offset: 0,
+    })
+}
+
+fn parse_fat(boot: &[u8]) -> io::Result<FsLayout> {
+    let bps = le(boot, 0x0B, 2);
+    let cluster = bps * boot[0x0D] as u64;
+    let reserved = le(boot, 0x0E, 2) * bps;
+    let fats = boot[0x10] as u64;
+    let root_entries = le(boot, 0x11, 2);
+    let fat_sectors = match le(boot, 0x16, 2) {
+        0 => le(boot, 0x24, 4),
+        n => n,
+    };
+    if bps == 0 || cluster == 0 || fats == 0 || fat_sectors == 0 {
+        return Err(invalid("FAT boot sector has a zero size field"));
+    }
+
+    let fat_end = reserved + fats * fat_sectors * bps;
+    let root = if root_entries > 0 {
+        // FAT12/16: fixed root directory right after the FATs
+        MetaRegion {
+            offset: fat_end,
+            len: (root_entries * 32).div_ceil(bps) * bps,
+            what: "root directory",
+        }
+    } else {
+        // FAT32: the root directory starts at a cluster in the data area
+        let root_cluster = le(boot, 0x2C, 4).max(2);
+        MetaRegion {
+            offset: fat_end + (root_cluster - 2) * cluster,
+            len: cluster,
+            what: "root directory",
+        }
+    };
+
+    Ok(FsLayout {
+        kind: FsKind::Fat,
+        regions: vec![
+            MetaRegion {
+                offset: 0,
+                len: reserved,
+                what: "boot + reserved sectors",
+            },
+            MetaRegion {
+                offset: reserved,
+                len: fat_end - reserved,
+                what: "FAT tables",
+            },
+            root,
+        ],
+        follow_up: None,
+        cluster_size: cluster,
+        heap_offset: 0,
+    })
+}
+
+fn parse_exfat(boot: &[u8]) -> io::Result<FsLayout> {
+    let bps_shift = boot[0x6C] as u32;
+    let spc_shift = boot[0x6D] as u32;
+    if !(9..=12).contains(&bps_shift) || bps_shift + spc_shift > 25 {
+        return Err(invalid(
+            "exFAT boot sector has an invalid sector or cluster size",
+        ));
+    }
+    let bps = 1u64 << bps_shift;
+    let cluster = bps << spc_shift;
+
+    let fat_offset = le(boot, 0x50, 4) * bps;
+    let fat_len = le(boot, 0x54, 4) * bps * (boot[0x6E] as u64).max(1);
+    let heap = le(boot, 0x58, 4) * bps;
+    let root = heap + (le(boot, 0x60, 4).max(2) - 2) * cluster;
+
+    Ok(FsLayout {
+        kind: FsKind::ExFat,
+        regions: vec![
+            // main and backup boot regions are 12 sectors each
+            MetaRegion {
+                offset: 0,
+                len: 24 * bps,
+                what: "boot regions",
+            },
+            MetaRegion {
+                offset: fat_offset,
+                len: fat_len,
+                what: "FAT",
+            },
+            MetaRegion {
+                offset: root,
+                len: cluster,
+                what: "root directory",
+            },
+        ],
+        follow_up: Some((root, cluster)),
+        cluster_size: cluster,
+        heap_offset: heap,
+    })
+}
+
+/// Regions found in the `follow_up` bytes: every run of the NTFS $MFT, or the
+/// exFAT allocation bitmap named in the root directory.
+pub fn follow_up_regions(layout: &FsLayout, data: &[u8]) -> io::Result<Vec<MetaRegion>> {
+    match layout.kind {
+        FsKind::Ntfs => mft_runs(data, layout.cluster_size),
+        FsKind::ExFat => exfat_bitmap(data, layout),
+        FsKind::Fat => Ok(Vec::new()),
+    }
+}
+
+/// Runs of $MFT's unnamed $DATA attribute, from MFT record 0.
+fn mft_runs(record: &[u8], cluster: u64) -> io::Result<Vec<MetaRegion>> {
+    if record.len() < 0x30 || &record[0..4] != b"FILE" {
+        return Err(invalid("$MFT record 0 has no FILE signature"));
+    }
+
+    // undo the update sequence: the last two bytes of every 512-byte stride
+    let mut rec = record.to_vec();
+    let usa = le(&rec, 4, 2) as usize;
+    let usa_count = le(&rec, 6, 2) as usize;
+    for i in 1..usa_count {
+        let (src, dst) = (usa + i * 2, i * 512 - 2);
+        if src + 2 > rec.len() || dst + 2 > rec.len() {
+            break;
+        }
+        let fixed = [rec[src], rec[src + 1]];
+        rec[dst..dst + 2].copy_from_slice(&fixed);
+    }
+
+    let mut at = le(&rec, 0x14, 2) as usize;
+    while at + 0x28 <= rec.len() {
+        let kind = le(&rec, at, 4);
+        let len = le(&rec, at + 4, 4) as usize;
+        if kind == 0xFFFF_FFFF || len == 0 || at + len > rec.len() {
+            break;
+        }
+        // non-resident $DATA
+        if kind == 0x80 && rec[at + 8] == 1 {
+            let runs = at + le(&rec, at + 0x20, 2) as usize;
+            return decode_runs(&rec[runs.min(at + len)..at + len], cluster);
+        }
+        at += len;
+    }
+
+    Err(invalid("$MFT record 0 has no non-resident $DATA attribute"))
+}
+
+/// Decode an NTFS mapping-pairs run list into absolute byte ranges.
+fn decode_runs(runs: &[u8], cluster: u64) -> io::Result<Vec<MetaRegion>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let mut lcn: i64 = 0;
+
+    while let Some(&header) = runs.get(pos) {
+        if header == 0 {
+            break;
+        }
+        let (len_size, off_size) = ((header & 0x0F) as usize, (header >> 4) as usize);
+        if len_size == 0
+            || len_size > 8
+            || off_size > 8
+            || pos + 1 + len_size + off_size > runs.len()
+        {
+            return Err(invalid("corrupt $MFT run list"));
+        }
+
+        let length = le(runs, pos + 1, len_size);
+        pos += 1 + len_size;
+        if off_size == 0 {
+            // sparse run: nothing on disk
+            continue;
+        }
+
+        // the offset is signed and relative to the previous run
+        let raw = le(runs, pos, off_size);
+        let shift = 64 - 8 * off_size as u32;
+        lcn += ((raw << shift) as i64) >> shift;
+        pos += off_size;
+
+        out.push(MetaRegion {
+            offset: lcn as u64 * cluster,
+            len: length * cluster,
+            what: "$MFT",
+        });
+    }
+
+    Ok(out)
+}
+
+/// The allocation bitmap entry (type 0x81) in the exFAT root directory.
+fn exfat_bitmap(root: &[u8], layout: &FsLayout) -> io::Result<Vec<MetaRegion>> {
+    for entry in root.chunks_exact(32) {
+        match entry[0] {
+            0x00 => break,
+            0x81 => {
+                let first = le(entry, 20, 4).max(2);
+                return Ok(vec![MetaRegion {
+                    offset: layout.heap_offset + (first - 2) * layout.cluster_size,
+                    len: le(entry, 24, 8),
+                    what: "allocation bitmap",
+                }]);
+            }
+            _ => {}
+        }
+    }
+
+    Err(invalid(
+        "exFAT root directory has no allocation bitmap entry",
+    ))
+}
+
+/// Grow a region to whole sectors and clip it to the volume.
+pub fn align_region(r: &MetaRegion, sector: u64, volume_len: u64) -> (u64, u64) {
+    let start = r.offset / sector * sector;
+    let end = (r.offset + r.len).div_ceil(sector) * sector;
+    let end = end.min(volume_len);
+    (start, end.saturating_sub(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(b: &mut [u8], at: usize, bytes: &[u8]) {
+        b[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn region(offset: u64, len: u64, what: &'static str) -> MetaRegion {
+        MetaRegion { offset, len, what }
+    }
+
+    /// 512-byte sectors, 8 per cluster, 2048 sectors, $MFT at cluster 4,
+    /// $MFTMirr at cluster 100, 1 KiB records.
+    fn ntfs_boot() -> Vec<u8> {
+        let mut b = vec![0u8; BOOT_SECTOR_LEN];
+        put(&mut b, 3, b"NTFS    ");
+        put(&mut b, 0x0B, &512u16.to_le_bytes());
+        b[0x0D] = 8;
+        put(&mut b, 0x28, &2047u64.to_le_bytes());
+        put(&mut b, 0x30, &4u64.to_le_bytes());
+        put(&mut b, 0x38, &100u64.to_le_bytes());
+        b[0x40] = (-10i8) as u8;
+        b
+    }
+
+    #[test]
+    fn ntfs_boot_sector() {
+        let layout = parse_boot_sector(&ntfs_boot(), 2048 * 512).unwrap();
+        assert_eq!(layout.kind, FsKind::Ntfs);
+        assert_eq!(
+            layout.regions,
+            vec![
+                region(0, 512, "boot sector"),
+                region(2047 * 512, 512, "backup boot sector"),
+                region(100 * 4096, 4 * 1024, "$MFTMirr"),
+            ]
+        );
+        // $MFT starts at cluster 4: record 0 is read from there
+        assert_eq!(layout.follow_up, Some((4 * 4096, 1024)));
+    }
+
+    #[test]
+    fn ntfs_mft_runs() {
+        let layout = parse_boot_sector(&ntfs_boot(), 2048 * 512).unwrap();
+        let mut rec = vec![0u8; 1024];
+        put(&mut rec, 0, b"FILE");
+        put(&mut rec, 4, &0x30u16.to_le_bytes());
+        put(&mut rec, 6, &3u16.to_le_bytes());
+        put(&mut rec, 0x14, &0x38u16.to_le_bytes());
+        // resident $STANDARD_INFORMATION, then the non-resident $DATA
+        put(&mut rec, 0x38, &0x10u32.to_le_bytes());
+        put(&mut rec, 0x3C, &0x60u32.to_le_bytes());
+        put(&mut rec, 0x98, &0x80u32.to_le_bytes());
+        put(&mut rec, 0x9C, &0x50u32.to_le_bytes());
+        rec[0xA0] = 1;
+        put(&mut rec, 0xB8, &0x40u16.to_le_bytes());
+        // 16 clusters at LCN 4, 8 at +32 (36), 2 at -16 (20)
+        put(
+            &mut rec,
+            0xD8,
+            &[
+                0x21, 0x10, 0x04, 0x00, 0x11, 0x08, 0x20, 0x11, 0x02, 0xF0, 0x00,
+            ],
+        );
+        put(&mut rec, 0xE8, &0xFFFF_FFFFu32.to_le_bytes());
+
+        assert_eq!(
+            follow_up_regions(&layout, &rec).unwrap(),
+            vec![
+                region(4 * 4096, 16 * 4096, "$MFT"),
+                region(36 * 4096, 8 * 4096, "$MFT"),
+                region(20 * 4096, 2 * 4096, "$MFT"),
+            ]
+        );
+
+        rec[0] = b'B';
+        assert!(follow_up_regions(&layout, &rec).is_err());
+    }
+
+    #[test]
+    fn fat16_boot_sector() {
+        let mut b = vec![0u8; BOOT_SECTOR_LEN];
+        put(&mut b, 0x0B, &512u16.to_le_bytes());
+        b[0x0D] = 4;
+        put(&mut b, 0x0E, &1u16.to_le_bytes());
+        b[0x10] = 2;
+        put(&mut b, 0x11, &512u16.to_le_bytes());
+        put(&mut b, 0x16, &200u16.to_le_bytes());
+        put(&mut b, 0x36, b"FAT16   ");
+
+        let layout = parse_boot_sector(&b, 1 << 30).unwrap();
+        assert_eq!(layout.kind, FsKind::Fat);
+        assert_eq!(
+            layout.regions,
+            vec![
+                region(0, 512, "boot + reserved sectors"),
+                region(512, 2 * 200 * 512, "FAT tables"),
+                region(512 + 2 * 200 * 512, 512 * 32, "root directory"),
+            ]
+        );
+        assert_eq!(layout.follow_up, None);
+    }
+
+    #[test]
+    fn fat32_boot_sector() {
+        let mut b = vec![0u8; BOOT_SECTOR_LEN];
+        put(&mut b, 0x0B, &512u16.to_le_bytes());
+        b[0x0D] = 8;
+        put(&mut b, 0x0E, &32u16.to_le_bytes());
+        b[0x10] = 2;
+        put(&mut b, 0x24, &1000u32.to_le_bytes());
+        put(&mut b, 0x2C, &3u32.to_le_bytes());
+        put(&mut b, 0x52, b"FAT32   ");
+
+        let layout = parse_boot_sector(&b, 1 << 30).unwrap();
+        let fat_end = 32 * 512 + 2 * 1000 * 512;
+        assert_eq!(
+            layout.regions,
+            vec![
+                region(0, 32 * 512, "boot + reserved sectors"),
+                region(32 * 512, 2 * 1000 * 512, "FAT tables"),
+                region(fat_end + 4096, 4096, "root directory"),
+            ]
+        );
+    }
+
+    #[test]
+    fn exfat_boot_sector_and_bitmap() {
+        let mut b = vec![0u8; BOOT_SECTOR_LEN];
+        put(&mut b, 3, b"EXFAT   ");
+        put(&mut b, 0x50, &128u32.to_le_bytes());
+        put(&mut b, 0x54, &64u32.to_le_bytes());
+        put(&mut b, 0x58, &1024u32.to_le_bytes());
+        put(&mut b, 0x60, &5u32.to_le_bytes());
+        b[0x6C] = 9;
+        b[0x6D] = 3;
+        b[0x6E] = 1;
+
+        let layout = parse_boot_sector(&b, 1 << 30).unwrap();
+        let heap = 1024 * 512;
+        let root = heap + 3 * 4096;
+        assert_eq!(layout.kind, FsKind::ExFat);
+        assert_eq!(
+            layout.regions,
+            vec![
+                region(0, 24 * 512, "boot regions"),
+                region(128 * 512, 64 * 512, "FAT"),
+                region(root, 4096, "root directory"),
+            ]
+        );
+        assert_eq!(layout.follow_up, Some((root, 4096)));
+
+        // a volume label entry, then the allocation bitmap at cluster 2
+        let mut dir = vec![0u8; 4096];
+        dir[0] = 0x83;
+        dir[32] = 0x81;
+        put(&mut dir, 32 + 20, &2u32.to_le_bytes());
+        put(&mut dir, 32 + 24, &1000u64.to_le_bytes());
+        assert_eq!(
+            follow_up_regions(&layout, &dir).unwrap(),
+            vec![region(heap, 1000, "allocation bitmap")]
+        );
+        assert!(follow_up_regions(&layout, &[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn unknown_or_short_boot_sectors_are_refused() {
+        assert!(parse_boot_sector(&[0u8; BOOT_SECTOR_LEN], 1 << 30).is_err());
+        assert!(parse_boot_sector(&ntfs_boot()[..100], 1 << 30).is_err());
+    }
+
+    #[test]
+    fn align_region_grows_to_sectors_and_clips() {
+        assert_eq!(
+            align_region(&region(1000, 100, "x"), 512, 1 << 20),
+            (512, 1024)
+        );
+        assert_eq!(
+            align_region(&region(4000, 1000, "x"), 4096, 8192),
+            (0, 8192)
+        );
+        assert_eq!(
+            align_region(&region(8000, 1000, "x"), 4096, 8192),
+            (4096, 4096)
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use std::io;
+
+use crate::util::size_format;
+
+// STORAGE_DEVICE_DESCRIPTOR field offsets (winioctl.h)
+const DESC_REMOVABLE: usize = 10;
+const DESC_PRODUCT_OFFSET: usize = 16;
+const DESC_REVISION_OFFSET: usize = 20;
+const DESC_SERIAL_OFFSET: usize = 24;
+const DESC_BUS_TYPE: usize = 28;
+const DESC_MIN_SIZE: usize = 36;
+
+// STORAGE_ACCESS_ALIGNMENT_DESCRIPTOR: BytesPerPhysicalSector
+const ALIGN_PHYSICAL_SECTOR: usize = 20;
+
+/// BusTypeNvme in STORAGE_BUS_TYPE.
+const BUS_TYPE_NVME: u32 = 17;
+
+// STORAGE_DEVICE_ID_DESCRIPTOR / STORAGE_IDENTIFIER layout (winioctl.h)
+const ID_DESC_COUNT: usize = 8;
+const ID_DESC_FIRST: usize = 12;
+const IDENT_SIZE: usize = 8;
+const IDENT_NEXT: usize = 10;
+const IDENT_DATA: usize = 16;
+
+// STORAGE_IDENTIFIER_CODE_SET / STORAGE_IDENTIFIER_TYPE values
+const ID_CODE_SET_BINARY: u32 = 1;
+const ID_TYPE_EUI64: u32 = 2;
+const ID_TYPE_FCPH_NAME: u32 = 3;
+
+/// Identity strings reported by IOCTL_STORAGE_QUERY_PROPERTY (StorageDeviceProperty).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiskIdentity {
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub firmware: Option<String>,
+    /// STORAGE_BUS_TYPE value
+    pub bus_type: u32,
+    /// RemovableMedia flag of the descriptor
+    pub removable: bool,
+}
+
+/// Parse a raw STORAGE_DEVICE_DESCRIPTOR buffer.
+pub fn parse_device_descriptor(buf: &[u8]) -> io::Result<DiskIdentity> {
+    if buf.len() < DESC_MIN_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "STORAGE_DEVICE_DESCRIPTOR is truncated",
+        ));
+    }
+
+    Ok(DiskIdentity {
+        model: descriptor_string(buf, read_u32(buf, DESC_PRODUCT_OFFSET)),
+        serial: descriptor_string(buf, read_u32(buf, DESC_SERIAL_OFFSET)),
+        firmware: descriptor_string(buf, read_u32(buf, DESC_REVISION_OFFSET)),
+        bus_type: read_u32(buf, DESC_BUS_TYPE),
+        removable: buf[DESC_REMOVABLE] != 0,
+    })
+}
+
+/// Physical sector size from a raw STORAGE_ACCESS_ALIGNMENT_DESCRIPTOR buffer.
+pub fn parse_physical_sector_size(buf: &[u8]) -> io::Result<u32> {
+    if buf.len() < ALIGN_PHYSICAL_SECTOR + 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "STORAGE_ACCESS_ALIGNMENT_DESCRIPTOR is truncated",
+        ));
+    }
+    Ok(read_u32(buf, ALIGN_PHYSICAL_SECTOR))
+}
+
+/// True if the serial matches any --exclude-serial entry (case/space insensitive).
+pub fn is_excluded(serial: Option<&str>, excludes: &[String]) -> bool {
+    match serial {
+        Some(s) => excludes
+            .iter()
+            .any(|e| e.trim().eq_ignore_ascii_case(s.trim())),
+        None => false,
+    }
+}
+
+/// One binary identifier from a STORAGE_DEVICE_ID_DESCRIPTOR.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceId {
+    /// STORAGE_IDENTIFIER_TYPE value
+    pub id_type: u32,
+    /// Identifier bytes as lowercase hex, in the order the device reports them
+    pub hex: String,
+}
+
+/// Parse a raw STORAGE_DEVICE_ID_DESCRIPTOR (StorageDeviceIdProperty) buffer.
+/// Only binary identifiers are kept; vendor ASCII strings are not stable IDs.
+pub fn parse_device_ids(buf: &[u8]) -> io::Result<Vec<DeviceId>> {
+    if buf.len() < ID_DESC_FIRST {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "STORAGE_DEVICE_ID_DESCRIPTOR is truncated",
+        ));
+    }
+
+    let mut ids = Vec::new();
+    let mut at = ID_DESC_FIRST;
+    for _ in 0..read_u32(buf, ID_DESC_COUNT) {
+        if at + IDENT_DATA > buf.len() {
+            break;
+        }
+        let len = read_u16(buf, at + IDENT_SIZE) as usize;
+        let data = buf.get(at + IDENT_DATA..at + IDENT_DATA + len);
+        if let (ID_CODE_SET_BINARY, Some(data)) = (read_u32(buf, at), data) {
+            ids.push(DeviceId {
+                id_type: read_u32(buf, at + 4),
+                hex: data.iter().map(|b| format!("{:02x}", b)).collect(),
+            });
+        }
+
+        let next = read_u16(buf, at + IDENT_NEXT) as usize;
+        if next == 0 {
+            break;
+        }
+        at += next;
+    }
+    Ok(ids)
+}
+
+/// Hex digits of a WWN or GUID as typed: "naa.", "eui.", "0x", braces,
+/// dashes, colons and spaces are dropped and the rest lowercased.
+pub fn normalize_device_id(s: &str) -> String {
+    let lower = s.trim().to_ascii_lowercase();
+    let rest = ["naa.", "eui.", "0x"]
+        .iter()
+        .find_map(|p| lower.strip_prefix(p))
+        .unwrap_or(&lower);
+    rest.chars()
+        .filter(|c| !matches!(c, '{' | '}' | '-' | ':' | ' '))
+        .collect()
+}
+
+/// --device-wwn: an NAA (FC-PH name) or EUI-64 identifier with these digits.
+pub fn matches_wwn(ids: &[DeviceId], wwn: &str) -> bool {
+    let want = normalize_device_id(wwn);
+    let wwn_type = |t: u32| matches!(t, ID_TYPE_EUI64 | ID_TYPE_FCPH_NAME);
+    !want.is_empty() && ids.iter().any(|id| wwn_type(id.id_type) && id.hex == want)
+}
+
+/// --device-guid: any 16-byte identifier (e.g. an NVMe NGUID) with these
+/// digits, compared in the byte order the device reports.
+pub fn matches_guid(ids: &[DeviceId], guid: &str) -> bool {
+    let want = normalize_device_id(guid);
+    want.len() == 32 && ids.iter().any(|id| id.hex == want)
+}
+
+/// Name for a STORAGE_BUS_TYPE value.
+pub fn bus_type_name(bus_type: u32) -> &'static str {
+    match bus_type {
+        1 => "SCSI",
+        2 => "ATAPI",
+        3 => "ATA",
+        4 => "1394",
+        5 => "SSA",
+        6 => "Fibre Channel",
+        7 => "USB",
+        8 => "RAID",
+        9 => "iSCSI",
+        10 => "SAS",
+        11 => "SATA",
+        12 => "SD",
+        13 => "MMC",
+        14 => "Virtual",
+        15 => "File-backed virtual",
+        16 => "Storage Spaces",
+        BUS_TYPE_NVME => "NVMe",
+        18 => "SCM",
+        19 => "UFS",
+        _ => "unknown",
+    }
+}
+
+/// Internal (non-removable) NVMe disks are the likeliest to hold something
+/// important without being the system disk, so wiping one takes the full serial.
+pub fn needs_serial_interlock(id: &DiskIdentity) -> bool {
+    id.bus_type == BUS_TYPE_NVME && !id.removable
+}
+
+/// The serial as typed must match exactly, apart from surrounding spaces.
+pub fn serial_matches(typed: &str, serial: &str) -> bool {
+    !serial.trim().is_empty() && typed.trim() == serial.trim()
+}
+
+/// How the disk now at an index differs from the one listed there, if at all.
+pub fn identity_mismatch(listed: &DiskIdentity, now: &DiskIdentity) -> Option<String> {
+    let show = |v: &Option<String>| v.clone().unwrap_or_else(|| "none".to_string());
+    if listed.serial != now.serial {
+        return Some(format!(
+            "serial {} is now {}",
+            show(&listed.serial),
+            show(&now.serial)
+        ));
+    }
+    if listed.model != now.model {
+        return Some(format!(
+            "model {} is now {}",
+            show(&listed.model),
+            show(&now.model)
+        ));
+    }
+    if listed.bus_type != now.bus_type {
+        return Some(format!(
+            "bus {} is now {}",
+            bus_type_name(listed.bus_type),
+            bus_type_name(now.bus_type)
+        ));
+    }
+    None
+}
+
+/// Placeholders understood by --confirm-phrase-format.
+pub const PHRASE_FIELDS: [&str; 4] = ["index", "serial", "model", "size_gb"];
+
+/// Expand `{index}`, `{serial}`, `{model}` and `{size_gb}` (decimal GB) in a
+/// confirmation phrase format. Fails on an unknown placeholder or on a field
+/// this disk doesn't report, since the operator could never type it.
+pub fn expand_phrase(
+    phrase_format: &str,
+    index: u32,
+    id: &DiskIdentity,
+    size: u64,
+) -> Result<String, String> {
+    let missing = |field: &str| format!("the disk reports no {} for {{{}}}", field, field);
+    let mut out = String::new();
+    let mut rest = phrase_format;
+
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in phrase format '{}'", phrase_format))?;
+        let value = match &rest[open + 1..open + close] {
+            "index" => index.to_string(),
+            "serial" => id.serial.clone().ok_or_else(|| missing("serial"))?,
+            "model" => id.model.clone().ok_or_else(|| missing("model"))?,
+            "size_gb" => (size / 1_000_000_000).to_string(),
+            other => {
+                return Err(format!(
+                    "unknown placeholder {{{}}} (use {})",
+                    other,
+                    PHRASE_FIELDS.map(|f| format!("{{{}}}", f)).join(", ")
+                ))
+            }
+        };
+        out.push_str(&value);
+        rest = &rest[open + close + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Everything known about a disk, shown before the phrase with --interactive-confirm-details.
+pub fn confirm_details(index: u32, id: &DiskIdentity, sector_size: u64, size: u64) -> String {
+    let unknown = "not reported";
+    let rows = [
+        ("Model", id.model.as_deref().unwrap_or(unknown).to_string()),
+        (
+            "Serial",
+            id.serial.as_deref().unwrap_or(unknown).to_string(),
+        ),
+        (
+            "Firmware",
+            id.firmware.as_deref().unwrap_or(unknown).to_string(),
+        ),
+        ("Bus type", bus_type_name(id.bus_type).to_string()),
+        ("Sector size", format!("{} bytes", sector_size)),
+        ("Total sectors", (size / sector_size.max(1)).to_string()),
+        (
+            "Capacity",
+            format!("{} ({} bytes)", size_format(size), size),
+        ),
+    ];
+
+    let mut out = format!("Disk details (PhysicalDrive{}):\n", index);
+    for (label, value) in rows {
+        out.push_str(&format!("  {:<13} : {}\n", label, value));
+    }
+    out
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn read_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+/// NUL-terminated ASCII at `offset`; 0 (or out of range) means not reported.
+fn descriptor_string(buf: &[u8], offset: u32) -> Option<String> {
+    let start = offset as usize;
+    if start == 0 || start >= buf.len() {
+        return None;
+    }
+
+    let end = buf[start..]
+        .iter()
+        .position(|&b| b == 0)
+        .map(|p| start + p)
+        .unwrap_or(buf.len());
+
+    let s = String::from_utf8_lossy(&buf[start..end]).trim().to_string();
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excluded_serials_ignore_case_and_spaces() {
+        let excludes = vec!["WD-123ABC".to_string(), " s9xnj0 ".to_string()];
+        assert!(is_excluded(Some("wd-123abc"), &excludes));
+        assert!(is_excluded(Some("S9XNJ0  "), &excludes));
+        assert!(!is_excluded(Some("WD-123AB"), &excludes));
+        assert!(!is_excluded(None, &excludes));
+        assert!(!is_excluded(Some("WD-123ABC"), &[]));
+    }
+
+    fn disk(serial: &str, model: &str, bus_type: u32) -> DiskIdentity {
+        DiskIdentity {
+            model: Some(model.to_string()),
+            serial: Some(serial.to_string()),
+            firmware: None,
+            bus_type,
+            removable: false,
+        }
+    }
+
+    #[test]
+    fn identity_mismatch_names_what_changed() {
+        let listed = disk("WD-123", "WDC WD40", 11);
+        assert_eq!(identity_mismatch(&listed, &listed.clone()), None);
+
+        let swapped = disk("S9X", "Samsung 990", 17);
+        assert_eq!(
+            identity_mismatch(&listed, &swapped).unwrap(),
+            "serial WD-123 is now S9X"
+        );
+
+        let rebadged = DiskIdentity {
+            model: None,
+            ..listed.clone()
+        };
+        assert_eq!(
+            identity_mismatch(&listed, &rebadged).unwrap(),
+            "model WDC WD40 is now none"
+        );
+
+        let moved = disk("WD-123", "WDC WD40", 7);
+        assert_eq!(
+            identity_mismatch(&listed, &moved).unwrap(),
+            "bus SATA is now USB"
+        );
+    }
+
+    /// STORAGE_DEVICE_ID_DESCRIPTOR holding the given (code set, type, bytes)
+    /// identifiers back to back.
+    fn id_descriptor(ids: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let mut buf = vec![0u8; ID_DESC_FIRST];
+        buf[ID_DESC_COUNT..ID_DESC_COUNT + 4].copy_from_slice(&(ids.len() as u32).to_le_bytes());
+        for (code_set, id_type, data) in ids {
+            let mut ident = vec![0u8; IDENT_DATA];
+            ident[0..4].copy_from_slice(&code_set.to_le_bytes());
+            ident[4..8].copy_from_slice(&id_type.to_le_bytes());
+            ident[IDENT_SIZE..IDENT_SIZE + 2].copy_from_slice(&(data.len() as u16).to_le_bytes());
+            let next = (IDENT_DATA + data.len()) as u16;
+            ident[IDENT_NEXT..IDENT_NEXT + 2].copy_from_slice(&next.to_le_bytes());
+            ident.extend_from_slice(data);
+            buf.extend_from_slice(&ident);
+        }
+        buf
+    }
+
+    #[test]
+    fn device_ids_keep_binary_identifiers_only() {
+        let naa = [0x50, 0x01, 0x4e, 0xe2, 0xb1, 0x23, 0x45, 0x67];
+        let buf = id_descriptor(&[
+            (ID_CODE_SET_BINARY, ID_TYPE_FCPH_NAME, &naa),
+            // ASCII vendor strings are skipped
+            (2, 1, b"WDC WD40EFRX"),
+            (ID_CODE_SET_BINARY, ID_TYPE_EUI64, &[0xAB; 8]),
+        ]);
+
+        let ids = parse_device_ids(&buf).unwrap();
+        assert_eq!(
+            ids,
+            vec![
+                DeviceId {
+                    id_type: ID_TYPE_FCPH_NAME,
+                    hex: "50014ee2b1234567".to_string(),
+                },
+                DeviceId {
+                    id_type: ID_TYPE_EUI64,
+                    hex: "abababababababab".to_string(),
+                },
+            ]
+        );
+        assert!(parse_device_ids(&buf[..ID_DESC_FIRST - 1]).is_err());
+    }
+
+    #[test]
+    fn wwn_matches_naa_or_eui64_in_any_notation() {
+        let ids = vec![
+            DeviceId {
+                id_type: ID_TYPE_FCPH_NAME,
+                hex: "50014ee2b1234567".to_string(),
+            },
+            // a 16-byte NGUID is not a WWN
+            DeviceId {
+                id_type: 8,
+                hex: "00112233445566778899aabbccddeeff".to_string(),
+            },
+        ];
+
+        assert!(matches_wwn(&ids, "0x50014EE2B1234567"));
+        assert!(matches_wwn(&ids, "50:01:4e:e2:b1:23:45:67"));
+        assert!(!matches_wwn(&ids, "50014ee2b1234568"));
+        assert!(!matches_wwn(&ids, "00112233445566778899aabbccddeeff"));
+        assert!(!matches_wwn(&ids, ""));
+    }
+
+    #[test]
+    fn only_internal_nvme_disks_need_the_serial() {
+        let internal = disk("S9X", "Samsung 990", BUS_TYPE_NVME);
+        assert!(needs_serial_interlock(&internal));
+
+        let enclosure = DiskIdentity {
+            removable: true,
+            ..internal.clone()
+        };
+        assert!(!needs_serial_interlock(&enclosure));
+        assert!(!needs_serial_interlock(&disk("WD-123", "WDC WD40", 11)));
+    }
+
+    #[test]
+    fn serial_must_be_typed_exactly() {
+        assert!(serial_matches(" S9XNJ0R123 \r\n", "S9XNJ0R123"));
+        assert!(!serial_matches("s9xnj0r123", "S9XNJ0R123"));
+        assert!(!serial_matches("S9XNJ0R12", "S9XNJ0R123"));
+        // a blank serial can never be confirmed
+        assert!(!serial_matches("", "  "));
+    }
+
+    #[test]
+    fn confirm_details_lists_identity_and_geometry() {
+        let id = DiskIdentity {
+            firmware: Some("82.00A82".to_string()),
+            ..disk("WD-123", "WDC WD40", 11)
+        };
+        let expected = "Disk details (PhysicalDrive2):\n  \
+            Model         : WDC WD40\n  \
+            Serial        : WD-123\n  \
+            Firmware      : 82.00A82\n  \
+            Bus type      : SATA\n  \
+            Sector size   : 512 bytes\n  \
+            Total sectors : 7814037168\n  \
+            Capacity      : 3726 GB (4000787030016 bytes)\n";
+        assert_eq!(confirm_details(2, &id, 512, 4_000_787_030_016), expected);
+
+        let bare = confirm_details(0, &DiskIdentity::default(), 4096, 0);
+        assert!(bare.contains("Serial        : not reported\n"));
+    }
+
+    #[test]
+    fn phrase_format_expands_the_disk_fields() {
+        let id = disk("WD-123", "WDC WD40", 11);
+        let size = 4_000_787_030_016;
+        let expand = |fmt: &str| expand_phrase(fmt, 2, &id, size);
+
+        assert_eq!(expand("WIPE-DISK-{index}").unwrap(), "WIPE-DISK-2");
+        assert_eq!(expand("ERASE-{serial}").unwrap(), "ERASE-WD-123");
+        assert_eq!(expand("{model} {size_gb}GB").unwrap(), "WDC WD40 4000GB");
+
+        assert!(expand("ERASE-{serial").unwrap_err().contains("unclosed"));
+        let unknown = expand("{colour}").unwrap_err();
+        assert!(unknown.starts_with("unknown placeholder {colour}"));
+        let bare = expand_phrase("ERASE-{serial}", 2, &DiskIdentity::default(), size);
+        assert_eq!(bare.unwrap_err(), "the disk reports no serial for {serial}");
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::wipe::WipeMode;
+
+/// Top level of a --job file: `{ "jobs": [ ... ] }`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobSpec {
+    jobs: Vec<JobEntry>,
+}
+
+/// One job as written in the file; missing settings come from the command line.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobEntry {
+    /// File path or \\.\PhysicalDriveN
+    target: String,
+    mode: Option<String>,
+    passes: Option<u32>,
+    verify: Option<bool>,
+    report: Option<PathBuf>,
+}
+
+/// A validated job, ready to run.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub target: String,
+    pub mode: Option<WipeMode>,
+    pub passes: Option<u32>,
+    pub verify: Option<bool>,
+    pub report: Option<PathBuf>,
+}
+
+/// Outcome of one job, written back to the results file.
+#[derive(Debug, Serialize)]
+pub struct JobResult {
+    pub target: String,
+    /// "completed", "failed" or "skipped"
+    pub status: String,
+    pub mode: String,
+    pub passes: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Parse and validate a job spec; the error names the first bad job.
+pub fn parse_job_spec(text: &str) -> io::Result<Vec<Job>> {
+    let bad = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let spec: JobSpec =
+        serde_json::from_str(text).map_err(|e| bad(format!("invalid job file: {}", e)))?;
+    if spec.jobs.is_empty() {
+        return Err(bad("job file lists no jobs".to_string()));
+    }
+
+    spec.jobs
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let n = i + 1;
+            if entry.target.trim().is_empty() {
+                return Err(bad(format!("job {}: target is empty", n)));
+            }
+            if entry.passes == Some(0) {
+                return Err(bad(format!("job {}: passes must be at least 1", n)));
+            }
+            let mode = match &entry.mode {
+                Some(m) => Some(
+                    WipeMode::from_str(m, true)
+                        .map_err(|_| bad(format!("job {}: unknown mode '{}'", n, m)))?,
+                ),
+                None => None,
+            };
+
+            Ok(Job {
+                target: entry.target.trim().to_string(),
+                mode,
+                passes: entry.passes,
+                verify: entry.verify,
+                report: entry.report,
+            })
+        })
+        .collect()
+}
+
+/// Where results go when --job-results isn't given: next to the job file.
+pub fn default_results_path(job_file: &Path) -> PathBuf {
+    job_file.with_extension("results.json")
+}
+
+pub fn write_job_results(path: &Path, results: &[JobResult]) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(results)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fs::write(path, text)?;
+    println!("[+] Job results written to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_spec_becomes_the_job_list() {
+        let spec = r#"{
+            "jobs": [
+                { "target": "\\\\.\\PhysicalDrive2", "mode": "zeros", "verify": true },
+                { "target": " D:\\vm.img ", "mode": "random", "passes": 3,
+                  "report": "D:\\reports\\vm.txt" }
+            ]
+        }"#;
+
+        let jobs = parse_job_spec(spec).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].target, r"\\.\PhysicalDrive2");
+        assert!(matches!(jobs[0].mode, Some(WipeMode::Zeros)));
+        assert_eq!((jobs[0].passes, jobs[0].verify), (None, Some(true)));
+        assert_eq!(jobs[1].target, r"D:\vm.img");
+        assert!(matches!(jobs[1].mode, Some(WipeMode::Random)));
+        assert_eq!(jobs[1].passes, Some(3));
+        assert_eq!(jobs[1].report, Some(PathBuf::from(r"D:\reports\vm.txt")));
+    }
+
+    #[test]
+    fn bad_jobs_are_named_by_number() {
+        let err = |spec: &str| parse_job_spec(spec).unwrap_err().to_string();
+
+        assert_eq!(err(r#"{ "jobs": [] }"#), "job file lists no jobs");
+        assert_eq!(
+            err(r#"{ "jobs": [{ "target": "a" }, { "target": " " }] }"#),
+            "job 2: target is empty"
+        );
+        assert_eq!(
+            err(r#"{ "jobs": [{ "target": "a", "passes": 0 }] }"#),
+            "job 1: passes must be at least 1"
+        );
+        assert_eq!(
+            err(r#"{ "jobs": [{ "target": "a", "mode": "shred" }] }"#),
+            "job 1: unknown mode 'shred'"
+        );
+        // unknown fields are a typo, not something to ignore
+        let typo = err(r#"{ "jobs": [{ "target": "a", "pases": 3 }] }"#);
+        assert!(typo.starts_with("invalid job file"));
+    }
+}
@@ -0,0 +1,33 @@
+//! File and disk wiping for Windows, as used by the `wipecore` binary.
+
+pub mod checkpoint;
+pub mod exec;
+pub mod identity;
+pub mod job;
+pub mod notify;
+pub mod preset;
+pub mod progress;
+pub mod report;
+pub mod resume;
+pub mod simulate;
+pub mod util;
+pub mod win;
+pub mod wipe;
+
+mod aligned;
+mod ata;
+mod fsmeta;
+mod mbr;
+mod pagefile;
+mod scsi;
+
+pub use progress::{ConsoleProgress, Progress, ProgressSink};
+pub use util::{format_eta, size_format};
+pub use win::{
+    disk_length, disk_sector_size, list_disks, physical_drive_index, protected_indexes,
+    show_disk_size,
+};
+pub use wipe::{
+    confirm_wipe, wipe_file, wipe_file_reported, wipe_file_with_progress, wipe_file_with_sink,
+    WipeMode, WipeOptions, WipeReport, WipeTarget,
+};
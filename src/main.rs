@@ -1,30 +1,220 @@
-mod util;
-mod wipe;
-mod win;
 mod cli;
 
-use std::fs::OpenOptions;
-use std::path::Path;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader};
+use std::os::windows::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 
-use crate::cli::parse_args;
-use crate::util::size_format;
-use crate::wipe::{confirm_wipe, wipe_file};
-use crate::win::{list_disks, run_disk_wipe_flow, show_disk_size};
+use wipecore::checkpoint::{CheckpointFile, DEFAULT_SAVE_EVERY};
+use wipecore::exec::ExecAfter;
+use wipecore::notify::{send_notification, Notification};
+use wipecore::report::{artifact_path, failed_report, wipe_report, write_reports, Report};
+use wipecore::resume::ResumeState;
+use wipecore::util::{
+    check_interrupted, format_eta, format_utc, is_attachable_image, is_disk_image, is_interrupted,
+    is_reparse_point, parse_size, read_target_list, size_format, unix_now, NEUTRAL_FILE_TIME,
+};
+use wipecore::job::{default_results_path, parse_job_spec, write_job_results, Job, JobResult};
+use wipecore::preset::{presets_path, save_preset};
+use wipecore::progress::{
+    disable_osc, disable_title, enable_compact, hold_output, say, set_title_target, take_output,
+    ConsoleProgress, Dashboard, ProgressSink,
+};
+use wipecore::simulate::BadSectorSim;
+use wipecore::wipe::{
+    build_summary, can_regenerate, check_aligned, check_buffer_size, check_io_size,
+    check_strict_sector_size, confirm_wipe, content_hash, describe_pass, effective_passes,
+    estimate_wipe_seconds, is_random_pass, is_readback_pass, limit_len, list_modes,
+    measure_rng_rate, pattern_verify, print_limit_warning, probe_addressing, read_bench,
+    resolve_range, verify_samples, verify_wipe, wipe_file_with_sink, write_stamp, WipeMode,
+    WipeOptions,
+};
+use wipecore::win::{
+    align_for_physical_sectors, available_memory, confirm_dismount, confirm_disk_phrase,
+    disk_length, disk_sector_size, disk_target_size, dismount_disk_volumes, dump_ctl_codes,
+    enable_vt_output, install_interrupt_handler, list_disks, physical_drive_index,
+    protected_indexes, resolve_device_id, run_disk_wipe_flow, run_free_space_wipe,
+    run_fs_metadata_wipe, run_mbr_gap_wipe, run_volume_wipe, set_file_times, show_disk_size,
+    show_protected, AttachedImage, ConsoleTitle, DiskWipeOptions, LowPriority, TargetLock,
+    WriteCacheGuard,
+};
+
+use crate::cli::{check_pass_count, job_args, parse_args, preset_values, Args};
+
+/// Exit status after Ctrl+C stopped a wipe (128 + SIGINT, as shells report it).
+const EXIT_INTERRUPTED: i32 = 130;
 
 fn main() {
+    run();
+    // after run() so its guards (title, priority) have been restored
+    if is_interrupted() {
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+}
+
+fn run() {
     let args = parse_args();
 
+    // Ctrl+C stops a wipe after the current chunk instead of mid-write
+    if let Err(e) = install_interrupt_handler() {
+        eprintln!("Warning: could not install the Ctrl+C handler: {}", e);
+    }
+
+    if args.no_osc {
+        disable_osc();
+    }
+    if args.compact {
+        enable_compact();
+    }
+
+    // restores the original window title when run returns
+    let title = if args.no_title {
+        None
+    } else {
+        ConsoleTitle::save()
+    };
+    if title.is_none() {
+        disable_title();
+    }
+
+    // back to normal priority when run returns
+    let _priority = if args.low_priority {
+        LowPriority::enter()
+            .map_err(|e| eprintln!("Warning: could not lower priority: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    if let Some(name) = &args.save_preset {
+        match presets_path().and_then(|path| {
+            save_preset(&path, name, preset_values(&args))?;
+            Ok(path)
+        }) {
+            Ok(path) => println!("[+] Saved preset '{}' to {}", name, path.display()),
+            Err(e) => eprintln!("Could not save preset '{}': {}", name, e),
+        }
+        return;
+    }
+
+    if args.list_modes {
+        list_modes();
+        return;
+    }
+
+    if args.dump_ctl_codes {
+        dump_ctl_codes();
+        return;
+    }
+
+    // planning aid: duration from size, passes and rate alone
+    if let (true, Some(size), Some(rate)) = (args.estimate, &args.size, args.rate) {
+        if let Err(e) = run_estimate(size, rate, &args) {
+            eprintln!("Estimate failed: {}", e);
+        }
+        return;
+    }
+
+    // non-destructive read throughput test
+    if let Some(device) = &args.read_bench {
+        if let Err(e) = run_read_bench(device, &args) {
+            eprintln!("Read benchmark of '{}' failed: {}", device, e);
+        }
+        return;
+    }
+
+    // read-only check of an earlier wipe
+    if let Some(target) = &args.verify_only {
+        if let Err(e) = run_verify_only(target, &args) {
+            eprintln!("Verify of '{}' failed: {}", target, e);
+        }
+        return;
+    }
+
+    if let Some(dir) = &args.output_dir {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!(
+                "Could not create output directory '{}': {}",
+                dir.display(),
+                e
+            );
+            return;
+        }
+    }
+
     // disk wipe mode
     if args.wipe_disk {
-        if let Err(e) = run_disk_wipe_flow(args.mode, args.passes, args.system_disk) {
+        if !buffer_size_ok(&args) {
+            return;
+        }
+
+        if let Err(e) = run_disk_wipe_flow(&disk_wipe_options(&args)) {
             eprintln!("Disk wipe failed or aborted: {}", e);
         }
         return;
     }
 
+    // every extent of a (possibly spanned) volume
+    if let Some(volume) = &args.wipe_volume {
+        if !buffer_size_ok(&args) {
+            return;
+        }
+        if let Err(e) = run_volume_wipe(volume, &disk_wipe_options(&args)) {
+            eprintln!("Volume wipe failed or aborted: {}", e);
+        }
+        return;
+    }
+
+    // free space only; live files stay
+    if let Some(volume) = &args.wipe_free {
+        if !buffer_size_ok(&args) {
+            return;
+        }
+        if let Err(e) = run_free_space_wipe(volume, &disk_wipe_options(&args)) {
+            eprintln!("Free space wipe failed or aborted: {}", e);
+        }
+        return;
+    }
+
+    // filesystem metadata only; file contents stay on disk
+    if let Some(volume) = &args.wipe_fs_metadata {
+        let excluded = &args.exclude_serial;
+        if let Err(e) = run_fs_metadata_wipe(volume, args.system_disk, excluded, args.dry_run) {
+            eprintln!("Filesystem metadata wipe failed or aborted: {}", e);
+        }
+        return;
+    }
+
+    // clear MBR-to-first-partition gap
+    if args.wipe_mbr_gap {
+        if let Some(disk_num) = args.disk {
+            let excluded = &args.exclude_serial;
+            if let Err(e) = run_mbr_gap_wipe(disk_num, args.system_disk, excluded, args.dry_run) {
+                eprintln!("MBR gap wipe failed or aborted: {}", e);
+            }
+        }
+        return;
+    }
+
+    // read-only preview of the protection logic
+    if args.show_protected {
+        if let Err(e) = show_protected(args.system_disk) {
+            eprintln!("Error while computing protected disks: {}", e);
+        }
+        return;
+    }
+
+    // locate pagefile/hiberfil and explain how to clear them
+    if args.wipe_pagefile {
+        if let Err(e) = show_pagefiles() {
+            eprintln!("Error while locating paging files: {}", e);
+        }
+        return;
+    }
+
     // just list disks
-    if args.list_disks {
-        if let Err(e) = list_disks(16, args.system_disk) {
+    if args.list_disks || args.quiet_list {
+        if let Err(e) = list_disks(16, args.system_disk, args.quiet_list) {
             eprintln!("Error while listing disks: {}", e);
         }
         return;
@@ -38,63 +228,1249 @@ fn main() {
         return;
     }
 
+    // batch of wipe jobs from a JSON file
+    if let Some(job_file) = &args.job {
+        if let Err(e) = run_job_file(job_file, &args) {
+            eprintln!("Job file '{}' failed: {}", job_file.display(), e);
+        }
+        return;
+    }
+
     // file wipe mode
-    let target = match &args.target {
-        Some(t) => t,
-        None => {
-            eprintln!("No target file specified.");
-            eprintln!("Usage (file wipe):   wipecore <target> [--mode ..] [--passes ..]");
-            eprintln!("Usage (disk size):   wipecore --disk <N>");
-            eprintln!("Usage (list disks):  wipecore --list-disks [--system-disk N]");
-            eprintln!("Usage (disk wipe):   wipecore --wipe-disk [--system-disk N] [--mode ..] [--passes ..]");
-            return;
+    let mut targets = args.targets.clone();
+    if let Some(list) = &args.devices_file {
+        match load_devices_file(list) {
+            Ok(more) => targets.extend(more),
+            Err(e) => {
+                eprintln!("Could not read --devices-file '{}': {}", list, e);
+                return;
+            }
+        }
+    }
+
+    // a stable identity, resolved to whatever index the disk has right now
+    if args.device_wwn.is_some() || args.device_guid.is_some() {
+        match resolve_device_id(args.device_wwn.as_deref(), args.device_guid.as_deref()) {
+            Ok(path) => targets.push(path),
+            Err(e) => {
+                eprintln!("Could not find the disk: {}", e);
+                return;
+            }
         }
+    }
+
+    if targets.is_empty() {
+        eprintln!("No target file specified.");
+        eprintln!("Usage (file wipe):   wipecore <target>... [--mode ..] [--passes ..] [--verify]");
+        eprintln!("Usage (target list): wipecore --devices-file <PATH|-> [--mode ..] [--passes ..]");
+        eprintln!("Usage (disk size):   wipecore --disk <N>");
+        eprintln!("Usage (list disks):  wipecore --list-disks [--system-disk N]");
+        eprintln!("Usage (disk wipe):   wipecore --wipe-disk [--system-disk N] [--mode ..] [--passes ..] [--safe-mode]");
+        eprintln!("Usage (MBR gap):     wipecore --disk <N> --wipe-mbr-gap [--system-disk N]");
+        return;
+    }
+
+    // check every target first so the summary shows the whole scope
+    let protected = if targets.iter().any(|t| physical_drive_index(t).is_some()) {
+        protected_indexes(args.system_disk)
+    } else {
+        Vec::new()
     };
+    let size_limit = safe_mode_limit(args);
+
+    let mut resume = match &args.resume {
+        Some(path) => match ResumeState::load(path) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                eprintln!("Could not read --resume state '{}': {}", path.display(), e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut files: Vec<(String, u64)> = Vec::new();
+    let mut skipped = 0;
+    for t in &targets {
+        if resume.as_ref().is_some_and(|r| r.is_done(t)) {
+            println!("[*] Skipping '{}': already wiped (--resume).", t);
+            skipped += 1;
+            continue;
+        }
+        if let Some(index) = physical_drive_index(t) {
+            match disk_target_size(index, &protected, &args.exclude_serial, size_limit) {
+                Ok(size) => files.push((t.clone(), size)),
+                Err(e) => eprintln!("Warning: skipping '{}': {}", t, e),
+            }
+            continue;
+        }
 
-    let path = Path::new(target);
+        if args.inside_image && is_attachable_image(Path::new(t)) {
+            println!("Note: '{}' is attached and the disk inside it wiped.", t);
+        } else if is_disk_image(Path::new(t)) {
+            println!(
+                "Note: '{}' is a disk image; the partition tables in it are overwritten too.",
+                t
+            );
+        }
+        match file_target_size(Path::new(t), args.follow_reparse) {
+            Ok(size) => files.push((t.clone(), size)),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+    }
+
+    if files.is_empty() && skipped > 0 {
+        println!("[+] Every target was already wiped; nothing left to resume.");
+        clear_resume(resume.as_ref());
+        return;
+    }
+    if files.is_empty() {
+        eprintln!("No wipeable targets left after protection checks.");
+        return;
+    }
+    let not_disk = files
+        .iter()
+        .find(|(t, _)| physical_drive_index(t).is_none());
+    if let (true, Some((t, _))) = (args.multi_disk_parallel, not_disk) {
+        eprintln!(
+            r"--multi-disk-parallel only wipes \\.\PhysicalDriveN targets, not '{}'.",
+            t
+        );
+        return;
+    }
 
-    let metadata = match std::fs::metadata(path) {
-        Ok(m) => m,
+    println!();
+    print!("{}", build_summary(&files, args.mode, args.passes));
+
+    match confirm_wipe(files.len()) {
+        Ok(true) => {}
+        Ok(false) => {
+            println!("Aborted by user.");
+            return;
+        }
         Err(e) => {
-            eprintln!("could not read metadata for '{}': {}", path.display(), e);
+            eprintln!("Error reading confirmation: {}", e);
             return;
         }
-    };
+    }
 
-    if !metadata.is_file() {
-        eprintln!("'{}' is not a regular file.", path.display());
+    // physical disks still need their own typed phrase
+    for (target, _) in &files {
+        if let Some(index) = physical_drive_index(target) {
+            let format = args.confirm_phrase_format.as_deref();
+            match confirm_disk_phrase(index, format, args.confirm_checksum) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("Aborted by user (confirmation phrase did not match).");
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Error reading confirmation: {}", e);
+                    return;
+                }
+            }
+            match confirm_dismount(index) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("Aborted by user (volumes left mounted).");
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Error reading confirmation: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    if args.dry_run {
+        let passes = effective_passes(args.mode, args.passes);
+        println!();
+        println!("=== DRY RUN: no file will be opened for write ===");
+        for pass in 1..=passes {
+            println!("  pass {}/{} : {}", pass, passes, describe_pass(args.mode, pass));
+        }
         return;
     }
 
-    let size_bytes = metadata.len();
-    if size_bytes == 0 {
-        eprintln!("File is empty (0 bytes), nothing to wipe.");
+    if !buffer_size_ok(&args) {
         return;
     }
 
-    println!("Target file : {}", path.display());
-    println!("Size :   {}", size_format(size_bytes));
-    println!("Mode :   {:?}", args.mode);
-    println!("Passes : {}", args.passes);
+    let mut reports = Vec::new();
+    let mut failed = 0;
 
-    if let Err(e) = confirm_wipe(path) {
-        eprintln!("Error reading confirmation: {}", e);
-        return;
+    // once per target, so a failure report names the seed/nonce actually written
+    let opts: Vec<WipeOptions> = files
+        .iter()
+        .map(|(target, size)| target_options(target, *size, &args))
+        .collect();
+
+    if args.multi_disk_parallel {
+        let results = run_parallel(&files, &opts, &args);
+        for (((target, size), opts), (started, result, output)) in
+            files.iter().zip(&opts).zip(results)
+        {
+            if !output.is_empty() {
+                println!();
+                println!("=== {} ===", target);
+                print!("{}", output);
+            }
+            if !finish_target(target, *size, opts, started, result, &args, &mut reports) {
+                failed += 1;
+            }
+        }
+    } else {
+        for ((target, size), opts) in files.iter().zip(&opts) {
+            println!();
+            println!("=== {} ===", target);
+
+            let started = unix_now();
+            let result = if args.inside_image && is_attachable_image(Path::new(target)) {
+                wipe_inside_image(Path::new(target), opts.clone(), &args)
+            } else {
+                let mut sink = ConsoleProgress::new();
+                run_file_wipe(Path::new(target), *size, opts.clone(), &args, &mut sink)
+            };
+            if !finish_target(target, *size, opts, started, result, &args, &mut reports) {
+                failed += 1;
+            } else if let (Some(state), None) = (&mut resume, physical_drive_index(target)) {
+                if let Err(e) = state.mark_done(target) {
+                    eprintln!("Warning: --resume could not record '{}': {}", target, e);
+                }
+            }
+            if is_interrupted() {
+                println!("[!] Interrupted: the remaining target(s) were not wiped.");
+                break;
+            }
+        }
     }
 
-    let f = match OpenOptions::new().read(true).write(true).open(path) {
-        Ok(f) => f,
+    if let Some(report_path) = &args.report {
+        if let Err(e) = write_reports(&reports, report_path) {
+            eprintln!("Could not write report: {}", e);
+        }
+    }
+
+    println!();
+    if failed == 0 {
+        println!("[+] Wipe completed ({} passes, {} file(s)).", args.passes, files.len());
+        clear_resume(resume.as_ref());
+    } else {
+        println!("[!] {} of {} file(s) failed.", failed, files.len());
+    }
+}
+
+/// The batch is done, so the --resume state has nothing left to skip.
+fn clear_resume(resume: Option<&ResumeState>) {
+    if let Some(Err(e)) = resume.map(ResumeState::clear) {
+        eprintln!("Warning: could not remove the --resume state: {}", e);
+    }
+}
+
+/// Record the report of one finished target and run the per-target hooks
+/// (--exec-after, --notify-url, --output-dir). Returns false if it failed.
+fn finish_target(
+    target: &str,
+    size: u64,
+    opts: &WipeOptions,
+    started: u64,
+    result: io::Result<Report>,
+    args: &Args,
+    reports: &mut Vec<Report>,
+) -> bool {
+    let error = match result {
+        Ok(report) => {
+            reports.push(report);
+            None
+        }
         Err(e) => {
-            eprintln!("could not open '{}' for writing: {}", path.display(), e);
-            return;
+            eprintln!("Wipe of '{}' failed: {}", target, e);
+            reports.push(failed_report(
+                target,
+                size,
+                opts,
+                started,
+                args.operator.as_deref(),
+                &e.to_string(),
+            ));
+            Some(e.to_string())
         }
     };
+    if let (Some(hook), Some(report)) = (exec_after(args), reports.last_mut()) {
+        hook.run(target, error.as_deref(), report);
+    }
+    if let Some(url) = &args.notify_url {
+        let elapsed = unix_now().saturating_sub(started);
+        let operator = args.operator.as_deref();
+        let note = Notification::new(target, size, elapsed, operator, error.as_deref());
+        send_notification(url, &note);
+    }
+    if let (Some(dir), Some(report)) = (&args.output_dir, reports.last()) {
+        write_dir_report(dir, target, started, report);
+    }
+    error.is_none()
+}
+
+/// --multi-disk-parallel: wipe every disk on its own thread with its own
+/// handle and `opts` entry, progress on one dashboard row each. Results come
+/// back in the order of `files`, with the time each wipe started and the
+/// output the thread held back while the dashboard was drawn.
+fn run_parallel(
+    files: &[(String, u64)],
+    opts: &[WipeOptions],
+    args: &Args,
+) -> Vec<(u64, io::Result<Report>, String)> {
+    // per-pass banners from several threads would tear the dashboard apart
+    enable_compact();
+    let names = files.iter().map(|(t, _)| t.clone()).collect();
+    let board = Dashboard::new(names, enable_vt_output());
+
+    println!();
+    println!("=== Wiping {} disks in parallel ===", files.len());
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .iter()
+            .zip(opts)
+            .enumerate()
+            .map(|(i, ((target, size), opts))| {
+                let mut row = board.row(i);
+                scope.spawn(move || {
+                    hold_output();
+                    let started = unix_now();
+                    let opts = opts.clone();
+                    let result = run_file_wipe(Path::new(target), *size, opts, args, &mut row);
+                    (started, result, take_output())
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| {
+                h.join().unwrap_or_else(|_| {
+                    let e = io::Error::new(io::ErrorKind::Other, "wipe thread panicked");
+                    (unix_now(), Err(e), String::new())
+                })
+            })
+            .collect()
+    })
+}
+
+/// --job: validate every job, confirm once, run them in order and write
+/// one result per job to the results file.
+fn run_job_file(job_file: &Path, args: &Args) -> io::Result<()> {
+    let jobs = parse_job_spec(&std::fs::read_to_string(job_file)?)?;
+
+    let protected = if jobs
+        .iter()
+        .any(|j| physical_drive_index(&j.target).is_some())
+    {
+        protected_indexes(args.system_disk)
+    } else {
+        Vec::new()
+    };
+    let size_limit = safe_mode_limit(args);
 
-    if let Err(e) = wipe_file(f, size_bytes, args.mode, args.passes) {
-        eprintln!("Wipe failed: {}", e);
-        return;
+    let mut results = Vec::new();
+    let mut runnable: Vec<(&Job, u64)> = Vec::new();
+    for job in &jobs {
+        let a = job_args(job, args);
+        let size = check_pass_count(&a).and_then(|()| match physical_drive_index(&job.target) {
+            Some(index) => disk_target_size(index, &protected, &args.exclude_serial, size_limit)
+                .map_err(|e| e.to_string()),
+            None => file_target_size(Path::new(&job.target), args.follow_reparse),
+        });
+        match size {
+            Ok(size) => runnable.push((job, size)),
+            Err(e) => {
+                eprintln!("Warning: skipping job '{}': {}", job.target, e);
+                results.push(job_result(job, &a, "skipped", Some(e)));
+            }
+        }
+    }
+
+    if runnable.is_empty() {
+        eprintln!("No runnable jobs left after validation.");
+        return write_job_results(&results_path(job_file, args), &results);
     }
 
     println!();
-    println!("[+] Wipe completed ({} passes).", args.passes);
-}
\ No newline at end of file
+    println!("=== Jobs from {} ===", job_file.display());
+    for (job, size) in &runnable {
+        let a = job_args(job, args);
+        println!(
+            "  {}  ({}, {:?}, {} pass(es){})",
+            job.target,
+            size_format(*size),
+            a.mode,
+            a.passes,
+            if a.verify { ", verify" } else { "" }
+        );
+    }
+
+    if !confirm_wipe(runnable.len())? {
+        println!("Aborted by user.");
+        return Ok(());
+    }
+    for (job, _) in &runnable {
+        if let Some(index) = physical_drive_index(&job.target) {
+            let format = args.confirm_phrase_format.as_deref();
+            if !confirm_disk_phrase(index, format, args.confirm_checksum)? {
+                println!("Aborted by user (confirmation phrase did not match).");
+                return Ok(());
+            }
+            if !confirm_dismount(index)? {
+                println!("Aborted by user (volumes left mounted).");
+                return Ok(());
+            }
+        }
+    }
+
+    if args.dry_run {
+        println!();
+        println!("=== DRY RUN: no job will be run ===");
+        return Ok(());
+    }
+
+    if !buffer_size_ok(args) {
+        return Ok(());
+    }
+
+    for (job, size) in &runnable {
+        let a = job_args(job, args);
+        println!();
+        println!("=== Job: {} ===", job.target);
+
+        let started = unix_now();
+        let opts = target_options(&job.target, *size, &a);
+        let mut sink = ConsoleProgress::new();
+        match run_file_wipe(Path::new(&job.target), *size, opts.clone(), &a, &mut sink) {
+            Ok(report) => {
+                if let Some(path) = job_report_path(job, args, started) {
+                    if let Err(e) = report.write_to(&path) {
+                        eprintln!("Could not write report: {}", e);
+                    }
+                }
+                results.push(job_result(job, &a, "completed", None));
+            }
+            Err(e) => {
+                eprintln!("Job '{}' failed: {}", job.target, e);
+                if let Some(path) = job_report_path(job, args, started) {
+                    let report = failed_report(
+                        &job.target,
+                        *size,
+                        &opts,
+                        started,
+                        a.operator.as_deref(),
+                        &e.to_string(),
+                    );
+                    if let Err(e) = report.write_to(&path) {
+                        eprintln!("Could not write report: {}", e);
+                    }
+                }
+                results.push(job_result(job, &a, "failed", Some(e.to_string())));
+            }
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.status != "completed").count();
+    println!();
+    if failed == 0 {
+        println!("[+] All {} job(s) completed.", results.len());
+    } else {
+        println!(
+            "[!] {} of {} job(s) did not complete.",
+            failed,
+            results.len()
+        );
+    }
+
+    write_job_results(&results_path(job_file, args), &results)
+}
+
+fn job_result(job: &Job, args: &Args, status: &str, error: Option<String>) -> JobResult {
+    JobResult {
+        target: job.target.clone(),
+        status: status.to_string(),
+        mode: format!("{:?}", args.mode),
+        passes: args.passes,
+        error,
+    }
+}
+
+fn results_path(job_file: &Path, args: &Args) -> PathBuf {
+    match (&args.job_results, &args.output_dir) {
+        (Some(path), _) => path.clone(),
+        (None, Some(dir)) => {
+            let key = job_file.with_extension("").display().to_string();
+            artifact_path(dir, &key, "results", "json", unix_now())
+        }
+        (None, None) => default_results_path(job_file),
+    }
+}
+
+/// A job's own "report" wins; otherwise --output-dir gets one per target.
+fn job_report_path(job: &Job, args: &Args, started: u64) -> Option<PathBuf> {
+    job.report.clone().or_else(|| {
+        let dir = args.output_dir.as_ref()?;
+        Some(artifact_path(dir, &job.target, "report", "txt", started))
+    })
+}
+
+/// --output-dir: one report file per wiped target.
+fn write_dir_report(dir: &Path, target: &str, started: u64, report: &Report) {
+    let path = artifact_path(dir, target, "report", "txt", started);
+    if let Err(e) = report.write_to(&path) {
+        eprintln!("Could not write report: {}", e);
+    }
+}
+
+/// Targets listed in a file, or on stdin when `path` is "-".
+fn load_devices_file(path: &str) -> io::Result<Vec<String>> {
+    if path == "-" {
+        let targets = read_target_list(io::stdin().lock())?;
+        println!("Note: targets were read from stdin; confirmations need a console.");
+        return Ok(targets);
+    }
+    read_target_list(BufReader::new(File::open(path)?))
+}
+
+/// Size of a wipeable regular file, or why it can't be wiped.
+/// Links and junctions are refused unless `follow_reparse` (--follow-reparse).
+fn file_target_size(path: &Path, follow_reparse: bool) -> Result<u64, String> {
+    let link = std::fs::symlink_metadata(path)
+        .map_err(|e| format!("could not read metadata for '{}': {}", path.display(), e))?;
+
+    if is_reparse_point(link.file_attributes()) {
+        let resolved = std::fs::canonicalize(path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| "an unresolvable target".to_string());
+        if !follow_reparse {
+            return Err(format!(
+                "'{}' is a reparse point (link/junction) to {}; use --follow-reparse to wipe that.",
+                path.display(),
+                resolved
+            ));
+        }
+        println!(
+            "Note: '{}' is a reparse point; wiping its target {}.",
+            path.display(),
+            resolved
+        );
+    }
+
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| format!("could not read metadata for '{}': {}", path.display(), e))?;
+
+    if !metadata.is_file() {
+        return Err(format!("'{}' is not a regular file.", path.display()));
+    }
+
+    if metadata.len() == 0 {
+        return Err(format!(
+            "'{}' is empty (0 bytes), nothing to wipe.",
+            path.display()
+        ));
+    }
+
+    Ok(metadata.len())
+}
+
+/// Wipe one confirmed target with its own `opts`; returns its report.
+fn run_file_wipe(
+    path: &Path,
+    size_bytes: u64,
+    mut opts: WipeOptions,
+    args: &Args,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<Report> {
+    // disks use the disk flow's key; files their canonical path, so "a.img"
+    // and "C:\dir\a.img" share one lock
+    let lock_key = match physical_drive_index(&path.display().to_string()) {
+        Some(index) => format!(r"\\.\PhysicalDrive{}", index),
+        None => std::fs::canonicalize(path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| path.display().to_string()),
+    };
+    let _lock = TargetLock::acquire(&lock_key)?;
+    set_title_target(&lock_key);
+
+    // held until the wipe is over so Windows can't remount them mid-write
+    let _volumes = match physical_drive_index(&path.display().to_string()) {
+        Some(index) => Some(dismount_disk_volumes(index)?),
+        None => None,
+    };
+
+    let mut f = open_wipe_target(path, needs_read_back(args))?;
+
+    let mut wipe_len = size_bytes;
+    let mut partial = args.offset.is_some() || args.length.is_some();
+
+    // disks report their sector size; files are treated as 512-byte sectors
+    let disk_index = physical_drive_index(&path.display().to_string());
+    let sector_size = match disk_index {
+        Some(index) => disk_sector_size(index)? as u64,
+        None => 512,
+    };
+    opts.sector_size = sector_size as usize;
+
+    if args.strict_sector_size && disk_index.is_some() {
+        check_strict_sector_size(
+            args.buffer_size,
+            args.io_size,
+            args.offset,
+            args.length,
+            args.units,
+            sector_size,
+        )?;
+    }
+
+    if let Some(io_size) = args.io_size {
+        check_io_size(io_size, args.buffer_size, sector_size)?;
+    }
+
+    if partial {
+        let (start, len) =
+            resolve_range(size_bytes, args.offset, args.length, args.units, sector_size)?;
+        if disk_index.is_some() {
+            check_aligned(start, len, sector_size)?;
+        }
+        say(format!(
+            "Range :  bytes {}..{} ({})",
+            start,
+            start + len,
+            size_format(len)
+        ));
+        opts.offset = start;
+        wipe_len = len;
+    }
+
+    let (capped_len, capped) = limit_len(wipe_len, args.limit_bytes, sector_size);
+    if capped {
+        wipe_len = capped_len;
+        partial = true;
+        print_limit_warning(wipe_len, &path.display().to_string());
+    }
+
+    // files can't wrap around, only devices are worth probing
+    if args.probe_addressing && disk_index.is_some() {
+        if partial {
+            say("Note: --probe-addressing skipped, only part of the disk is wiped.");
+        } else {
+            check_interrupted()?;
+            probe_addressing(&mut f, size_bytes, sector_size)?;
+        }
+    }
+
+    if let Some(index) = disk_index {
+        align_for_physical_sectors(index, &mut opts);
+    }
+
+    // restored when this function returns, after any verify
+    let _write_cache = match disk_index {
+        Some(index) if args.disable_write_cache => WriteCacheGuard::disable(index)?,
+        _ => None,
+    };
+
+    // files only: a disk's "before" is whatever it held, hashing it proves little
+    let before = match disk_index {
+        None if args.checksum_before_after => {
+            say(format!(
+                "[*] Hashing '{}' before the wipe...",
+                path.display()
+            ));
+            let hash = content_hash(&mut f, opts.offset, wipe_len, opts.buffer_size)?;
+            Some(hash)
+        }
+        Some(_) if args.checksum_before_after => {
+            say("Note: --checksum-before-after skipped, it only applies to files.");
+            None
+        }
+        _ => None,
+    };
+
+    let started = unix_now();
+
+    let report = if args.simulate_bad_sectors.is_empty() {
+        wipe_file_with_sink(&mut f, wipe_len, &opts, sink)?
+    } else if disk_index.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--simulate-bad-sectors only works on file targets",
+        ));
+    } else {
+        say(format!(
+            "Note: simulating bad sectors at offset(s) {:?}.",
+            args.simulate_bad_sectors
+        ));
+        let mut sim = BadSectorSim::new(&mut f, &args.simulate_bad_sectors, sector_size);
+        wipe_file_with_sink(&mut sim, wipe_len, &opts, sink)?
+    };
+    // read back in the chunks the final pass was written in, not the size that failed
+    opts.buffer_size = report.buffer_size;
+
+    let mut post_hash = None;
+    if args.verify {
+        post_hash = Some(verify_wipe(&mut f, wipe_len, &opts, sink)?);
+    }
+
+    if args.pattern_verify {
+        pattern_verify(&mut f, wipe_len, &opts, sink)?;
+    }
+
+    if let Some(count) = args.verify_samples {
+        verify_samples(&mut f, wipe_len, &opts, count, sector_size)?;
+    }
+
+    let mut content_hashes = None;
+    if let Some((before, was_zero)) = before {
+        let (after, _) = content_hash(&mut f, opts.offset, wipe_len, opts.buffer_size)?;
+        say(format!("Content SHA-256 before : {}", before));
+        say(format!("Content SHA-256 after  : {}", after));
+        if was_zero {
+            say("Warning: the content was already all zeros before the wipe.");
+        }
+        if before == after && !was_zero {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "content hash is unchanged after the wipe; the overwrite did not take effect",
+            ));
+        }
+        content_hashes = Some((before, after));
+    }
+
+    if args.stamp && partial {
+        say("Note: --stamp skipped, only part of the file was wiped.");
+    } else if args.stamp {
+        write_stamp(&mut f, sector_size as usize, size_bytes, args.operator.as_deref())?;
+    }
+
+    // only reached after the wipe (and any verify) succeeded
+    if args.truncate && (partial || disk_index.is_some()) {
+        say("Note: --truncate skipped, only whole regular files are truncated.");
+    } else if args.truncate {
+        f.set_len(0)?;
+        f.sync_all()?;
+        say(format!("[+] Truncated '{}' to 0 bytes.", path.display()));
+    }
+
+    // after the truncate, which would move the modified time again
+    let scrubbed = args.scrub_timestamps && disk_index.is_none();
+    if args.scrub_timestamps && !scrubbed {
+        say("Note: --scrub-timestamps skipped, disks have no file times.");
+    } else if scrubbed {
+        set_file_times(&f, NEUTRAL_FILE_TIME)?;
+        say(format!(
+            "[+] Timestamps of '{}' set to {}.",
+            path.display(),
+            format_utc(NEUTRAL_FILE_TIME)
+        ));
+    }
+
+    let target = path.display().to_string();
+    let mut report = wipe_report(
+        &target,
+        wipe_len,
+        &opts,
+        started,
+        post_hash.as_deref(),
+        args.operator.as_deref(),
+    );
+    if let Some((before, after)) = content_hashes {
+        report.add("Content SHA-256 before", before);
+        report.add("Content SHA-256 after", after);
+    }
+    if scrubbed {
+        report.add("File times", format_utc(NEUTRAL_FILE_TIME));
+    }
+    Ok(report)
+}
+
+/// Anything that reads the target back after (or while) writing it.
+fn needs_read_back(args: &Args) -> bool {
+    let passes = effective_passes(args.mode, args.passes);
+    args.verify
+        || args.verify_each
+        || args.pattern_verify
+        || args.verify_samples.is_some()
+        || args.probe_addressing
+        || args.checksum_before_after
+        || (1..=passes).any(|p| is_readback_pass(args.mode, p))
+}
+
+/// Open a target read+write. A target the OS only lets us write (e.g. a
+/// write-only ACL) is still wiped when nothing needs to read it back;
+/// otherwise that is reported as verification being unavailable.
+fn open_wipe_target(path: &Path, read_back: bool) -> io::Result<File> {
+    let err = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(f) => return Ok(f),
+        Err(e) => e,
+    };
+
+    let write_only = if err.kind() == io::ErrorKind::PermissionDenied {
+        OpenOptions::new().write(true).open(path).ok()
+    } else {
+        None
+    };
+    match write_only {
+        Some(_) if read_back => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "verification unavailable: '{}' can be written but not read back; \
+                 drop the verify options to wipe it write-only",
+                path.display()
+            ),
+        )),
+        Some(f) => {
+            say(format!(
+                "Note: '{}' is not readable; wiping it write-only.",
+                path.display()
+            ));
+            Ok(f)
+        }
+        None => Err(io::Error::new(
+            err.kind(),
+            format!("could not open '{}' for writing: {}", path.display(), err),
+        )),
+    }
+}
+
+/// --inside-image: attach a .vhd/.vhdx, wipe the disk it presents, detach it.
+fn wipe_inside_image(image: &Path, opts: WipeOptions, args: &Args) -> io::Result<Report> {
+    let attached = AttachedImage::attach(image)?;
+    let dev = attached.physical_path();
+    println!("[*] Attached {} as {}", image.display(), dev);
+
+    let index = physical_drive_index(dev).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} is not a \\\\.\\PhysicalDriveN path", dev),
+        )
+    })?;
+    // a dynamic image grows to its full virtual size as this writes
+    let size = disk_length(index)?;
+    let mut sink = ConsoleProgress::new();
+    let mut report = run_file_wipe(Path::new(dev), size, opts, args, &mut sink)?;
+    report.add("Image", image.display());
+    Ok(report)
+}
+
+/// --estimate: print the expected wipe duration; a device only supplies its length.
+fn run_estimate(size: &str, rate: f64, args: &Args) -> io::Result<()> {
+    if !(rate.is_finite() && rate > 0.0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--rate must be a positive number of MB/s, got {}", rate),
+        ));
+    }
+
+    let bytes = match physical_drive_index(size) {
+        Some(disk_num) => disk_length(disk_num)?,
+        None => parse_size(size).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+    };
+    let passes = effective_passes(args.mode, args.passes);
+
+    println!("Size      : {} ({} bytes)", size_format(bytes), bytes);
+    println!("Passes    : {} ({:?})", passes, args.mode);
+    println!("Rate      : {:.2} MB/s", rate);
+
+    // on a slow CPU the random generator, not the disk, sets the pace
+    let random = (1..=passes).any(|p| is_random_pass(args.mode, p));
+    let rng_rate = if random {
+        let measured = measure_rng_rate(args.seed.is_some(), args.buffer_size as usize);
+        println!("RNG rate  : {:.2} MB/s (measured)", measured);
+        if measured < rate {
+            println!("Note: random passes are limited by the CPU, not --rate.");
+        }
+        measured
+    } else {
+        rate
+    };
+
+    let secs = estimate_wipe_seconds(bytes, args.mode, passes, rate, rng_rate);
+    println!("Estimate  : {}", format_eta(secs));
+    Ok(())
+}
+
+/// --read-bench: time sequential reads with the configured buffer size.
+fn run_read_bench(device: &str, args: &Args) -> io::Result<()> {
+    let disk_index = physical_drive_index(device);
+    let size = match disk_index {
+        Some(index) => disk_length(index)?,
+        None => std::fs::metadata(device)?.len(),
+    };
+
+    let mut amount = size.min(args.read_bench_size);
+    if let Some(index) = disk_index {
+        // raw disk reads must stay sector aligned
+        amount -= amount % disk_sector_size(index)? as u64;
+    }
+
+    println!(
+        "Reading {} from {} ({} per read)...",
+        size_format(amount),
+        device,
+        size_format(args.buffer_size)
+    );
+
+    let mut f = File::open(device)?;
+    let mut sink = ConsoleProgress::new();
+    let stats = read_bench(&mut f, amount, args.buffer_size as usize, &mut sink)?;
+
+    println!(
+        "[+] Read {} in {:.1}s: {:.2} MB/s",
+        size_format(stats.bytes),
+        stats.secs,
+        stats.mib_s
+    );
+
+    Ok(())
+}
+
+/// --verify-only: read a target back against the final pass of --mode and
+/// --passes, listing mismatched ranges. Nothing is opened for write.
+fn run_verify_only(target: &str, args: &Args) -> io::Result<()> {
+    let mut opts = wipe_options(args);
+    // regenerate what the wipe wrote, not the values picked for a new one
+    opts.seed = args.seed;
+    opts.nonce = args.nonce;
+    let last_pass = effective_passes(args.mode, args.passes);
+    if !can_regenerate(&opts, last_pass) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{:?} ends in random data; pass the --seed or --nonce from its report",
+                args.mode
+            ),
+        ));
+    }
+
+    let size = match physical_drive_index(target) {
+        Some(index) => disk_length(index)?,
+        None => std::fs::metadata(target)?.len(),
+    };
+    println!("Checking {} ({})...", target, size_format(size));
+
+    let mut f = File::open(target)?;
+    match pattern_verify(&mut f, size, &opts, &mut ConsoleProgress::new()) {
+        Ok(()) => println!("[+] {} is clean.", target),
+        // the mismatches were listed above
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            println!("[!] {} is DIRTY: {}", target, e)
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Engine settings shared by the file and disk flows.
+fn wipe_options(args: &Args) -> WipeOptions {
+    let mut opts = WipeOptions::new(args.mode, args.passes);
+    opts.buffer_size = args.buffer_size as usize;
+    opts.io_size = args.io_size.map(|n| n as usize);
+    opts.reverse = args.reverse;
+    opts.sync_every = args.sync_every;
+    opts.verify_each = args.verify_each;
+    opts.on_verify_fail = args.on_verify_fail;
+    opts.min_throughput = args.min_throughput;
+    opts.throughput_grace = args.min_throughput_grace;
+    opts.eta_window = args.eta_window;
+    opts.checkpoint_every = args.checkpoint_every;
+    opts.seed = args.seed;
+    if matches!(args.mode, WipeMode::RandomComplement) && opts.seed.is_none() {
+        // the complement pass must regenerate the random pass, so pin a seed
+        opts.seed = Some(rand::random());
+    }
+    if matches!(args.mode, WipeMode::Nonce) {
+        opts.nonce = Some(rand::random());
+    }
+    opts
+}
+
+/// `wipe_options` for one target, with its --checkpoint-file. A resumed
+/// wipe takes the seed and nonce it started with unless --seed is given.
+fn target_options(target: &str, size: u64, args: &Args) -> WipeOptions {
+    let mut opts = wipe_options(args);
+    if let Some(cp_path) = &args.checkpoint_file {
+        let mut cp = CheckpointFile {
+            path: cp_path.clone(),
+            target: target.to_string(),
+            size,
+            mode: format!("{:?}", opts.mode),
+            passes: effective_passes(opts.mode, opts.passes),
+            seed: None,
+            nonce: None,
+            save_every: args.checkpoint_every.unwrap_or(DEFAULT_SAVE_EVERY),
+        };
+        // an unreadable record is reported by the wipe's own load
+        if let (Ok(Some((seed, nonce))), None) = (cp.saved_stream(), args.seed) {
+            opts.seed = seed;
+            opts.nonce = nonce;
+        }
+        cp.seed = opts.seed;
+        cp.nonce = opts.nonce;
+        opts.checkpoint = Some(cp);
+    }
+    opts
+}
+
+fn exec_after(args: &Args) -> Option<ExecAfter> {
+    args.exec_after.as_ref().map(|command| ExecAfter {
+        command: command.clone(),
+        always: args.exec_always,
+    })
+}
+
+/// Largest disk --safe-mode lets any flow wipe, unless --allow-large.
+fn safe_mode_limit(args: &Args) -> Option<u64> {
+    (args.safe_mode && !args.allow_large).then_some(args.safe_limit)
+}
+
+/// Disk-flow settings shared by --wipe-disk and --wipe-volume.
+fn disk_wipe_options(args: &Args) -> DiskWipeOptions {
+    DiskWipeOptions {
+        wipe: wipe_options(args),
+        verify: args.verify,
+        system_disk: args.system_disk,
+        size_limit: safe_mode_limit(args),
+        exclude_serials: args.exclude_serial.clone(),
+        report: args.report.clone(),
+        output_dir: args.output_dir.clone(),
+        offset: args.offset,
+        length: args.length,
+        units: args.units,
+        dry_run: args.dry_run,
+        trim_verify: args.trim_then_verify_unreadable,
+        stamp: args.stamp,
+        reinit_mbr: args.reinit_mbr,
+        force_mbr: args.force_mbr,
+        no_buffering: args.no_buffering,
+        probe_addressing: args.probe_addressing,
+        disable_write_cache: args.disable_write_cache,
+        operator: args.operator.clone(),
+        limit_bytes: args.limit_bytes,
+        confirm_details: args.interactive_confirm_details,
+        verify_samples: args.verify_samples,
+        pattern_verify: args.pattern_verify,
+        write_same: args.write_same,
+        report_smart: args.report_include_smart,
+        phrase_format: args.confirm_phrase_format.clone(),
+        confirm_checksum: args.confirm_checksum,
+        exec_after: exec_after(args),
+        notify_url: args.notify_url.clone(),
+        strict_sector_size: args.strict_sector_size,
+    }
+}
+
+/// --buffer-size must fit comfortably in free RAM.
+fn buffer_size_ok(args: &Args) -> bool {
+    let free = match available_memory() {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Warning: could not query free memory: {}", e);
+            return true;
+        }
+    };
+
+    match check_buffer_size(args.buffer_size, free) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use wipecore::progress::{FnProgress, Progress};
+
+    #[test]
+    fn truncate_keeps_the_file_at_zero_length() {
+        let path = std::env::temp_dir().join(format!("wipecore-trunc-{}", std::process::id()));
+        std::fs::write(&path, vec![0xAAu8; 64 << 10]).unwrap();
+        let args = Args::try_parse_from(["wipecore", "--truncate"]).unwrap();
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+
+        hold_output();
+        let mut sink = FnProgress(|_: &Progress| {});
+        let res = run_file_wipe(&path, 64 << 10, opts, &args, &mut sink);
+        take_output();
+
+        let len = std::fs::metadata(&path).map(|m| m.len());
+        std::fs::remove_file(&path).ok();
+        res.unwrap();
+        assert_eq!(len.unwrap(), 0);
+    }
+
+    #[test]
+    fn interrupted_wipe_reports_failed_not_completed() {
+        let args = Args::try_parse_from(["wipecore"]).unwrap();
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+        let result = Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "wipe interrupted by Ctrl+C",
+        ));
+        let mut reports = Vec::new();
+
+        let ok = finish_target("image.bin", 4096, &opts, 0, result, &args, &mut reports);
+        assert!(!ok);
+
+        let path = std::env::temp_dir().join(format!("wipecore-report-{}", std::process::id()));
+        write_reports(&reports, &path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(text.contains("FAILED (wipe interrupted by Ctrl+C)"));
+        assert!(!text.contains("completed"));
+    }
+
+    #[test]
+    fn output_dir_gets_one_named_report_per_target() {
+        let dir = std::env::temp_dir().join(format!("wipecore-out-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_arg = dir.display().to_string();
+        let args = Args::try_parse_from(["wipecore", "--output-dir", &dir_arg]).unwrap();
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+        // 2024-05-01 13:45:00 UTC
+        let started = 1_714_571_100;
+
+        let done = Ok(wipe_report("a", 4096, &opts, started, None, None));
+        let failed = Err(io::Error::new(io::ErrorKind::Other, "boom"));
+        let mut reports = Vec::new();
+        for (target, result) in [(r"D:\a.img", done), (r"D:\b.bin", failed)] {
+            finish_target(target, 4096, &opts, started, result, &args, &mut reports);
+        }
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(
+            names,
+            [
+                "a.img-report-20240501-134500.txt",
+                "b.bin-report-20240501-134500.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn disk_image_file_is_overwritten_end_to_end() {
+        let name = format!("wipecore-disk-{}.img", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        // an MBR signature up front and a GPT backup header at the end
+        let mut image = vec![0x5Au8; 96 << 10];
+        image[510..512].copy_from_slice(&[0x55, 0xAA]);
+        let tail = image.len() - 512;
+        image[tail..tail + 8].copy_from_slice(b"EFI PART");
+        std::fs::write(&path, &image).unwrap();
+        assert!(is_disk_image(&path));
+
+        let args = Args::try_parse_from(["wipecore"]).unwrap();
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+        hold_output();
+        let mut sink = FnProgress(|_: &Progress| {});
+        let res = run_file_wipe(&path, image.len() as u64, opts, &args, &mut sink);
+        take_output();
+
+        let after = std::fs::read(&path);
+        std::fs::remove_file(&path).ok();
+        res.unwrap();
+        let after = after.unwrap();
+        assert_eq!(after.len(), image.len());
+        assert!(after.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn exec_after_sees_the_target_and_status_in_its_environment() {
+        let tmp = std::env::temp_dir();
+        let path = tmp.join(format!("wipecore-exec-{}", std::process::id()));
+        let dump = tmp.join(format!("wipecore-exec-env-{}", std::process::id()));
+        std::fs::write(&path, vec![0xAAu8; 16 << 10]).unwrap();
+        let command = format!("set WIPECORE_> \"{}\"", dump.display());
+        let args = Args::try_parse_from(["wipecore", "--exec-after", &command]).unwrap();
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+        let target = path.display().to_string();
+
+        hold_output();
+        let mut sink = FnProgress(|_: &Progress| {});
+        let result = run_file_wipe(&path, 16 << 10, opts.clone(), &args, &mut sink);
+        let mut reports = Vec::new();
+        let ok = finish_target(&target, 16 << 10, &opts, 0, result, &args, &mut reports);
+        take_output();
+
+        let env = std::fs::read_to_string(&dump);
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&dump).ok();
+        assert!(ok);
+        let env = env.unwrap();
+        assert!(env.contains(&format!("WIPECORE_TARGET={}", target)));
+        assert!(env.contains("WIPECORE_STATUS=completed"));
+        assert!(reports[0].render().contains("-> exit code 0"));
+    }
+
+    #[test]
+    fn verify_needs_a_readable_target() {
+        let plain = Args::try_parse_from(["wipecore"]).unwrap();
+        let verify = Args::try_parse_from(["wipecore", "--verify"]).unwrap();
+        assert!(!needs_read_back(&plain));
+        assert!(needs_read_back(&verify));
+
+        // Everyone denied read-data: the file opens for writing only
+        let path = std::env::temp_dir().join(format!("wipecore-wo-{}", std::process::id()));
+        std::fs::write(&path, [0xAAu8; 512]).unwrap();
+        let icacls = |how: &[&str]| {
+            std::process::Command::new("icacls")
+                .arg(&path)
+                .args(how)
+                .output()
+                .unwrap()
+        };
+        assert!(icacls(&["/deny", "*S-1-1-0:(RD)"]).status.success());
+
+        hold_output();
+        let checked = open_wipe_target(&path, true).map(drop);
+        let write_only = open_wipe_target(&path, false).map(drop);
+        take_output();
+        icacls(&["/reset"]);
+        std::fs::remove_file(&path).ok();
+
+        let err = checked.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().starts_with("verification unavailable:"));
+        write_only.unwrap();
+    }
+
+    #[test]
+    fn scrubbed_file_times_are_the_neutral_value() {
+        let path = std::env::temp_dir().join(format!("wipecore-scrub-{}", std::process::id()));
+        std::fs::write(&path, vec![0xAAu8; 16 << 10]).unwrap();
+        let flags = ["wipecore", "--truncate", "--scrub-timestamps"];
+        let args = Args::try_parse_from(flags).unwrap();
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+
+        hold_output();
+        let mut sink = FnProgress(|_: &Progress| {});
+        let res = run_file_wipe(&path, 16 << 10, opts, &args, &mut sink);
+        take_output();
+
+        let meta = std::fs::metadata(&path);
+        std::fs::remove_file(&path).ok();
+        let report = res.unwrap().render();
+        let meta = meta.unwrap();
+        let neutral = std::time::UNIX_EPOCH + std::time::Duration::from_secs(NEUTRAL_FILE_TIME);
+        assert_eq!(meta.modified().unwrap(), neutral);
+        assert_eq!(meta.created().unwrap(), neutral);
+        assert!(report.contains("2000-01-01 00:00:00 UTC"));
+    }
+}
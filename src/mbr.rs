@@ -0,0 +1,177 @@
+use std::io;
+
+pub const MBR_SIZE: usize = 512;
+const PARTITION_TABLE_OFFSET: usize = 446;
+const PARTITION_ENTRY_SIZE: usize = 16;
+const GPT_PROTECTIVE_TYPE: u8 = 0xEE;
+const DISK_SIGNATURE_OFFSET: usize = 440;
+
+/// One primary entry from the MBR partition table.
+#[derive(Clone, Copy, Debug)]
+pub struct MbrPartition {
+    pub part_type: u8,
+    pub start_lba: u32,
+}
+
+/// Parse the four primary partition entries from sector 0.
+/// Empty entries (type 0 or no sectors) are skipped.
+pub fn parse_mbr(sector: &[u8]) -> io::Result<Vec<MbrPartition>> {
+    if sector.len() < MBR_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "MBR sector is shorter than 512 bytes",
+        ));
+    }
+
+    if sector[510] != 0x55 || sector[511] != 0xAA {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No MBR boot signature (0x55AA) in sector 0",
+        ));
+    }
+
+    let mut parts = Vec::new();
+
+    for i in 0..4 {
+        let e = &sector[PARTITION_TABLE_OFFSET + i * PARTITION_ENTRY_SIZE..][..PARTITION_ENTRY_SIZE];
+        let part_type = e[4];
+        let start_lba = u32::from_le_bytes([e[8], e[9], e[10], e[11]]);
+        let sectors = u32::from_le_bytes([e[12], e[13], e[14], e[15]]);
+
+        if part_type == 0 || sectors == 0 {
+            continue;
+        }
+
+        parts.push(MbrPartition {
+            part_type,
+            start_lba,
+        });
+    }
+
+    Ok(parts)
+}
+
+/// Sectors between the MBR and the first partition, as (first_lba, sector_count).
+/// Returns None when there is nothing to clear.
+pub fn mbr_gap_range(parts: &[MbrPartition]) -> io::Result<Option<(u64, u64)>> {
+    // on GPT disks sector 1 holds the GPT header, never touch it
+    if parts.iter().any(|p| p.part_type == GPT_PROTECTIVE_TYPE) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Disk uses GPT (protective MBR found); there is no MBR gap to wipe",
+        ));
+    }
+
+    let first_start = match parts.iter().map(|p| p.start_lba).min() {
+        Some(s) => s as u64,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "MBR has no partitions; gap is undefined",
+            ))
+        }
+    };
+
+    if first_start <= 1 {
+        return Ok(None);
+    }
+
+    Ok(Some((1, first_start - 1)))
+}
+
+/// Bytes of the disk an MBR can't reach: its 32-bit LBAs stop at 2^32 sectors
+/// (2 TiB with 512-byte sectors). None when the whole disk is addressable.
+pub fn mbr_unaddressable(size: u64, sector_size: u64) -> Option<u64> {
+    let limit = (1u64 << 32) * sector_size;
+    (size > limit).then(|| size - limit)
+}
+
+/// A blank MBR: boot signature and disk signature, no partitions.
+pub fn empty_mbr(disk_signature: u32, sector_size: usize) -> Vec<u8> {
+    let mut sector = vec![0u8; sector_size.max(MBR_SIZE)];
+    sector[DISK_SIGNATURE_OFFSET..DISK_SIGNATURE_OFFSET + 4]
+        .copy_from_slice(&disk_signature.to_le_bytes());
+    sector[510] = 0x55;
+    sector[511] = 0xAA;
+    sector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sector 0 with the boot signature and the given (type, start, sectors) entries.
+    fn synthetic_mbr(entries: &[(u8, u32, u32)]) -> Vec<u8> {
+        let mut sector = vec![0u8; MBR_SIZE];
+        for (i, &(part_type, start, sectors)) in entries.iter().enumerate() {
+            let e = PARTITION_TABLE_OFFSET + i * PARTITION_ENTRY_SIZE;
+            sector[e + 4] = part_type;
+            sector[e + 8..e + 12].copy_from_slice(&start.to_le_bytes());
+            sector[e + 12..e + 16].copy_from_slice(&sectors.to_le_bytes());
+        }
+        sector[510] = 0x55;
+        sector[511] = 0xAA;
+        sector
+    }
+
+    #[test]
+    fn gap_runs_from_sector_1_to_the_first_partition() {
+        let sector = synthetic_mbr(&[(0x07, 4096, 1000), (0x0C, 2048, 1000)]);
+        let parts = parse_mbr(&sector).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(mbr_gap_range(&parts).unwrap(), Some((1, 2047)));
+    }
+
+    #[test]
+    fn legacy_sector_63_layout() {
+        let parts = parse_mbr(&synthetic_mbr(&[(0x07, 63, 1000)])).unwrap();
+        assert_eq!(mbr_gap_range(&parts).unwrap(), Some((1, 62)));
+    }
+
+    #[test]
+    fn partition_at_sector_1_leaves_no_gap() {
+        let parts = parse_mbr(&synthetic_mbr(&[(0x07, 1, 1000)])).unwrap();
+        assert_eq!(mbr_gap_range(&parts).unwrap(), None);
+    }
+
+    #[test]
+    fn empty_entries_are_skipped() {
+        let sector = synthetic_mbr(&[(0x00, 63, 1000), (0x07, 2048, 0), (0x07, 4096, 8)]);
+        let parts = parse_mbr(&sector).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].start_lba, 4096);
+    }
+
+    #[test]
+    fn gpt_and_empty_tables_are_refused() {
+        let gpt = parse_mbr(&synthetic_mbr(&[(GPT_PROTECTIVE_TYPE, 1, 1000)])).unwrap();
+        assert!(mbr_gap_range(&gpt).is_err());
+        assert!(mbr_gap_range(&[]).is_err());
+    }
+
+    #[test]
+    fn missing_signature_or_short_sector_is_invalid() {
+        let mut sector = synthetic_mbr(&[(0x07, 2048, 1000)]);
+        sector[511] = 0;
+        assert!(parse_mbr(&sector).is_err());
+        assert!(parse_mbr(&[0u8; 100]).is_err());
+    }
+
+    #[test]
+    fn empty_mbr_has_signatures_and_no_partitions() {
+        let sector = empty_mbr(0xDEAD_BEEF, 4096);
+        assert_eq!(sector.len(), 4096);
+        let signature = &sector[DISK_SIGNATURE_OFFSET..][..4];
+        assert_eq!(signature, &0xDEAD_BEEFu32.to_le_bytes());
+        assert!(parse_mbr(&sector).unwrap().is_empty());
+        assert_eq!(empty_mbr(1, 0).len(), MBR_SIZE);
+    }
+
+    #[test]
+    fn unaddressable_past_2_tib_of_sectors() {
+        let limit = (1u64 << 32) * 512;
+        assert_eq!(mbr_unaddressable(limit, 512), None);
+        assert_eq!(mbr_unaddressable(limit + 512, 512), Some(512));
+        assert_eq!(mbr_unaddressable(limit + 512, 4096), None);
+    }
+}
@@ -0,0 +1,187 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Connect, send and receive are each given this long before giving up.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// JSON body POSTed to --notify-url when a target is done.
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub target: String,
+    /// "completed" or "failed"
+    pub status: String,
+    pub bytes: u64,
+    pub elapsed_secs: u64,
+    pub operator: Option<String>,
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Notification {
+    /// `error` is None for a wipe that completed.
+    pub fn new(
+        target: &str,
+        bytes: u64,
+        elapsed_secs: u64,
+        operator: Option<&str>,
+        error: Option<&str>,
+    ) -> Self {
+        Notification {
+            target: target.to_string(),
+            status: if error.is_some() {
+                "failed"
+            } else {
+                "completed"
+            }
+            .to_string(),
+            bytes,
+            elapsed_secs,
+            operator: operator.map(str::to_string),
+            hostname: env::var("COMPUTERNAME").ok(),
+            error: error.map(str::to_string),
+        }
+    }
+}
+
+/// An http:// URL split into (host, port, path). There is no TLS client
+/// here, so https:// is refused rather than sent in the clear.
+pub fn parse_http_url(url: &str) -> Result<(String, u16, String), String> {
+    let rest = match url.strip_prefix("http://") {
+        Some(rest) => rest,
+        None if url.starts_with("https://") => {
+            return Err("https:// is not supported; use an http:// endpoint".to_string())
+        }
+        None => return Err(format!("'{}' is not an http:// URL", url)),
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| format!("bad port '{}' in '{}'", port, url))?;
+            (host, port)
+        }
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(format!("no host in '{}'", url));
+    }
+
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// POST the notification and complain on failure; a dashboard being down
+/// never changes the outcome of the wipe itself.
+pub fn send_notification(url: &str, n: &Notification) {
+    match post_json(url, n) {
+        Ok(200..=299) => println!("[*] Notified {}", url),
+        Ok(code) => eprintln!("Warning: --notify-url {} answered HTTP {}", url, code),
+        Err(e) => eprintln!("Warning: could not notify {}: {}", url, e),
+    }
+}
+
+/// One HTTP/1.1 POST with `Connection: close`; returns the status code.
+fn post_json(url: &str, n: &Notification) -> io::Result<u16> {
+    let (host, port, path) =
+        parse_http_url(url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let body = serde_json::to_string(n).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} did not resolve", host))
+        })?;
+    let mut stream = TcpStream::connect_timeout(&addr, NOTIFY_TIMEOUT)?;
+    stream.set_read_timeout(Some(NOTIFY_TIMEOUT))?;
+    stream.set_write_timeout(Some(NOTIFY_TIMEOUT))?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    // only the status line matters: "HTTP/1.1 204 No Content"
+    let mut head = [0u8; 64];
+    let n = stream.read(&mut head)?;
+    String::from_utf8_lossy(&head[..n])
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no HTTP status line in reply"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Accept one request, answer 204 and hand back the body it carried.
+    fn serve_once(listener: TcpListener) -> thread::JoinHandle<String> {
+        thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut got = Vec::new();
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = conn.read(&mut buf).unwrap();
+                assert!(n > 0, "request ended early");
+                got.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&got).into_owned();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let len = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .and_then(|v| v.trim().parse::<usize>().ok())
+                        .unwrap();
+                    if body.len() >= len {
+                        conn.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+                        return body.to_string();
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn posted_body_carries_the_summary_fields() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = serve_once(listener);
+
+        let n = Notification::new(r"D:\old.img", 4096, 7, Some("alice"), Some("boom"));
+        assert_eq!(post_json(&url, &n).unwrap(), 204);
+
+        let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(body["target"], r"D:\old.img");
+        assert_eq!(body["status"], "failed");
+        assert_eq!(body["bytes"], 4096);
+        assert_eq!(body["elapsed_secs"], 7);
+        assert_eq!(body["operator"], "alice");
+        assert_eq!(body["error"], "boom");
+        assert!(body.get("hostname").is_some());
+    }
+
+    #[test]
+    fn https_and_bad_ports_are_refused() {
+        assert!(parse_http_url("https://example.com/x").is_err());
+        assert!(parse_http_url("http://example.com:99999/").is_err());
+        let parsed = parse_http_url("http://example.com:8080").unwrap();
+        assert_eq!(parsed, ("example.com".to_string(), 8080, "/".to_string()));
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+/// Registry key holding the PagingFiles multi-string.
+pub const MEMORY_MANAGEMENT_KEY: &str =
+    r"SYSTEM\CurrentControlSet\Control\Session Manager\Memory Management";
+
+/// Paths named by PagingFiles entries such as "C:\pagefile.sys 0 0".
+/// A "?:" drive (system-managed) resolves to the system drive.
+pub fn paging_file_paths(entries: &[String], system_drive: &str) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+
+    for entry in entries {
+        let path = match entry.split_whitespace().next() {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let path = match path.strip_prefix("?:") {
+            Some(rest) => format!("{}{}", system_drive, rest),
+            None => path.to_string(),
+        };
+
+        let path = PathBuf::from(path);
+        if !out.contains(&path) {
+            out.push(path);
+        }
+    }
+
+    out
+}
+
+/// hiberfil.sys always lives in the root of the system drive.
+pub fn hiberfil_path(system_drive: &str) -> PathBuf {
+    PathBuf::from(format!(r"{}\hiberfil.sys", system_drive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paging_files_resolve_against_the_system_drive() {
+        let entries = [
+            r"?:\pagefile.sys".to_string(),
+            r"D:\pagefile.sys 1024 4096".to_string(),
+            String::new(),
+            r"D:\pagefile.sys 0 0".to_string(),
+        ];
+        assert_eq!(
+            paging_file_paths(&entries, "C:"),
+            [
+                PathBuf::from(r"C:\pagefile.sys"),
+                PathBuf::from(r"D:\pagefile.sys")
+            ]
+        );
+        assert_eq!(hiberfil_path("C:"), PathBuf::from(r"C:\hiberfil.sys"));
+    }
+}
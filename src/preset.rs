@@ -0,0 +1,180 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One named preset: (flag id, value) pairs in the order they were saved.
+pub type Preset = Vec<(String, String)>;
+
+/// %APPDATA%\wipecore\presets.toml
+pub fn presets_path() -> io::Result<PathBuf> {
+    let appdata = env::var_os("APPDATA").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "APPDATA is not set; cannot locate presets.toml",
+        )
+    })?;
+    Ok(PathBuf::from(appdata).join("wipecore").join("presets.toml"))
+}
+
+/// The preset called `name`, or an error naming the ones that do exist.
+pub fn load_preset(path: &Path, name: &str) -> io::Result<Preset> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let presets = parse_presets(&text)?;
+    match presets.iter().find(|(n, _)| n == name) {
+        Some((_, values)) => Ok(values.clone()),
+        None => {
+            let known: Vec<&str> = presets.iter().map(|(n, _)| n.as_str()).collect();
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no preset '{}' in {} (known: {})",
+                    name,
+                    path.display(),
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                ),
+            ))
+        }
+    }
+}
+
+/// Add or replace the preset called `name`, keeping every other preset.
+pub fn save_preset(path: &Path, name: &str, values: Preset) -> io::Result<()> {
+    let mut presets = match fs::read_to_string(path) {
+        Ok(t) => parse_presets(&t)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    match presets.iter_mut().find(|(n, _)| n == name) {
+        Some(entry) => entry.1 = values,
+        None => presets.push((name.to_string(), values)),
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // write then rename so a crash never leaves a half-written file
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, render_presets(&presets))?;
+    fs::rename(&tmp, path)
+}
+
+/// The small TOML subset we write: `[name]` sections of `key = value` lines.
+fn parse_presets(text: &str) -> io::Result<Vec<(String, Preset)>> {
+    let mut presets: Vec<(String, Preset)> = Vec::new();
+
+    for (n, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            presets.push((name.trim().to_string(), Vec::new()));
+            continue;
+        }
+
+        let bad = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("presets.toml line {}: {}", n + 1, what),
+            )
+        };
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| bad("expected key = value"))?;
+        let section = presets
+            .last_mut()
+            .ok_or_else(|| bad("setting outside a [preset] section"))?;
+        let value = value.trim();
+        let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(quoted) => unescape(quoted),
+            None => value.to_string(),
+        };
+        section.1.push((key.trim().to_string(), value));
+    }
+
+    Ok(presets)
+}
+
+fn render_presets(presets: &[(String, Preset)]) -> String {
+    let mut out = String::from("# WipeCore presets (--save-preset / --preset)\n");
+    for (name, values) in presets {
+        out.push_str(&format!("\n[{}]\n", name));
+        for (key, value) in values {
+            // numbers and booleans stay bare, anything else is a TOML string
+            if value.parse::<u64>().is_ok() || value == "true" || value == "false" {
+                out.push_str(&format!("{} = {}\n", key, value));
+            } else {
+                out.push_str(&format!("{} = \"{}\"\n", key, escape(value)));
+            }
+        }
+    }
+    out
+}
+
+/// TOML basic-string escaping for the two characters a path or phrase can
+/// hold that would end or break the string: `\` and `"`.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Undo `escape`. Any other backslash is kept as written, so files saved
+/// before escaping (e.g. `"C:\dir"`) still load.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next @ ('\\' | '"'))) => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_and_backslashes_survive_save_then_load() {
+        let path = env::temp_dir()
+            .join(format!("wipecore-presets-{}", std::process::id()))
+            .join("presets.toml");
+        let values: Preset = vec![
+            ("report".to_string(), r"C:\Reports\disk 1.txt".to_string()),
+            ("operator".to_string(), r#"J. "Ops" Doe\"#.to_string()),
+            ("passes".to_string(), "3".to_string()),
+        ];
+
+        save_preset(&path, "office", values.clone()).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = load_preset(&path, "office").unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert!(text.contains(r#"report = "C:\\Reports\\disk 1.txt""#));
+        assert_eq!(loaded, values);
+    }
+
+    #[test]
+    fn unescaped_backslashes_from_older_files_still_load() {
+        let presets = parse_presets("[old]\nreport = \"C:\\dir\\a.txt\"\n").unwrap();
+        assert_eq!(presets[0].1[0].1, r"C:\dir\a.txt");
+    }
+}
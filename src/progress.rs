@@ -0,0 +1,631 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::env;
+use std::io::{stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::util::format_eta;
+use crate::win::set_console_title;
+
+/// Minimum time between two progress updates.
+const THROTTLE_MS: u128 = 200;
+
+/// Seconds between heartbeat lines when stdout is not a terminal.
+const HEARTBEAT_SECS: u64 = 5;
+
+/// Cleared by --no-osc.
+static OSC_ALLOWED: AtomicBool = AtomicBool::new(true);
+
+/// Cleared by --no-title or when there is no console window.
+static TITLE_ALLOWED: AtomicBool = AtomicBool::new(true);
+
+/// Set by --compact.
+static COMPACT: AtomicBool = AtomicBool::new(false);
+
+/// Target named in the window title while it is being wiped.
+static TITLE_TARGET: Mutex<Option<String>> = Mutex::new(None);
+
+thread_local! {
+    /// Lines a --multi-disk-parallel worker holds back; see `hold_output`.
+    static HELD_OUTPUT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// OSC 9;4 states understood by Windows Terminal.
+pub const OSC_CLEAR: u8 = 0;
+pub const OSC_NORMAL: u8 = 1;
+
+/// --no-osc: never send taskbar/tab progress escapes.
+pub fn disable_osc() {
+    OSC_ALLOWED.store(false, Ordering::Relaxed);
+}
+
+/// --compact: one progress line for the whole wipe instead of one per pass.
+pub fn enable_compact() {
+    COMPACT.store(true, Ordering::Relaxed);
+}
+
+/// Whether per-pass banners should be left out.
+pub fn is_compact() -> bool {
+    COMPACT.load(Ordering::Relaxed)
+}
+
+/// From now on `say` on this thread collects its lines instead of printing
+/// them, so wipe threads don't tear the --multi-disk-parallel dashboard.
+pub fn hold_output() {
+    HELD_OUTPUT.with(|h| *h.borrow_mut() = Some(String::new()));
+}
+
+/// Lines collected since `hold_output`; `say` prints directly again.
+pub fn take_output() -> String {
+    HELD_OUTPUT.with(|h| h.borrow_mut().take().unwrap_or_default())
+}
+
+/// Keep `line` if this thread holds its output; false if it should be printed.
+fn held(line: &str) -> bool {
+    HELD_OUTPUT.with(|h| match h.borrow_mut().as_mut() {
+        Some(held) => {
+            held.push_str(line);
+            held.push('\n');
+            true
+        }
+        None => false,
+    })
+}
+
+/// Print one line of wipe output, or keep it if this thread holds its output.
+pub fn say(line: impl AsRef<str>) {
+    if !held(line.as_ref()) {
+        println!("{}", line.as_ref());
+    }
+}
+
+/// `say` for warnings, which go to stderr when they are not held.
+pub fn say_err(line: impl AsRef<str>) {
+    if !held(line.as_ref()) {
+        eprintln!("{}", line.as_ref());
+    }
+}
+
+/// `ESC ] 9 ; 4 ; state ; percent BEL`, the Windows Terminal progress sequence.
+pub fn osc_progress(state: u8, percent: u8) -> String {
+    format!("\x1b]9;4;{};{}\x07", state, percent.min(100))
+}
+
+/// --no-title: leave the console window title alone.
+pub fn disable_title() {
+    TITLE_ALLOWED.store(false, Ordering::Relaxed);
+}
+
+/// Name the target shown in the window title from now on.
+pub fn set_title_target(target: &str) {
+    if let Ok(mut t) = TITLE_TARGET.lock() {
+        *t = Some(target.to_string());
+    }
+}
+
+/// Window title for a progress update, e.g. "WipeCore 42% (pass 1/3) - PhysicalDrive2".
+pub fn title_text(percent: u8, pass: u32, passes: u32, target: &str) -> String {
+    let name = target.strip_prefix(r"\\.\").unwrap_or(target);
+    format!(
+        "WipeCore {}% (pass {}/{}) - {}",
+        percent.min(100),
+        pass,
+        passes,
+        name
+    )
+}
+
+/// One progress snapshot for a write pass or a verification read.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress<'a> {
+    /// Phase label, e.g. "Pass" or "Verify"
+    pub label: &'a str,
+    pub pass: u32,
+    pub passes: u32,
+    pub done: u64,
+    pub total: u64,
+    pub speed_mib_s: f64,
+    pub eta_secs: u64,
+}
+
+impl Progress<'_> {
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        (self.done as f64 / self.total as f64) * 100.0
+    }
+
+    /// Percent of all passes together, counting earlier passes as complete.
+    pub fn overall_percent(&self) -> f64 {
+        let passes = self.passes.max(1) as f64;
+        (self.pass.saturating_sub(1) as f64 * 100.0 + self.percent()) / passes
+    }
+
+    /// Seconds left for this and every later pass at the current speed.
+    fn overall_eta_secs(&self) -> u64 {
+        let later = self.passes.saturating_sub(self.pass) as u64 * self.total;
+        let left = later + self.total.saturating_sub(self.done);
+        if self.speed_mib_s <= 0.0 {
+            return self.eta_secs;
+        }
+        (left as f64 / (self.speed_mib_s * 1024.0 * 1024.0)) as u64
+    }
+}
+
+/// Receives progress snapshots from the wipe / verify loops.
+pub trait ProgressSink {
+    fn report(&mut self, p: &Progress);
+}
+
+/// Adapts a closure into a sink, for frontends that draw their own UI.
+pub struct FnProgress<F>(pub F);
+
+impl<F: FnMut(&Progress)> ProgressSink for FnProgress<F> {
+    fn report(&mut self, p: &Progress) {
+        (self.0)(p)
+    }
+}
+
+/// Default sink: single carriage-return line on a terminal. When stdout is
+/// redirected the `\r` line never shows up, so print a full heartbeat line
+/// every few seconds instead. Inside Windows Terminal the tab's progress
+/// indicator is driven too (OSC 9;4).
+pub struct ConsoleProgress {
+    tty: bool,
+    last_beat: Option<Instant>,
+    osc: bool,
+    osc_active: bool,
+}
+
+impl Default for ConsoleProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleProgress {
+    pub fn new() -> Self {
+        let tty = stdout().is_terminal();
+        ConsoleProgress {
+            tty,
+            last_beat: None,
+            // only Windows Terminal sets WT_SESSION; other consoles would print garbage
+            osc: tty && OSC_ALLOWED.load(Ordering::Relaxed) && env::var_os("WT_SESSION").is_some(),
+            osc_active: false,
+        }
+    }
+}
+
+impl Drop for ConsoleProgress {
+    // an aborted wipe must not leave the tab stuck at some percentage
+    fn drop(&mut self) {
+        if self.osc_active {
+            print!("{}", osc_progress(OSC_CLEAR, 0));
+            stdout().flush().ok();
+        }
+    }
+}
+
+impl ProgressSink for ConsoleProgress {
+    fn report(&mut self, p: &Progress) {
+        if TITLE_ALLOWED.load(Ordering::Relaxed) {
+            if let Some(target) = TITLE_TARGET.lock().ok().and_then(|t| t.clone()) {
+                set_console_title(&title_text(p.percent() as u8, p.pass, p.passes, &target));
+            }
+        }
+
+        let line = if is_compact() {
+            compact_line(p)
+        } else {
+            progress_line(p)
+        };
+
+        if self.tty {
+            print!("\r{}", line);
+            if self.osc {
+                let finished = p.done == p.total;
+                let state = if finished { OSC_CLEAR } else { OSC_NORMAL };
+                print!("{}", osc_progress(state, p.percent() as u8));
+                self.osc_active = !finished;
+            }
+            stdout().flush().ok();
+            return;
+        }
+
+        let due = match self.last_beat {
+            Some(t) => t.elapsed().as_secs() >= HEARTBEAT_SECS,
+            None => true,
+        };
+        if due || p.done == p.total {
+            println!("{}", line);
+            self.last_beat = Some(Instant::now());
+        }
+    }
+}
+
+fn progress_line(p: &Progress) -> String {
+    format!(
+        "{} {}/{}:  {:6.2}%  |  {:7.2} MB/s  |   ETA {}",
+        p.label,
+        p.pass,
+        p.passes,
+        p.percent(),
+        p.speed_mib_s,
+        format_eta(p.eta_secs)
+    )
+}
+
+/// --compact line, e.g. "Pass 2/3  |  overall  45.10%  |  ...". Trailing
+/// spaces wipe out leftovers of a longer previous line.
+fn compact_line(p: &Progress) -> String {
+    format!(
+        "{} {}/{}  |  overall {:6.2}%  |  {:7.2} MB/s  |  ETA {}    ",
+        p.label,
+        p.pass,
+        p.passes,
+        p.overall_percent(),
+        p.speed_mib_s,
+        format_eta(p.overall_eta_secs())
+    )
+}
+
+/// Latest state of one --multi-disk-parallel row.
+#[derive(Clone, Debug, Default)]
+struct Row {
+    line: String,
+    /// Percent over all passes, as in the --compact line
+    percent: f64,
+    size: u64,
+    speed_mib_s: f64,
+    last_beat: Option<Instant>,
+}
+
+/// --multi-disk-parallel: one progress row per disk plus a total row,
+/// redrawn in place. Each wipe thread reports through its own `DashboardRow`.
+pub struct Dashboard {
+    names: Vec<String>,
+    rows: Mutex<(Vec<Row>, bool)>,
+    /// Cursor movement works here; otherwise rows print as heartbeat lines
+    tty: bool,
+}
+
+impl Dashboard {
+    /// `tty` should only be set once escape sequences are known to work.
+    pub fn new(names: Vec<String>, tty: bool) -> Arc<Self> {
+        let rows = vec![Row::default(); names.len()];
+        Arc::new(Dashboard {
+            names,
+            rows: Mutex::new((rows, false)),
+            tty: tty && stdout().is_terminal(),
+        })
+    }
+
+    /// Sink for the disk at `index` in the list given to `new`.
+    pub fn row(self: &Arc<Self>, index: usize) -> DashboardRow {
+        DashboardRow {
+            board: Arc::clone(self),
+            index,
+        }
+    }
+
+    fn update(&self, index: usize, p: &Progress) {
+        let mut guard = match self.rows.lock() {
+            Ok(g) => g,
+            Err(_) => return,
+        };
+        let (rows, drawn) = &mut *guard;
+        let row = &mut rows[index];
+        row.line = compact_line(p);
+        row.percent = p.overall_percent();
+        row.size = p.total;
+        row.speed_mib_s = p.speed_mib_s;
+
+        if !self.tty {
+            let due = match row.last_beat {
+                Some(t) => t.elapsed().as_secs() >= HEARTBEAT_SECS,
+                None => true,
+            };
+            if due || p.done == p.total {
+                println!("{}: {}", self.names[index], row.line);
+                row.last_beat = Some(Instant::now());
+            }
+            return;
+        }
+
+        let width = self.names.iter().map(|n| n.len()).max().unwrap_or(0);
+        let mut out = String::new();
+        if *drawn {
+            // back to the first row of the previous drawing
+            out.push_str(&format!("\x1b[{}A", rows.len() + 1));
+        }
+        for (name, row) in self.names.iter().zip(rows.iter()) {
+            out.push_str(&format!("\r\x1b[K{:width$}  {}\n", name, row.line));
+        }
+        out.push_str(&format!("\r\x1b[K{}\n", total_line(rows)));
+        print!("{}", out);
+        stdout().flush().ok();
+        *drawn = true;
+    }
+}
+
+/// Progress sink for one disk of a `Dashboard`.
+pub struct DashboardRow {
+    board: Arc<Dashboard>,
+    index: usize,
+}
+
+impl ProgressSink for DashboardRow {
+    fn report(&mut self, p: &Progress) {
+        self.board.update(self.index, p);
+    }
+}
+
+/// All disks together: percent weighted by disk size, and the summed speed
+/// of the disks still running.
+fn total_line(rows: &[Row]) -> String {
+    let size: u64 = rows.iter().map(|r| r.size).sum();
+    let percent = if size == 0 {
+        0.0
+    } else {
+        rows.iter().map(|r| r.percent * r.size as f64).sum::<f64>() / size as f64
+    };
+    let speed: f64 = rows
+        .iter()
+        .filter(|r| r.percent < 100.0)
+        .map(|r| r.speed_mib_s)
+        .sum();
+    format!(
+        "Total ({} disks)  |  {:6.2}%  |  {:7.2} MB/s",
+        rows.len(),
+        percent,
+        speed
+    )
+}
+
+/// Tracks timing for one phase and throttles updates to the sink.
+pub struct ProgressTracker {
+    start: Instant,
+    last_print: Instant,
+    /// --eta-window: recent throughput for the ETA instead of the phase average
+    eta_window: Option<RateWindow>,
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        ProgressTracker {
+            start: now,
+            last_print: now,
+            eta_window: None,
+        }
+    }
+
+    /// Base the ETA on the last `window_bytes` written, so it follows a
+    /// drop in speed (e.g. an SSD cache filling up) instead of averaging it away.
+    pub fn with_eta_window(window_bytes: Option<u64>) -> Self {
+        let mut tracker = Self::new();
+        tracker.eta_window = window_bytes.map(RateWindow::by_bytes);
+        tracker
+    }
+
+    /// Emit an update every ~200ms, and always on completion.
+    pub fn update(
+        &mut self,
+        sink: &mut dyn ProgressSink,
+        label: &str,
+        pass: u32,
+        passes: u32,
+        done: u64,
+        total: u64,
+    ) {
+        if self.last_print.elapsed().as_millis() < THROTTLE_MS && done != total {
+            return;
+        }
+
+        let secs = self.start.elapsed().as_secs_f64().max(0.000_001);
+        let done_mib = done as f64 / (1024.0 * 1024.0);
+        let speed_mib_s = done_mib / secs;
+
+        let eta_speed = match &mut self.eta_window {
+            Some(w) => {
+                w.push(secs, done);
+                w.rate_mib_s().unwrap_or(speed_mib_s)
+            }
+            None => speed_mib_s,
+        };
+
+        let remain_bytes = total.saturating_sub(done);
+        let eta_secs = if eta_speed > 0.0 {
+            (remain_bytes as f64 / (1024.0 * 1024.0) / eta_speed).max(0.0) as u64
+        } else {
+            0
+        };
+
+        sink.report(&Progress {
+            label,
+            pass,
+            passes,
+            done,
+            total,
+            speed_mib_s,
+            eta_secs,
+        });
+        self.last_print = Instant::now();
+    }
+}
+
+/// How far back a RateWindow looks.
+#[derive(Clone, Copy, Debug)]
+enum WindowSpan {
+    Secs(f64),
+    Bytes(u64),
+}
+
+/// Throughput over a trailing window (of time or of bytes) instead of since the start.
+pub struct RateWindow {
+    span: WindowSpan,
+    samples: VecDeque<(f64, u64)>,
+}
+
+impl RateWindow {
+    pub fn new(window_secs: f64) -> Self {
+        RateWindow {
+            span: WindowSpan::Secs(window_secs),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Window covering the most recent `window_bytes` of progress.
+    pub fn by_bytes(window_bytes: u64) -> Self {
+        RateWindow {
+            span: WindowSpan::Bytes(window_bytes),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record `total_bytes` done at `secs` since the phase started.
+    pub fn push(&mut self, secs: f64, total_bytes: u64) {
+        self.samples.push_back((secs, total_bytes));
+
+        // keep one sample at or before the window start so the span stays full
+        while self.samples.len() > 2 {
+            let (t, b) = self.samples[1];
+            let outside = match self.span {
+                WindowSpan::Secs(w) => t <= secs - w,
+                WindowSpan::Bytes(w) => b <= total_bytes.saturating_sub(w),
+            };
+            if !outside {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// MiB/s across the window, None until two samples are apart in time.
+    pub fn rate_mib_s(&self) -> Option<f64> {
+        let (t0, b0) = *self.samples.front()?;
+        let (t1, b1) = *self.samples.back()?;
+        let dt = t1 - t0;
+        if dt <= 0.0 {
+            return None;
+        }
+        Some((b1 - b0) as f64 / (1024.0 * 1024.0) / dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn snapshot(pass: u32, passes: u32, done: u64, total: u64) -> Progress<'static> {
+        Progress {
+            label: "Pass",
+            pass,
+            passes,
+            done,
+            total,
+            speed_mib_s: 100.0,
+            eta_secs: 0,
+        }
+    }
+
+    #[test]
+    fn redirected_output_beats_at_most_every_few_seconds() {
+        let mut sink = ConsoleProgress {
+            tty: false,
+            last_beat: None,
+            osc: false,
+            osc_active: false,
+        };
+
+        sink.report(&snapshot(1, 1, 10, 100));
+        assert!(sink.last_beat.is_some(), "first update always prints");
+
+        let recent = Instant::now() - Duration::from_secs(1);
+        sink.last_beat = Some(recent);
+        sink.report(&snapshot(1, 1, 20, 100));
+        assert_eq!(sink.last_beat, Some(recent));
+
+        // the final update prints even inside the heartbeat interval
+        sink.report(&snapshot(1, 1, 100, 100));
+        assert_ne!(sink.last_beat, Some(recent));
+
+        sink.last_beat = Some(Instant::now() - Duration::from_secs(HEARTBEAT_SECS));
+        sink.report(&snapshot(1, 1, 30, 100));
+        assert!(sink.last_beat.unwrap().elapsed().as_secs() < HEARTBEAT_SECS);
+    }
+
+    #[test]
+    fn osc_sequence_carries_state_and_clamped_percent() {
+        assert_eq!(osc_progress(OSC_NORMAL, 42), "\x1b]9;4;1;42\x07");
+        assert_eq!(osc_progress(OSC_NORMAL, 250), "\x1b]9;4;1;100\x07");
+        assert_eq!(osc_progress(OSC_CLEAR, 0), "\x1b]9;4;0;0\x07");
+    }
+
+    #[test]
+    fn title_names_the_disk_without_the_device_prefix() {
+        assert_eq!(
+            title_text(42, 1, 3, r"\\.\PhysicalDrive2"),
+            "WipeCore 42% (pass 1/3) - PhysicalDrive2"
+        );
+        assert_eq!(
+            title_text(120, 3, 3, r"D:\vm.img"),
+            r"WipeCore 100% (pass 3/3) - D:\vm.img"
+        );
+    }
+
+    #[test]
+    fn rate_window_follows_a_drop_in_speed() {
+        const MIB: u64 = 1024 * 1024;
+        let mut by_time = RateWindow::new(10.0);
+        let mut by_bytes = RateWindow::by_bytes(200 * MIB);
+        assert_eq!(by_time.rate_mib_s(), None);
+
+        // 100 MiB/s for two seconds, then 10 MiB/s
+        for (secs, mib) in [(0.0, 0), (1.0, 100), (2.0, 200), (12.0, 300), (22.0, 400)] {
+            by_time.push(secs, mib * MIB);
+            by_bytes.push(secs, mib * MIB);
+        }
+        assert_eq!(by_time.rate_mib_s(), Some(10.0));
+        assert_eq!(by_bytes.rate_mib_s(), Some(10.0));
+    }
+
+    #[test]
+    fn compact_line_counts_earlier_and_later_passes() {
+        const MIB: u64 = 1024 * 1024;
+        let p = snapshot(2, 3, 500 * MIB, 1000 * MIB);
+        assert_eq!(p.overall_percent(), 50.0);
+        // half of pass 2 and all of pass 3 left at 100 MiB/s
+        assert_eq!(
+            compact_line(&p),
+            "Pass 2/3  |  overall  50.00%  |   100.00 MB/s  |  ETA 00:15    "
+        );
+    }
+
+    #[test]
+    fn total_line_weighs_disks_by_size_and_sums_running_speeds() {
+        let board = Dashboard::new(vec!["Disk1".into(), "Disk2".into()], false);
+        board.row(0).report(&snapshot(1, 1, 100, 100));
+        board.row(1).report(&Progress {
+            speed_mib_s: 30.0,
+            ..snapshot(1, 1, 0, 300)
+        });
+
+        let rows = &board.rows.lock().unwrap().0;
+        // the finished disk no longer adds to the speed
+        assert_eq!(
+            total_line(rows),
+            "Total (2 disks)  |   25.00%  |    30.00 MB/s"
+        );
+    }
+}
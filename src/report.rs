@@ -0,0 +1,248 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ata::{smart_attribute_name, SmartAttribute};
+use crate::util::{format_utc, size_format, unix_now};
+use crate::wipe::{
+    describe_pass, effective_passes, is_complement_pass, is_random_pass, pass_pattern, pass_seed,
+    pattern_hash, WipeOptions,
+};
+
+/// Plain-text wipe report written by --report, one "key : value" per line.
+pub struct Report {
+    fields: Vec<(String, String)>,
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Report { fields: Vec::new() }
+    }
+
+    pub fn add(&mut self, key: &str, value: impl ToString) {
+        self.fields.push((key.to_string(), value.to_string()));
+    }
+
+    pub fn render(&self) -> String {
+        let width = self.fields.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+
+        let mut out = String::from("=== WipeCore Wipe Report ===\n");
+        for (k, v) in &self.fields {
+            out.push_str(&format!("{:width$} : {}\n", k, v, width = width));
+        }
+        out
+    }
+
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        write_atomic(path, &self.render())?;
+        println!("[+] Report written to {}", path.display());
+        Ok(())
+    }
+}
+
+/// Write several reports (one per target) into a single file.
+pub fn write_reports(reports: &[Report], path: &Path) -> io::Result<()> {
+    let text: Vec<String> = reports.iter().map(|r| r.render()).collect();
+    write_atomic(path, &text.join("\n"))?;
+    println!("[+] Report written to {}", path.display());
+    Ok(())
+}
+
+/// --output-dir file for one target: "<target>-<kind>-<YYYYMMDD-HHMMSS>.<ext>",
+/// where target is its last path component with unsafe characters replaced.
+pub fn artifact_path(dir: &Path, target: &str, kind: &str, ext: &str, when: u64) -> PathBuf {
+    let last = target
+        .split(['\\', '/', ':'])
+        .rfind(|c| !c.is_empty() && *c != ".")
+        .unwrap_or("target");
+    let key: String = last
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // "2024-05-01 13:45:00 UTC" -> "20240501-134500"
+    let digits: String = format_utc(when)
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect();
+    let stamp = format!("{}-{}", &digits[..8], &digits[8..]);
+
+    dir.join(format!("{}-{}-{}.{}", key, kind, stamp, ext))
+}
+
+/// Write to "<path>.tmp" and rename it over `path`, so a crash never leaves
+/// a half-written report behind.
+fn write_atomic(path: &Path, text: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut f = File::create(&tmp)?;
+    f.write_all(text.as_bytes())?;
+    f.sync_all()?;
+    drop(f);
+
+    fs::rename(&tmp, path)
+}
+
+/// Standard report for one finished wipe of `target`.
+pub fn wipe_report(
+    target: &str,
+    size: u64,
+    opts: &WipeOptions,
+    started: u64,
+    post_hash: Option<&str>,
+    operator: Option<&str>,
+) -> Report {
+    let mut r = report_header(target, size, opts, started, operator);
+    match post_hash {
+        Some(h) => {
+            r.add("Post-wipe SHA-256", h);
+            // a verified fixed pattern means the hash is exactly that fill of `size` bytes
+            let last_pass = effective_passes(opts.mode, opts.passes);
+            if let Some(b) = pass_pattern(opts.mode, last_pass) {
+                r.add("Expected SHA-256", pattern_hash(b, size));
+            }
+        }
+        None => r.add("Post-wipe SHA-256", "not computed (run with --verify)"),
+    }
+    r.add("Result", "completed");
+    r
+}
+
+/// Report for a wipe or verification that stopped with `error`; it must never
+/// read as a completed wipe.
+pub fn failed_report(
+    target: &str,
+    size: u64,
+    opts: &WipeOptions,
+    started: u64,
+    operator: Option<&str>,
+    error: &str,
+) -> Report {
+    let mut r = report_header(target, size, opts, started, operator);
+    r.add("Post-wipe SHA-256", "not computed (wipe failed)");
+    r.add("Result", format!("FAILED ({})", error));
+    r
+}
+
+fn report_header(
+    target: &str,
+    size: u64,
+    opts: &WipeOptions,
+    started: u64,
+    operator: Option<&str>,
+) -> Report {
+    let passes = effective_passes(opts.mode, opts.passes);
+
+    let mut r = Report::new();
+    r.add("Target", target);
+    r.add("Size", format!("{} ({} bytes)", size_format(size), size));
+    r.add("Mode", format!("{:?}", opts.mode));
+    r.add("Passes", passes);
+    r.add("Final pattern", describe_pass(opts.mode, passes));
+    if opts.reverse {
+        r.add("Direction", "reverse (end to start)");
+    }
+    if let Some(base) = opts.seed {
+        let seeds: Vec<String> = (1..=passes)
+            // complement passes reuse the seed of the pass before them
+            .filter(|&p| is_random_pass(opts.mode, p) && !is_complement_pass(opts.mode, p))
+            .map(|p| format!("pass {}={}", p, pass_seed(base, p)))
+            .collect();
+        if !seeds.is_empty() {
+            r.add("Random seeds", seeds.join(", "));
+        }
+    }
+    if let Some(nonce) = opts.nonce {
+        r.add("Nonce", format!("{:016x}", nonce));
+    }
+    r.add("Started", format_utc(started));
+    r.add("Finished", format_utc(unix_now()));
+    r.add("Operator", operator.unwrap_or("unknown"));
+    r
+}
+
+/// --report-include-smart: one row per attribute with its raw value before
+/// and after the wipe, so counters that grew during the wipe stand out.
+pub fn add_smart(r: &mut Report, before: &[SmartAttribute], after: &[SmartAttribute]) {
+    for a in after {
+        let key = format!("SMART {:3} {}", a.id, smart_attribute_name(a.id));
+        let value = match before.iter().find(|b| b.id == a.id) {
+            Some(b) if b.raw == a.raw => format!("{} (unchanged, value {})", a.raw, a.current),
+            Some(b) => format!(
+                "{} -> {} ({:+}), value {} -> {}",
+                b.raw,
+                a.raw,
+                a.raw as i64 - b.raw as i64,
+                b.current,
+                a.current
+            ),
+            None => format!("n/a -> {}, value {}", a.raw, a.current),
+        };
+        r.add(&key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wipe::WipeMode;
+
+    fn field(report: &Report, key: &str) -> Option<String> {
+        report
+            .fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn operator_is_named_in_the_report() {
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+        let r = wipe_report("image.bin", 4096, &opts, 0, None, Some("alice"));
+        assert_eq!(field(&r, "Operator").as_deref(), Some("alice"));
+        assert!(r.render().lines().any(|l| l.ends_with(": alice")));
+
+        let r = failed_report("image.bin", 4096, &opts, 0, None, "boom");
+        assert_eq!(field(&r, "Operator").as_deref(), Some("unknown"));
+    }
+
+    #[test]
+    fn smart_rows_pair_before_and_after() {
+        let attr = |id, current, raw| SmartAttribute { id, current, raw };
+        let before = [attr(5, 100, 0), attr(9, 99, 1200)];
+        let after = [attr(5, 98, 8), attr(9, 99, 1200), attr(197, 100, 2)];
+
+        let mut r = Report::new();
+        add_smart(&mut r, &before, &after);
+
+        let rows: Vec<(&str, &str)> = r
+            .fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "SMART   5 Reallocated sectors",
+                    "0 -> 8 (+8), value 100 -> 98"
+                ),
+                ("SMART   9 Power-on hours", "1200 (unchanged, value 99)"),
+                ("SMART 197 Pending sectors", "n/a -> 2, value 100"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// One finished target as recorded by --resume.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    size: u64,
+    /// Last-write time in nanoseconds since the Unix epoch
+    mtime: u64,
+    path: String,
+}
+
+/// --resume state for a batch of file wipes: one "size mtime path" line per
+/// target that finished, appended as soon as it does. A file only counts as
+/// done while its path, size and last-write time all still match, so a new
+/// file created at the same path is wiped again.
+pub struct ResumeState {
+    path: PathBuf,
+    done: Vec<Entry>,
+}
+
+impl ResumeState {
+    /// Read the state file; a missing one means nothing has finished yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(ResumeState {
+            path: path.to_path_buf(),
+            done: text.lines().filter_map(parse_entry).collect(),
+        })
+    }
+
+    /// True if `target` was wiped by an earlier run and hasn't changed since.
+    pub fn is_done(&self, target: &str) -> bool {
+        match current_entry(target) {
+            Ok(now) => self.done.contains(&now),
+            Err(_) => false,
+        }
+    }
+
+    /// Record `target` as finished, as it is on disk right now.
+    pub fn mark_done(&mut self, target: &str) -> io::Result<()> {
+        let entry = current_entry(target)?;
+
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(f, "{} {} {}", entry.size, entry.mtime, entry.path)?;
+        // the record must survive the crash it exists for
+        f.sync_all()?;
+
+        self.done.push(entry);
+        Ok(())
+    }
+
+    /// Forget the batch once every target in it has finished.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Entry for `target` from its current metadata.
+fn current_entry(target: &str) -> io::Result<Entry> {
+    let meta = fs::metadata(target)?;
+    let mtime = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+
+    Ok(Entry {
+        size: meta.len(),
+        mtime,
+        path: target.to_string(),
+    })
+}
+
+/// "size mtime path"; the path is last so it may contain spaces.
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut parts = line.splitn(3, ' ');
+    let size = parts.next()?.parse().ok()?;
+    let mtime = parts.next()?.parse().ok()?;
+    let path = parts.next()?.to_string();
+    Some(Entry { size, mtime, path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::{hold_output, take_output, FnProgress, Progress};
+    use crate::wipe::{wipe_file_with_sink, WipeMode, WipeOptions};
+    use std::fs::File;
+
+    #[test]
+    fn resumed_batch_skips_finished_files_and_wipes_the_rest() {
+        let dir = std::env::temp_dir().join(format!("wipecore-resume-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let state_path = dir.join("state");
+        let targets: Vec<String> = ["a.bin", "b.bin", "c.bin"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, [0xAAu8; 4096]).unwrap();
+                path.display().to_string()
+            })
+            .collect();
+
+        // the interrupted run got through a.bin only
+        ResumeState::load(&state_path)
+            .unwrap()
+            .mark_done(&targets[0])
+            .unwrap();
+
+        let mut state = ResumeState::load(&state_path).unwrap();
+        let pending: Vec<&String> = targets.iter().filter(|t| !state.is_done(t)).collect();
+        assert_eq!(pending, [&targets[1], &targets[2]]);
+
+        let opts = WipeOptions::new(WipeMode::Zeros, 1);
+        hold_output();
+        for target in pending {
+            let mut f = File::options().read(true).write(true).open(target).unwrap();
+            let mut sink = FnProgress(|_: &Progress| {});
+            wipe_file_with_sink(&mut f, 4096, &opts, &mut sink).unwrap();
+            drop(f);
+            state.mark_done(target).unwrap();
+        }
+        take_output();
+
+        let contents: Vec<Vec<u8>> = targets.iter().map(|t| fs::read(t).unwrap()).collect();
+        let every_target_done = targets.iter().all(|t| state.is_done(t));
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(contents[0], [0xAAu8; 4096]);
+        assert!(contents[1..].iter().all(|c| c.iter().all(|&b| b == 0)));
+        assert!(every_target_done);
+    }
+
+    #[test]
+    fn a_changed_file_is_no_longer_done() {
+        let path = std::env::temp_dir().join(format!("wipecore-resume-new-{}", std::process::id()));
+        let state_path = path.with_extension("state");
+        fs::write(&path, [0u8; 512]).unwrap();
+        let target = path.display().to_string();
+
+        let mut state = ResumeState::load(&state_path).unwrap();
+        state.mark_done(&target).unwrap();
+        // a new file at the same path, with a different size
+        fs::write(&path, [0u8; 1024]).unwrap();
+        let reloaded = ResumeState::load(&state_path).unwrap();
+        let done = reloaded.is_done(&target);
+
+        fs::remove_file(&path).ok();
+        reloaded.clear().unwrap();
+        assert!(!done);
+        assert!(!state_path.exists());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::wipe::WipeMode;
+
+/// WRITE SAME(16) operation code.
+pub const WRITE_SAME_16: u8 = 0x93;
+
+/// Blocks per WRITE SAME command, so each one finishes well inside its timeout.
+pub const WRITE_SAME_MAX_BLOCKS: u64 = 1 << 16;
+
+/// Largest logical block the pass-through buffer holds.
+pub const WRITE_SAME_MAX_SECTOR: usize = 4096;
+
+/// CDB telling the device to repeat the one-block data buffer over `blocks` blocks from `lba`.
+pub fn write_same16_cdb(lba: u64, blocks: u32) -> [u8; 16] {
+    let mut cdb = [0u8; 16];
+    cdb[0] = WRITE_SAME_16;
+    cdb[2..10].copy_from_slice(&lba.to_be_bytes());
+    cdb[10..14].copy_from_slice(&blocks.to_be_bytes());
+    cdb
+}
+
+/// (lba, blocks) commands covering `blocks` blocks from `first_lba`.
+pub fn write_same_ranges(first_lba: u64, blocks: u64) -> Vec<(u64, u32)> {
+    let mut ranges = Vec::new();
+    let mut done = 0;
+    while done < blocks {
+        let n = (blocks - done).min(WRITE_SAME_MAX_BLOCKS);
+        ranges.push((first_lba + done, n as u32));
+        done += n;
+    }
+    ranges
+}
+
+/// Why --write-same can't be used for this wipe, or None if it is worth trying.
+/// `bus_type` is STORAGE_DEVICE_DESCRIPTOR.BusType from the storage query.
+pub fn write_same_blocker(mode: WipeMode, bus_type: u32, sector_size: u64) -> Option<&'static str> {
+    if !matches!(mode, WipeMode::Zeros) {
+        return Some("only --mode zeros can be offloaded");
+    }
+    // SCSI, Fibre Channel, RAID, iSCSI, SAS take SCSI commands natively
+    if !matches!(bus_type, 1 | 6 | 8 | 9 | 10) {
+        return Some("the disk's bus does not take SCSI commands");
+    }
+    if sector_size as usize > WRITE_SAME_MAX_SECTOR {
+        return Some("the sector size is larger than 4096 bytes");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_same_cdb_is_big_endian_lba_and_count() {
+        let cdb = write_same16_cdb(0x0102_0304_0506_0708, 0x0001_0000);
+        assert_eq!(cdb, [0x93, 0, 1, 2, 3, 4, 5, 6, 7, 8, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn ranges_cover_every_block_in_bounded_commands() {
+        let ranges = write_same_ranges(100, 2 * WRITE_SAME_MAX_BLOCKS + 5);
+        assert_eq!(
+            ranges,
+            [
+                (100, 1 << 16),
+                (100 + (1 << 16), 1 << 16),
+                (100 + (2 << 16), 5)
+            ]
+        );
+        assert!(write_same_ranges(0, 0).is_empty());
+    }
+
+    #[test]
+    fn only_zero_wipes_of_scsi_disks_are_offloaded() {
+        assert_eq!(write_same_blocker(WipeMode::Zeros, 10, 512), None);
+        assert_eq!(write_same_blocker(WipeMode::Zeros, 1, 4096), None);
+        assert!(write_same_blocker(WipeMode::Random, 10, 512).is_some());
+        // SATA and NVMe fall back to streaming zeros
+        assert!(write_same_blocker(WipeMode::Zeros, 11, 512).is_some());
+        assert!(write_same_blocker(WipeMode::Zeros, 17, 512).is_some());
+        assert!(write_same_blocker(WipeMode::Zeros, 10, 8192).is_some());
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::wipe::WipeTarget;
+
+/// ERROR_INVALID_PARAMETER, what quirky devices return for a large write.
+const ERROR_INVALID_PARAMETER: i32 = 87;
+
+/// ERROR_IO_DEVICE, what a genuinely bad sector returns.
+const ERROR_IO_DEVICE: i32 = 1117;
+
+/// Hidden --simulate-bad-sectors: a file target whose writes fail over the
+/// given byte offsets. A multi-sector write that touches one is rejected with
+/// ERROR_INVALID_PARAMETER, so the per-sector fallback runs; the sector write
+/// itself then fails with ERROR_IO_DEVICE. Reads pass straight through.
+pub struct BadSectorSim<'a> {
+    inner: &'a mut File,
+    bad: Vec<u64>,
+    sector_size: u64,
+}
+
+impl<'a> BadSectorSim<'a> {
+    pub fn new(inner: &'a mut File, bad: &[u64], sector_size: u64) -> Self {
+        // fail whole sectors, like a device would
+        let mut bad: Vec<u64> = bad.iter().map(|o| o - o % sector_size).collect();
+        bad.sort_unstable();
+        bad.dedup();
+        BadSectorSim {
+            inner,
+            bad,
+            sector_size,
+        }
+    }
+}
+
+impl Write for BadSectorSim<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = self.inner.stream_position()?;
+        let end = start + buf.len() as u64;
+        let hit = self
+            .bad
+            .iter()
+            .any(|&b| b < end && b + self.sector_size > start);
+        if !hit {
+            return self.inner.write(buf);
+        }
+
+        if buf.len() as u64 > self.sector_size {
+            Err(io::Error::from_raw_os_error(ERROR_INVALID_PARAMETER))
+        } else {
+            Err(io::Error::from_raw_os_error(ERROR_IO_DEVICE))
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Read for BadSectorSim<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for BadSectorSim<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl WipeTarget for BadSectorSim<'_> {
+    fn sync_data(&self) -> io::Result<()> {
+        self.inner.sync_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::{hold_output, take_output, FnProgress, Progress};
+    use crate::wipe::{wipe_file_reported, WipeMode, WipeOptions};
+
+    #[test]
+    fn simulated_bad_sectors_are_skipped_and_reported() {
+        let path = std::env::temp_dir().join(format!("wipecore-sim-{}", std::process::id()));
+        std::fs::write(&path, vec![0xAAu8; 8192]).unwrap();
+        let mut file = File::options().read(true).write(true).open(&path).unwrap();
+        let mut sim = BadSectorSim::new(&mut file, &[1000, 5000, 5100], 512);
+        let mut opts = WipeOptions::new(WipeMode::Zeros, 1);
+        opts.buffer_size = 4096;
+
+        hold_output();
+        let res = wipe_file_reported(&mut sim, 8192, &opts, &mut FnProgress(|_: &Progress| {}));
+        let out = take_output();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // the two unwritable sectors keep the old data and fail the byte count
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::WriteZero);
+        assert!(out.contains("Sector at offset 512 could not be written"));
+        assert!(out.contains("Sector at offset 4608 could not be written"));
+        assert!(out.contains("2 sector(s) could not be overwritten (first at offset 512)"));
+        for (i, sector) in data.chunks(512).enumerate() {
+            let expected = if i == 1 || i == 9 { 0xAA } else { 0 };
+            assert!(sector.iter().all(|&b| b == expected), "sector {}", i);
+        }
+    }
+}
@@ -1,6 +1,12 @@
 use std::ffi::OsStr;
+use std::io::{self, BufRead, Write};
 use std::iter;
 use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
 
 /// format size
 pub fn size_format(size_bytes: u64) -> String {
@@ -8,7 +14,8 @@ pub fn size_format(size_bytes: u64) -> String {
     const UNITS: [&str; 4] = ["Bytes", "KB", "MB", "GB"]; // limit it into GB
     let mut i = 0;
 
-    while size >= 900.0 && i < UNITS.len() - 1 {
+    // roll over at 1024, or earlier when rounding would print "1024"
+    while size >= 1023.5 && i < UNITS.len() - 1 {
         size /= 1024.0;
         i += 1;
     }
@@ -35,6 +42,113 @@ pub fn format_eta(seconds: u64) -> String {
     }
 }
 
+/// Seconds to write `size` bytes `passes` times at `rate` MB/s (MiB, as progress shows).
+pub fn estimate_seconds(size: u64, passes: u32, rate: f64) -> u64 {
+    (size as f64 * passes as f64 / (rate * 1024.0 * 1024.0)).ceil() as u64
+}
+
+/// Parse a byte count like "4096", "512M", "1G" or "4TiB" (binary units).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let digits = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    if digits == 0 {
+        return Err(format!("invalid size '{}': expected e.g. 4096, 512M, 1G", s));
+    }
+
+    let n: u64 = t[..digits]
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+
+    let mult: u64 = match t[digits..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        other => {
+            return Err(format!(
+                "invalid size suffix '{}' in '{}': use K, M, G or T",
+                other, s
+            ))
+        }
+    };
+
+    n.checked_mul(mult)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+/// Parse a duration like "90", "30s", "5m" or "2h" (bare numbers are seconds).
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let t = s.trim();
+    let digits = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    if digits == 0 {
+        return Err(format!("invalid duration '{}': expected e.g. 30s, 5m, 2h", s));
+    }
+
+    let n: u64 = t[..digits]
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+
+    let mult: u64 = match t[digits..].to_ascii_lowercase().as_str() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        other => {
+            return Err(format!(
+                "invalid duration suffix '{}' in '{}': use s, m or h",
+                other, s
+            ))
+        }
+    };
+
+    n.checked_mul(mult)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{}' is too large", s))
+}
+
+/// --scrub-timestamps sets every file time to this: 2000-01-01 00:00:00 UTC.
+pub const NEUTRAL_FILE_TIME: u64 = 946_684_800;
+
+/// Format Unix seconds as "2024-05-01 13:45:00 UTC".
+pub fn format_utc(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let rem = unix_secs % 86_400;
+
+    // civil-from-days (Howard Hinnant), valid for any date after 1970
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        y,
+        m,
+        d,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Current time as Unix seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Lowercase hex string, e.g. for SHA-256 digests.
+pub fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 /// Convert &str to a Windows wide string buffer (ending with 0).
 pub fn to_pcwstr(s: &str) -> Vec<u16> {
     OsStr::new(s)
@@ -42,3 +156,357 @@ pub fn to_pcwstr(s: &str) -> Vec<u16> {
         .chain(iter::once(0))
         .collect()
 }
+
+/// One target per line; blank lines and `#` comments are skipped.
+pub fn read_target_list<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut targets = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let entry = line.trim();
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        targets.push(entry.to_string());
+    }
+    Ok(targets)
+}
+
+/// Which query finally produced a disk size (see `resolve_zero_size`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SizeSource {
+    /// IOCTL_DISK_GET_LENGTH_INFO on the given retry
+    Retry(u32),
+    /// cylinders x tracks x sectors x bytes-per-sector
+    Geometry,
+}
+
+/// Some enclosures report 0 bytes until the drive spins up: ask `length`
+/// again up to `retries` times, `delay` apart, then fall back to `geometry`.
+pub fn resolve_zero_size(
+    mut length: impl FnMut() -> io::Result<u64>,
+    geometry: impl FnOnce() -> io::Result<u64>,
+    retries: u32,
+    delay: Duration,
+) -> Option<(u64, SizeSource)> {
+    for attempt in 1..=retries {
+        std::thread::sleep(delay);
+        if let Ok(size) = length() {
+            if size > 0 {
+                return Some((size, SizeSource::Retry(attempt)));
+            }
+        }
+    }
+
+    match geometry() {
+        Ok(size) if size > 0 => Some((size, SizeSource::Geometry)),
+        _ => None,
+    }
+}
+
+/// FILE_ATTRIBUTE_REPARSE_POINT: symlinks, junctions and other redirected paths.
+const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+/// True when Windows file attributes mark a reparse point.
+pub fn is_reparse_point(attributes: u32) -> bool {
+    attributes & FILE_ATTRIBUTE_REPARSE_POINT != 0
+}
+
+/// Set on Ctrl+C; every chunk loop stops at its next chunk boundary.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// True while `read_answer` waits, so the Ctrl+C handler knows to cancel the read.
+static PROMPTING: AtomicBool = AtomicBool::new(false);
+
+/// Ask every running wipe to flush and stop after its current chunk.
+pub fn request_interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub fn is_prompting() -> bool {
+    PROMPTING.load(Ordering::SeqCst)
+}
+
+/// Err(Interrupted) once Ctrl+C was pressed, for loops and steps that must not go on.
+pub fn check_interrupted() -> io::Result<()> {
+    if is_interrupted() {
+        return Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "interrupted by Ctrl+C",
+        ));
+    }
+    Ok(())
+}
+
+/// Print `prompt`, read one line from `input` and return it trimmed.
+/// Ctrl-C or a closed input gives an empty answer, which every caller treats
+/// as "cancel", instead of a raw read error.
+pub fn read_answer(prompt: &str, mut input: impl BufRead) -> io::Result<String> {
+    println!("{}", prompt);
+    print!("> ");
+    io::stdout().flush()?; // make sure the prompt shows
+
+    let mut line = String::new();
+    PROMPTING.store(true, Ordering::SeqCst);
+    let read = input.read_line(&mut line);
+    PROMPTING.store(false, Ordering::SeqCst);
+
+    match read {
+        // a Ctrl+C cancels even if the console read went on and got a whole line
+        _ if is_interrupted() => {}
+        Ok(0) => {}
+        Ok(_) => return Ok(line.trim().to_string()),
+        // ERROR_OPERATION_ABORTED: the Ctrl+C handler cancelled the console read
+        // (a plain Interrupted error never gets here, read_line retries those)
+        Err(e) if e.raw_os_error() == Some(995) => {}
+        Err(e) => return Err(e),
+    }
+
+    // the cursor is still after "> "; move off it before the caller's message
+    println!();
+    Ok(String::new())
+}
+
+/// Every destructive confirmation goes through here: the answer must equal
+/// `expected` exactly, ignoring surrounding whitespace (case matters).
+pub fn confirm(prompt: &str, expected: &str, input: impl BufRead) -> io::Result<bool> {
+    Ok(read_answer(prompt, input)? == expected)
+}
+
+/// Standard wording for a typed confirmation phrase.
+pub fn phrase_prompt(phrase: &str) -> String {
+    format!("Type EXACTLY: {}\nAnything else will cancel.", phrase)
+}
+
+/// --confirm-checksum alphabet: no 0/O or 1/I/L, which are easy to misread.
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+/// A fresh 4-character code for --confirm-checksum, different every run.
+pub fn confirm_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..4)
+        .map(|_| CODE_CHARS[rng.gen_range(0..CODE_CHARS.len())] as char)
+        .collect()
+}
+
+/// The typed code must match exactly; only case and surrounding space are forgiven.
+pub fn code_matches(typed: &str, code: &str) -> bool {
+    typed.trim().eq_ignore_ascii_case(code)
+}
+
+fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| exts.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+/// Raw or virtual disk image files, judged by extension.
+pub fn is_disk_image(path: &Path) -> bool {
+    has_extension(path, &["img", "vhd", "vhdx"])
+}
+
+/// Images AttachVirtualDisk can present as a disk; raw .img files can't be.
+pub fn is_attachable_image(path: &Path) -> bool {
+    has_extension(path, &["vhd", "vhdx"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor, Read};
+
+    /// Console input whose read was cancelled by the Ctrl+C handler.
+    struct CancelledRead;
+
+    impl Read for CancelledRead {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::from_raw_os_error(995))
+        }
+    }
+
+    /// Input that fails for some other reason.
+    struct BrokenRead;
+
+    impl Read for BrokenRead {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::from_raw_os_error(6))
+        }
+    }
+
+    #[test]
+    fn read_answer_trims_the_line() {
+        assert_eq!(read_answer("?", &b"  YES \r\n"[..]).unwrap(), "YES");
+    }
+
+    #[test]
+    fn cancelled_prompt_is_an_empty_answer() {
+        assert_eq!(read_answer("?", BufReader::new(CancelledRead)).unwrap(), "");
+        assert!(!confirm("?", "YES", BufReader::new(CancelledRead)).unwrap());
+    }
+
+    #[test]
+    fn closed_input_is_an_empty_answer() {
+        assert_eq!(read_answer("?", &b""[..]).unwrap(), "");
+        assert!(!confirm("?", "YES", &b""[..]).unwrap());
+    }
+
+    #[test]
+    fn other_read_errors_are_returned() {
+        assert!(read_answer("?", BufReader::new(BrokenRead)).is_err());
+    }
+
+    #[test]
+    fn format_utc_known_dates() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(NEUTRAL_FILE_TIME), "2000-01-01 00:00:00 UTC");
+        // leap day, and the last second of a leap year
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1_735_689_599), "2024-12-31 23:59:59 UTC");
+        assert_eq!(format_utc(1_714_571_100), "2024-05-01 13:45:00 UTC");
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size(" 512B "), Ok(512));
+        assert_eq!(parse_size("64k"), Ok(64u64 << 10));
+        assert_eq!(parse_size("8M"), Ok(8u64 << 20));
+        assert_eq!(parse_size("1GiB"), Ok(1u64 << 30));
+        assert_eq!(parse_size("4TB"), Ok(4u64 << 40));
+    }
+
+    #[test]
+    fn parse_size_rejects_garbage() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("99999999T").is_err());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5M"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration(" 2h "), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_garbage() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("18446744073709551615h").is_err());
+    }
+
+    #[test]
+    fn zero_size_is_retried_before_geometry() {
+        let mut answers = vec![Ok(0), Ok(0), Ok(4096)].into_iter();
+        let resolved = resolve_zero_size(
+            || answers.next().unwrap(),
+            || panic!("geometry asked although a retry worked"),
+            5,
+            Duration::ZERO,
+        );
+        assert_eq!(resolved, Some((4096, SizeSource::Retry(3))));
+    }
+
+    #[test]
+    fn zero_size_falls_back_to_geometry() {
+        let length = || Err(io::Error::from(io::ErrorKind::Other));
+        let resolved = resolve_zero_size(length, || Ok(8192), 2, Duration::ZERO);
+        assert_eq!(resolved, Some((8192, SizeSource::Geometry)));
+
+        let never = resolve_zero_size(|| Ok(0), || Ok(0), 2, Duration::ZERO);
+        assert_eq!(never, None);
+    }
+
+    #[test]
+    fn confirm_accepts_the_exact_phrase() {
+        assert!(confirm("?", "WIPE-DISK-2", &b"WIPE-DISK-2\n"[..]).unwrap());
+    }
+
+    #[test]
+    fn confirm_ignores_surrounding_whitespace() {
+        assert!(confirm("?", "YES", &b"  YES \t\r\n"[..]).unwrap());
+    }
+
+    #[test]
+    fn confirm_is_case_sensitive() {
+        assert!(!confirm("?", "YES", &b"yes\n"[..]).unwrap());
+        assert!(!confirm("?", "WIPE-DISK-2", &b"wipe-disk-2\n"[..]).unwrap());
+    }
+
+    #[test]
+    fn confirm_rejects_empty_input() {
+        assert!(!confirm("?", "YES", &b"\n"[..]).unwrap());
+        assert!(!confirm("?", "YES", &b""[..]).unwrap());
+    }
+
+    #[test]
+    fn size_format_rolls_over_at_1024() {
+        assert_eq!(size_format(999), "999 Bytes");
+        assert_eq!(size_format(1023), "1023 Bytes");
+        assert_eq!(size_format(1024), "1.00 KB");
+        assert_eq!(size_format(10 * 1024), "10 KB");
+        assert_eq!(size_format(1_048_063), "1023 KB");
+        // 1023.5 KB would round to "1024 KB"
+        assert_eq!(size_format(1_048_064), "1.00 MB");
+        assert_eq!(size_format(5u64 << 40), "5120 GB");
+    }
+
+    #[test]
+    fn code_matches_forgives_case_and_spaces_only() {
+        assert!(code_matches("K7QP", "K7QP"));
+        assert!(code_matches("  k7qp\r\n", "K7QP"));
+        assert!(!code_matches("K7Q", "K7QP"));
+        assert!(!code_matches("K7 QP", "K7QP"));
+        assert!(!code_matches("", "K7QP"));
+    }
+
+    #[test]
+    fn confirm_codes_use_the_unambiguous_alphabet() {
+        let code = confirm_code();
+        assert_eq!(code.len(), 4);
+        assert!(code.bytes().all(|c| CODE_CHARS.contains(&c)));
+    }
+
+    #[test]
+    fn target_list_from_a_pipe_skips_blanks_and_comments() {
+        let lines = [
+            r"\\.\PhysicalDrive2",
+            "",
+            "# spares",
+            r"  D:\old.img  ",
+            r"#\\.\PhysicalDrive3",
+        ];
+        let piped = Cursor::new(lines.join("\r\n"));
+        let targets = read_target_list(piped).unwrap();
+        assert_eq!(targets, vec![r"\\.\PhysicalDrive2", r"D:\old.img"]);
+
+        assert!(read_target_list(Cursor::new("")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reparse_point_bit_is_detected_among_other_attributes() {
+        // FILE_ATTRIBUTE_ARCHIVE | FILE_ATTRIBUTE_REPARSE_POINT, as on a symlinked file
+        assert!(is_reparse_point(0x20 | 0x400));
+        // a junction is a directory with the reparse bit
+        assert!(is_reparse_point(0x10 | 0x400));
+        assert!(!is_reparse_point(0x20));
+        assert!(!is_reparse_point(0));
+    }
+
+    #[test]
+    fn estimate_for_a_spec_sheet_rate() {
+        // 1 TiB, 3 passes at 200 MB/s
+        let secs = estimate_seconds(1 << 40, 3, 200.0);
+        assert_eq!(secs, 15729);
+        assert_eq!(format_eta(secs), "04:22:09");
+        assert_eq!(format_eta(estimate_seconds(300 << 20, 1, 100.0)), "00:03");
+    }
+}
@@ -1,31 +1,152 @@
+use std::collections::BTreeMap;
 use std::env;
-use std::fs::OpenOptions;
+use std::fs::{FileTimes, OpenOptions};
+use std::os::windows::fs::{FileTimesExt, OpenOptionsExt};
 use std::io;
-use std::io::Write;
-use windows::core::PCWSTR;
-use windows::Win32::Foundation::CloseHandle;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::Foundation::{
+    CloseHandle, GetLastError, BOOL, BOOLEAN, ERROR_ALREADY_EXISTS, ERROR_SUCCESS, FALSE, HANDLE,
+    TRUE, WIN32_ERROR,
+};
+use windows::Win32::Security::PSECURITY_DESCRIPTOR;
 use windows::Win32::Storage::FileSystem::{
-    CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_GENERIC_READ, FILE_SHARE_READ, FILE_SHARE_WRITE,
-    OPEN_EXISTING,
+    CreateFileW, GetDiskFreeSpaceExW, GetDiskFreeSpaceW, FILE_ATTRIBUTE_NORMAL, FILE_FLAG_NO_BUFFERING,
+    FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+use windows::Win32::Storage::Vhd::{
+    AttachVirtualDisk, DetachVirtualDisk, GetVirtualDiskPhysicalPath, OpenVirtualDisk,
+    ATTACH_VIRTUAL_DISK_FLAG_NO_DRIVE_LETTER, DETACH_VIRTUAL_DISK_FLAG_NONE,
+    OPEN_VIRTUAL_DISK_FLAG_NONE, VIRTUAL_DISK_ACCESS_ALL, VIRTUAL_STORAGE_TYPE,
+    VIRTUAL_STORAGE_TYPE_DEVICE_UNKNOWN, VIRTUAL_STORAGE_TYPE_VENDOR_UNKNOWN,
+};
+use windows::Win32::System::Console::{
+    GetConsoleMode, GetConsoleTitleW, GetStdHandle, SetConsoleCtrlHandler, SetConsoleMode,
+    SetConsoleTitleW, CONSOLE_MODE, CTRL_BREAK_EVENT, CTRL_C_EVENT,
+    ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+};
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_MULTI_SZ};
+use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+use windows::Win32::System::Threading::{
+    CreateMutexW, GetCurrentProcess, GetCurrentThread, GetPriorityClass, ReleaseMutex,
+    SetPriorityClass, SetThreadPriority, BELOW_NORMAL_PRIORITY_CLASS, PROCESS_CREATION_FLAGS,
+    THREAD_MODE_BACKGROUND_BEGIN, THREAD_MODE_BACKGROUND_END,
+};
+use windows::Win32::System::WindowsProgramming::GetUserNameW;
+use windows::Win32::System::IO::{CancelIoEx, DeviceIoControl};
+use windows::Win32::System::Ioctl::{
+    DISK_CACHE_INFORMATION, DISK_GEOMETRY, FSCTL_DISMOUNT_VOLUME, FSCTL_LOCK_VOLUME,
+    GET_LENGTH_INFORMATION, IOCTL_DISK_GET_CACHE_INFORMATION, IOCTL_DISK_GET_DRIVE_GEOMETRY,
+    IOCTL_DISK_GET_LENGTH_INFO, IOCTL_DISK_SET_CACHE_INFORMATION,
 };
-use windows::Win32::System::IO::DeviceIoControl;
-use windows::Win32::System::Ioctl::{GET_LENGTH_INFORMATION, IOCTL_DISK_GET_LENGTH_INFO};
 
-use crate::util::{size_format, to_pcwstr};
-use crate::wipe::{wipe_file, WipeMode};
+use crate::aligned::{AlignedBuf, IO_ALIGN};
+use crate::ata::{
+    decode_trim_support, nonzero_samples, parse_smart_attributes, sample_offsets, SmartAttribute,
+    TrimSupport, IDENTIFY_LEN,
+};
+use crate::exec::ExecAfter;
+use crate::fsmeta::{
+    align_region, follow_up_regions, parse_boot_sector, FsKind, MetaRegion, BOOT_SECTOR_LEN,
+};
+use crate::identity::{
+    confirm_details, expand_phrase, identity_mismatch, is_excluded, matches_guid, matches_wwn,
+    needs_serial_interlock, parse_device_descriptor, parse_device_ids, parse_physical_sector_size,
+    serial_matches, DeviceId, DiskIdentity,
+};
+use crate::mbr::{empty_mbr, mbr_gap_range, mbr_unaddressable, parse_mbr, MBR_SIZE};
+use crate::notify::{send_notification, Notification};
+use crate::pagefile::{hiberfil_path, paging_file_paths, MEMORY_MANAGEMENT_KEY};
+use crate::report::{add_smart, artifact_path, failed_report, wipe_report, write_reports};
+use crate::scsi::{write_same16_cdb, write_same_blocker, write_same_ranges, WRITE_SAME_MAX_SECTOR};
+use crate::util::{
+    check_interrupted, code_matches, confirm, confirm_code, is_interrupted, is_prompting,
+    phrase_prompt, read_answer, request_interrupt, resolve_zero_size, size_format, to_pcwstr,
+    unix_now, SizeSource,
+};
+use crate::progress::{say, say_err, set_title_target, ConsoleProgress, ProgressTracker};
+use crate::wipe::{
+    align_to_physical, check_aligned, check_io_size, check_strict_sector_size, describe_pass,
+    effective_passes, limit_len, overwrite_tail, pattern_verify, print_limit_warning,
+    probe_addressing, resolve_range, verify_samples, verify_wipe, wipe_file, write_stamp, Units,
+    WipeOptions,
+};
 
 const IOCTL_VOLUME_BASE: u32 = 'V' as u32;
 const METHOD_BUFFERED: u32 = 0;
 const FILE_ANY_ACCESS: u32 = 0;
 
 /// CTL_CODE(DeviceType, Function, Method, Access)
+/// Out-of-range fields would bleed into their neighbours, so they are rejected.
 const fn ctl_code(device_type: u32, function: u32, method: u32, access: u32) -> u32 {
+    debug_assert!(device_type < 0x10000, "CTL_CODE device type must fit in 16 bits");
+    debug_assert!(function < 0x1000, "CTL_CODE function must fit in 12 bits");
+    debug_assert!(method < 4, "CTL_CODE method must fit in 2 bits");
+    debug_assert!(access < 4, "CTL_CODE access must fit in 2 bits");
     (device_type << 16) | (access << 14) | (function << 2) | method
 }
 
 const IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS: u32 =
     ctl_code(IOCTL_VOLUME_BASE, 0, METHOD_BUFFERED, FILE_ANY_ACCESS);
 
+const IOCTL_STORAGE_BASE: u32 = 0x2D;
+const IOCTL_STORAGE_QUERY_PROPERTY: u32 =
+    ctl_code(IOCTL_STORAGE_BASE, 0x500, METHOD_BUFFERED, FILE_ANY_ACCESS);
+
+// STORAGE_PROPERTY_ID / STORAGE_QUERY_TYPE values
+const STORAGE_DEVICE_PROPERTY: u32 = 0;
+const STORAGE_DEVICE_ID_PROPERTY: u32 = 2;
+const STORAGE_ACCESS_ALIGNMENT_PROPERTY: u32 = 6;
+const PROPERTY_STANDARD_QUERY: u32 = 0;
+
+const FILE_READ_WRITE_ACCESS: u32 = 3;
+const FILE_WRITE_ACCESS: u32 = 2;
+
+const IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES: u32 =
+    ctl_code(IOCTL_STORAGE_BASE, 0x501, METHOD_BUFFERED, FILE_WRITE_ACCESS);
+
+const IOCTL_SCSI_BASE: u32 = 0x04;
+const IOCTL_ATA_PASS_THROUGH: u32 =
+    ctl_code(IOCTL_SCSI_BASE, 0x40B, METHOD_BUFFERED, FILE_READ_WRITE_ACCESS);
+
+const IOCTL_SCSI_PASS_THROUGH: u32 =
+    ctl_code(IOCTL_SCSI_BASE, 0x401, METHOD_BUFFERED, FILE_READ_WRITE_ACCESS);
+
+const SCSI_IOCTL_DATA_OUT: u8 = 0;
+const SCSI_SENSE_LEN: usize = 32;
+
+const DEVICE_DSM_ACTION_TRIM: u32 = 1;
+const DEVICE_DSM_FLAG_ENTIRE_DATA_SET_RANGE: u32 = 1;
+
+const ATA_FLAGS_DRDY_REQUIRED: u16 = 0x01;
+const ATA_FLAGS_DATA_IN: u16 = 0x02;
+const ATA_CMD_IDENTIFY_DEVICE: u8 = 0xEC;
+const ATA_CMD_SMART: u8 = 0xB0;
+const SMART_READ_DATA: u8 = 0xD0;
+// SMART commands carry this signature in LBA mid / high
+const SMART_LBA_MID: u8 = 0x4F;
+const SMART_LBA_HIGH: u8 = 0xC2;
+
+/// Name and value of every IOCTL this tool sends, for --dump-ctl-codes.
+const CTL_CODE_TABLE: [(&str, u32); 7] = [
+    ("IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS", IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS),
+    ("IOCTL_STORAGE_QUERY_PROPERTY", IOCTL_STORAGE_QUERY_PROPERTY),
+    ("IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES", IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES),
+    ("IOCTL_ATA_PASS_THROUGH", IOCTL_ATA_PASS_THROUGH),
+    ("IOCTL_SCSI_PASS_THROUGH", IOCTL_SCSI_PASS_THROUGH),
+    ("IOCTL_DISK_GET_LENGTH_INFO", IOCTL_DISK_GET_LENGTH_INFO),
+    ("IOCTL_DISK_GET_DRIVE_GEOMETRY", IOCTL_DISK_GET_DRIVE_GEOMETRY),
+];
+
+/// Re-reads of a disk that reports 0 bytes before trying its geometry.
+const ZERO_SIZE_RETRIES: u32 = 3;
+const ZERO_SIZE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Sectors read back by --trim-then-verify-unreadable.
+const TRIM_VERIFY_SAMPLES: u64 = 64;
+
 #[allow(non_snake_case)]
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
@@ -35,18 +156,582 @@ struct DiskExtent {
     ExtentLength: i64,
 }
 
+/// Enough for spanned/striped volumes; more extents than this are ignored.
+const MAX_VOLUME_EXTENTS: usize = 16;
+
 #[allow(non_snake_case)]
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct VolumeDiskExtentsLocal {
     NumberOfDiskExtents: u32,
-    Extents: [DiskExtent; 1],
+    Extents: [DiskExtent; MAX_VOLUME_EXTENTS],
+}
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct StoragePropertyQueryLocal {
+    PropertyId: u32,
+    QueryType: u32,
+    AdditionalParameters: [u8; 1],
+}
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct DeviceManageDataSetAttributesLocal {
+    Size: u32,
+    Action: u32,
+    Flags: u32,
+    ParameterBlockOffset: u32,
+    ParameterBlockLength: u32,
+    DataSetRangesOffset: u32,
+    DataSetRangesLength: u32,
+}
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct AtaPassThroughExLocal {
+    Length: u16,
+    AtaFlags: u16,
+    PathId: u8,
+    TargetId: u8,
+    Lun: u8,
+    ReservedAsUchar: u8,
+    DataTransferLength: u32,
+    TimeOutValue: u32,
+    ReservedAsUlong: u32,
+    DataBufferOffset: usize,
+    PreviousTaskFile: [u8; 8],
+    CurrentTaskFile: [u8; 8],
+}
+
+#[repr(C)]
+struct AtaDataBuffer {
+    apt: AtaPassThroughExLocal,
+    data: [u8; IDENTIFY_LEN],
+}
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct ScsiPassThroughLocal {
+    Length: u16,
+    ScsiStatus: u8,
+    PathId: u8,
+    TargetId: u8,
+    Lun: u8,
+    CdbLength: u8,
+    SenseInfoLength: u8,
+    DataIn: u8,
+    DataTransferLength: u32,
+    TimeOutValue: u32,
+    DataBufferOffset: usize,
+    SenseInfoOffset: u32,
+    Cdb: [u8; 16],
+}
+
+#[repr(C)]
+struct WriteSameBuffer {
+    spt: ScsiPassThroughLocal,
+    sense: [u8; SCSI_SENSE_LEN],
+    data: [u8; WRITE_SAME_MAX_SECTOR],
+}
+
+/// Settings for the interactive disk wipe flow.
+pub struct DiskWipeOptions {
+    pub wipe: WipeOptions,
+    pub verify: bool,
+    pub system_disk: Option<u32>,
+    /// Safe mode: refuse disks larger than this many bytes
+    pub size_limit: Option<u64>,
+    /// Disks with these serial numbers are always protected
+    pub exclude_serials: Vec<String>,
+    /// Write a wipe report here when done
+    pub report: Option<PathBuf>,
+    /// --output-dir: write the report here, named after the target
+    pub output_dir: Option<PathBuf>,
+    /// Optional sub-range to wipe instead of the whole disk
+    pub offset: Option<u64>,
+    pub length: Option<u64>,
+    pub units: Units,
+    /// Go through selection and confirmation but never open for write
+    pub dry_run: bool,
+    /// TRIM the whole disk afterwards and sample-check it reads back as zeros
+    pub trim_verify: bool,
+    /// Write the "WIPED BY WIPECORE" banner to sector 0 afterwards
+    pub stamp: bool,
+    /// Write a blank MBR to sector 0 afterwards
+    pub reinit_mbr: bool,
+    /// Reinit as MBR even past the 2^32-sector limit
+    pub force_mbr: bool,
+    /// Open the disk with FILE_FLAG_NO_BUFFERING
+    pub no_buffering: bool,
+    /// Write/read probe sectors out to the last one before wiping
+    pub probe_addressing: bool,
+    /// Turn the disk's write cache off until the wipe and verify are done
+    pub disable_write_cache: bool,
+    pub operator: Option<String>,
+    /// Hidden test switch: write at most this many bytes
+    pub limit_bytes: Option<u64>,
+    /// Print the full identity/geometry block before the phrase
+    pub confirm_details: bool,
+    /// Spot-check this many sectors after the wipe
+    pub verify_samples: Option<u64>,
+    /// Full read-back counting every byte off the final pattern
+    pub pattern_verify: bool,
+    /// Zero through SCSI WRITE SAME where the disk supports it
+    pub write_same: bool,
+    /// Put SMART attributes from before and after the wipe in the report
+    pub report_smart: bool,
+    /// --confirm-phrase-format; None means WIPE-DISK-N
+    pub phrase_format: Option<String>,
+    /// Also ask for a random per-run code after the phrase
+    pub confirm_checksum: bool,
+    /// Command to run once the wipe is done
+    pub exec_after: Option<ExecAfter>,
+    /// --notify-url endpoint for the JSON summary
+    pub notify_url: Option<String>,
+    /// Refuse sizes and offsets that are not whole sectors instead of trying them
+    pub strict_sector_size: bool,
+}
+
+impl DiskWipeOptions {
+    /// --report, or a file named after `target` in --output-dir.
+    fn report_path(&self, target: &str, started: u64) -> Option<PathBuf> {
+        self.report.clone().or_else(|| {
+            let dir = self.output_dir.as_ref()?;
+            Some(artifact_path(dir, target, "report", "txt", started))
+        })
+    }
+}
+
+/// Named mutex held while one target is being wiped, so a second WipeCore
+/// run on the same target fails instead of interleaving writes.
+pub struct TargetLock {
+    handle: OwnedHandle,
+}
+
+impl TargetLock {
+    pub fn acquire(target: &str) -> io::Result<TargetLock> {
+        let name = target_lock_name(target);
+
+        // Global\ needs SeCreateGlobalPrivilege (admin); plain users get Local\
+        let handle = match create_owned_mutex(&format!(r"Global\{}", name)) {
+            Ok(h) => h,
+            Err(_) => create_owned_mutex(&format!(r"Local\{}", name))?,
+        };
+
+        match handle {
+            Some(handle) => Ok(TargetLock { handle }),
+            None => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already being wiped by another WipeCore", target),
+            )),
+        }
+    }
+}
+
+impl Drop for TargetLock {
+    fn drop(&mut self) {
+        // the handle itself is closed when the field drops
+        unsafe {
+            let _ = ReleaseMutex(self.handle.0);
+        }
+    }
+}
+
+/// Mutex name for a target: case-insensitive, no backslashes (not allowed in names).
+fn target_lock_name(target: &str) -> String {
+    format!(
+        "WipeCore-{}",
+        target.trim().to_ascii_lowercase().replace('\\', "/")
+    )
+}
+
+/// New mutex owned by us, or None if someone already holds that name.
+fn create_owned_mutex(name: &str) -> io::Result<Option<OwnedHandle>> {
+    let wide = to_pcwstr(name);
+
+    let handle = unsafe { CreateMutexW(None, true, PCWSTR(wide.as_ptr())) }
+        .map(OwnedHandle::new)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("CreateMutexW failed: {e}")))?;
+
+    if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+        return Ok(None);
+    }
+
+    Ok(Some(handle))
+}
+
+/// Win32 handle that is closed on drop, so early returns can't leak it. The
+/// second field is what closes it: `CloseHandle`, or a stand-in in tests.
+struct OwnedHandle(HANDLE, fn(HANDLE) -> windows::core::Result<()>);
+
+impl OwnedHandle {
+    fn new(handle: HANDLE) -> Self {
+        OwnedHandle(handle, |h| unsafe { CloseHandle(h) })
+    }
+}
+
+impl Drop for OwnedHandle {
+    fn drop(&mut self) {
+        if let Err(e) = (self.1)(self.0) {
+            eprintln!("Warning: CloseHandle failed: {e}");
+        }
+    }
+}
+
+/// Write cache setting saved by --disable-write-cache, put back on drop so a
+/// failed or interrupted wipe still leaves the disk as it was found.
+pub struct WriteCacheGuard {
+    handle: OwnedHandle,
+    saved: DISK_CACHE_INFORMATION,
+    disk_num: u32,
+}
+
+impl WriteCacheGuard {
+    /// Turn off the write cache of PhysicalDrive`disk_num`. None when it was
+    /// already off, so there is nothing to restore.
+    pub fn disable(disk_num: u32) -> io::Result<Option<WriteCacheGuard>> {
+        let handle = open_disk_handle(disk_num, true)?;
+        let saved = get_cache_info(&handle)?;
+        if saved.WriteCacheEnabled.0 == 0 {
+            say(format!(
+                "[*] PhysicalDrive{} write cache is already off.",
+                disk_num
+            ));
+            return Ok(None);
+        }
+
+        set_cache_info(&handle, &write_cache_request(&saved, false))?;
+        // some drives accept the request and keep caching anyway
+        if get_cache_info(&handle)?.WriteCacheEnabled.0 != 0 {
+            say(format!(
+                "Warning: PhysicalDrive{} still reports write caching; verify may hit the cache.",
+                disk_num
+            ));
+        } else {
+            say(format!(
+                "[*] PhysicalDrive{} write cache disabled.",
+                disk_num
+            ));
+        }
+
+        Ok(Some(WriteCacheGuard {
+            handle,
+            saved,
+            disk_num,
+        }))
+    }
+}
+
+impl Drop for WriteCacheGuard {
+    fn drop(&mut self) {
+        let request = write_cache_request(&self.saved, true);
+        match set_cache_info(&self.handle, &request) {
+            Ok(()) => say(format!(
+                "[*] PhysicalDrive{} write cache re-enabled.",
+                self.disk_num
+            )),
+            Err(e) => say_err(format!(
+                "Warning: could not re-enable the write cache of PhysicalDrive{}: {}",
+                self.disk_num, e
+            )),
+        }
+    }
+}
+
+/// `saved` with the write cache switched; never asks the drive to persist it.
+fn write_cache_request(saved: &DISK_CACHE_INFORMATION, enabled: bool) -> DISK_CACHE_INFORMATION {
+    let mut request = *saved;
+    request.WriteCacheEnabled = BOOLEAN(enabled as u8);
+    request.ParametersSavable = BOOLEAN(0);
+    request
+}
+
+fn get_cache_info(handle: &OwnedHandle) -> io::Result<DISK_CACHE_INFORMATION> {
+    let mut info = DISK_CACHE_INFORMATION::default();
+    let mut br: u32 = 0;
+
+    unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_DISK_GET_CACHE_INFORMATION,
+            None,
+            0,
+            Some(&mut info as *mut _ as *mut _),
+            std::mem::size_of::<DISK_CACHE_INFORMATION>() as u32,
+            Some(&mut br),
+            None,
+        )
+    }
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("cache query failed: {e}")))?;
+
+    Ok(info)
+}
+
+fn set_cache_info(handle: &OwnedHandle, info: &DISK_CACHE_INFORMATION) -> io::Result<()> {
+    let mut br: u32 = 0;
+
+    unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_DISK_SET_CACHE_INFORMATION,
+            Some(info as *const _ as *const _),
+            std::mem::size_of::<DISK_CACHE_INFORMATION>() as u32,
+            None,
+            0,
+            Some(&mut br),
+            None,
+        )
+    }
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("cache update failed: {e}")))
+}
+
+/// A .vhd/.vhdx attached by --inside-image; detached again on drop.
+pub struct AttachedImage {
+    handle: OwnedHandle,
+    physical_path: String,
+}
+
+impl AttachedImage {
+    /// Attach `image` read/write without drive letters, so Windows doesn't
+    /// mount its volumes, and look up the \\.\PhysicalDriveN it appears as.
+    pub fn attach(image: &Path) -> io::Result<AttachedImage> {
+        let wide = to_pcwstr(&image.display().to_string());
+        let storage_type = VIRTUAL_STORAGE_TYPE {
+            DeviceId: VIRTUAL_STORAGE_TYPE_DEVICE_UNKNOWN,
+            VendorId: VIRTUAL_STORAGE_TYPE_VENDOR_UNKNOWN,
+        };
+        let vhd_error = |what: &str, err: WIN32_ERROR| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "{}({}) failed: {}",
+                    what,
+                    image.display(),
+                    io::Error::from_raw_os_error(err.0 as i32)
+                ),
+            )
+        };
+
+        let mut raw = HANDLE::default();
+        let err = unsafe {
+            OpenVirtualDisk(
+                &storage_type,
+                PCWSTR(wide.as_ptr()),
+                VIRTUAL_DISK_ACCESS_ALL,
+                OPEN_VIRTUAL_DISK_FLAG_NONE,
+                None,
+                &mut raw,
+            )
+        };
+        if err != ERROR_SUCCESS {
+            return Err(vhd_error("OpenVirtualDisk", err));
+        }
+        let handle = OwnedHandle::new(raw);
+
+        let err = unsafe {
+            AttachVirtualDisk(
+                handle.0,
+                PSECURITY_DESCRIPTOR::default(),
+                ATTACH_VIRTUAL_DISK_FLAG_NO_DRIVE_LETTER,
+                0,
+                None,
+                None,
+            )
+        };
+        if err != ERROR_SUCCESS {
+            return Err(vhd_error("AttachVirtualDisk", err));
+        }
+
+        let mut path = vec![0u16; 260];
+        let mut bytes = (path.len() * 2) as u32;
+        let err =
+            unsafe { GetVirtualDiskPhysicalPath(handle.0, &mut bytes, PWSTR(path.as_mut_ptr())) };
+        if err != ERROR_SUCCESS {
+            // closing the handle detaches it; it wasn't attached permanently
+            return Err(vhd_error("GetVirtualDiskPhysicalPath", err));
+        }
+
+        let len = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        Ok(AttachedImage {
+            handle,
+            physical_path: String::from_utf16_lossy(&path[..len]),
+        })
+    }
+
+    pub fn physical_path(&self) -> &str {
+        &self.physical_path
+    }
+}
+
+impl Drop for AttachedImage {
+    fn drop(&mut self) {
+        let err = unsafe { DetachVirtualDisk(self.handle.0, DETACH_VIRTUAL_DISK_FLAG_NONE, 0) };
+        if err != ERROR_SUCCESS {
+            eprintln!(
+                "Warning: could not detach {}: {}",
+                self.physical_path,
+                io::Error::from_raw_os_error(err.0 as i32)
+            );
+        }
+    }
+}
+
+/// CPU and I/O priority lowered by --low-priority, put back on drop.
+pub struct LowPriority {
+    saved_class: u32,
+}
+
+impl LowPriority {
+    /// Below-normal priority class for the process, plus background mode for
+    /// this thread, which also drops its I/O priority. The wipe runs here.
+    pub fn enter() -> io::Result<LowPriority> {
+        let process = unsafe { GetCurrentProcess() };
+        let saved_class = unsafe { GetPriorityClass(process) };
+        if saved_class == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let failed = |what: &str, e: windows::core::Error| {
+            io::Error::new(io::ErrorKind::Other, format!("{} failed: {e}", what))
+        };
+
+        let res = unsafe { SetPriorityClass(process, BELOW_NORMAL_PRIORITY_CLASS) };
+        res.map_err(|e| failed("SetPriorityClass", e))?;
+        // built before background mode so an error below still restores the class
+        let low = LowPriority { saved_class };
+
+        let res = unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) };
+        res.map_err(|e| failed("SetThreadPriority", e))?;
+
+        say("[*] Running at low CPU and I/O priority.");
+        Ok(low)
+    }
+}
+
+impl Drop for LowPriority {
+    fn drop(&mut self) {
+        // ending background mode fails harmlessly if it never began
+        let _ = unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_END) };
+        let class = PROCESS_CREATION_FLAGS(self.saved_class);
+        if let Err(e) = unsafe { SetPriorityClass(GetCurrentProcess(), class) } {
+            say_err(format!("Warning: could not restore the priority: {e}"));
+        }
+    }
+}
+
+/// Console window title from startup, put back on drop.
+pub struct ConsoleTitle {
+    original: Vec<u16>,
+}
+
+impl ConsoleTitle {
+    /// None when there is no console window to title.
+    pub fn save() -> Option<ConsoleTitle> {
+        let mut buf = vec![0u16; 1024];
+        let n = unsafe { GetConsoleTitleW(&mut buf) } as usize;
+        if n == 0 {
+            return None;
+        }
+        buf.truncate(n.min(buf.len() - 1));
+        buf.push(0);
+        Some(ConsoleTitle { original: buf })
+    }
+}
+
+impl Drop for ConsoleTitle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = SetConsoleTitleW(PCWSTR(self.original.as_ptr()));
+        }
+    }
+}
+
+/// Best effort: a failed title update is not worth interrupting a wipe for.
+pub fn set_console_title(title: &str) {
+    let wide = to_pcwstr(title);
+    unsafe {
+        let _ = SetConsoleTitleW(PCWSTR(wide.as_ptr()));
+    }
+}
+
+/// Turn on escape-sequence handling for stdout (conhost leaves it off), so
+/// cursor movement works. False when stdout is not a console or it refused.
+pub fn enable_vt_output() -> bool {
+    unsafe {
+        let out = match GetStdHandle(STD_OUTPUT_HANDLE) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+        let mut mode = CONSOLE_MODE(0);
+        if GetConsoleMode(out, &mut mode).is_err() {
+            return false;
+        }
+        SetConsoleMode(out, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING).is_ok()
+    }
+}
+
+/// Console control handler: the first Ctrl+C / Ctrl+Break lets the wipe
+/// finish its chunk and flush, or cancels an open prompt; a second one
+/// falls through to the default handler and kills the process.
+unsafe extern "system" fn on_console_ctrl(ctrl_type: u32) -> BOOL {
+    match ctrl_type {
+        CTRL_C_EVENT | CTRL_BREAK_EVENT if !is_interrupted() => {
+            request_interrupt();
+            // std retries a console read that Ctrl+C merely aborted; a cancelled
+            // one fails, so the prompt returns and reads as "cancel"
+            if is_prompting() {
+                if let Ok(input) = GetStdHandle(STD_INPUT_HANDLE) {
+                    let _ = CancelIoEx(input, None);
+                }
+            }
+            TRUE
+        }
+        _ => FALSE,
+    }
+}
+
+/// Route Ctrl+C to `request_interrupt` instead of ending the process mid-write.
+pub fn install_interrupt_handler() -> io::Result<()> {
+    unsafe { SetConsoleCtrlHandler(Some(on_console_ctrl), true) }
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("SetConsoleCtrlHandler: {e}")))
 }
 
 struct DiskInfo {
     index: u32,
     size_bytes: u64,
-    is_system: bool,
+    is_protected: bool,
+    excluded: bool,
+    identity: DiskIdentity,
+}
+
+impl DiskInfo {
+    /// A listed disk; protected if `protected` names it or its serial is excluded.
+    fn new(
+        index: u32,
+        size_bytes: u64,
+        identity: DiskIdentity,
+        protected: &BTreeMap<u32, Vec<String>>,
+        exclude_serials: &[String],
+    ) -> Self {
+        let excluded = is_excluded(identity.serial.as_deref(), exclude_serials);
+        DiskInfo {
+            index,
+            size_bytes,
+            is_protected: protected.contains_key(&index) || excluded,
+            excluded,
+            identity,
+        }
+    }
+}
+
+/// Disks the operator may pick for --wipe-disk.
+fn selectable_disks(disks: &[DiskInfo]) -> Vec<&DiskInfo> {
+    disks.iter().filter(|d| !d.is_protected).collect()
 }
 
 // public API used by main.rs
@@ -54,6 +739,69 @@ pub fn show_disk_size(disk_num: u32) -> io::Result<()> {
     let path = format!(r"\\.\PhysicalDrive{}", disk_num);
     println!("Opening physical drive: {}", path);
 
+    let size = disk_length(disk_num)?;
+
+    println!("Disk {} size: {}", disk_num, size_format(size));
+
+    Ok(())
+}
+
+/// Set created, modified and accessed time of an open file to `unix_secs`
+/// (SetFileTime). Done last, since any later write moves the modified time again.
+pub fn set_file_times(f: &std::fs::File, unix_secs: u64) -> io::Result<()> {
+    let t = std::time::UNIX_EPOCH + Duration::from_secs(unix_secs);
+    let times = FileTimes::new()
+        .set_created(t)
+        .set_modified(t)
+        .set_accessed(t);
+    f.set_times(times)
+}
+
+/// Free physical memory in bytes (GlobalMemoryStatusEx).
+pub fn available_memory() -> io::Result<u64> {
+    let mut status = MEMORYSTATUSEX {
+        dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..Default::default()
+    };
+
+    unsafe { GlobalMemoryStatusEx(&mut status) }.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("GlobalMemoryStatusEx failed: {e}"),
+        )
+    })?;
+
+    Ok(status.ullAvailPhys)
+}
+
+/// Name of the logged-in Windows user (GetUserNameW), for --operator's default.
+pub fn current_username() -> Option<String> {
+    let mut buf = vec![0u16; 257]; // UNLEN + 1
+    let mut len = buf.len() as u32;
+
+    unsafe { GetUserNameW(PWSTR(buf.as_mut_ptr()), &mut len) }.ok()?;
+
+    // len includes the terminating NUL
+    buf.truncate(len.saturating_sub(1) as usize);
+    let name = String::from_utf16_lossy(&buf);
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Print the numeric IOCTL codes so they can be checked against winioctl.h.
+pub fn dump_ctl_codes() {
+    println!("IOCTL control codes used by WipeCore:");
+    for (name, code) in CTL_CODE_TABLE {
+        println!("  {:<42} 0x{:08X}", name, code);
+    }
+}
+
+/// Exact device length in bytes from IOCTL_DISK_GET_LENGTH_INFO.
+pub fn disk_length(disk_num: u32) -> io::Result<u64> {
+    let path = format!(r"\\.\PhysicalDrive{}", disk_num);
     let wide = to_pcwstr(&path);
 
     let handle = unsafe {
@@ -67,14 +815,15 @@ pub fn show_disk_size(disk_num: u32) -> io::Result<()> {
             None,
         )
     }
+    .map(OwnedHandle::new)
     .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("CreateFileW failed: {e}")))?;
 
     let mut length_info = GET_LENGTH_INFORMATION { Length: 0 };
     let mut br: u32 = 0;
 
-    unsafe {
+    let res = unsafe {
         DeviceIoControl(
-            handle,
+            handle.0,
             IOCTL_DISK_GET_LENGTH_INFO,
             None,
             0,
@@ -83,14 +832,9 @@ pub fn show_disk_size(disk_num: u32) -> io::Result<()> {
             Some(&mut br),
             None,
         )
-    }
-    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("DeviceIoControl failed: {e}")))?;
+    };
 
-    unsafe {
-        if let Err(e) = CloseHandle(handle) {
-            eprintln!("Warning: CloseHandle failed: {e}");
-        }
-    }
+    res.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("DeviceIoControl failed: {e}")))?;
 
     let size_i64 = length_info.Length;
     if size_i64 < 0 {
@@ -99,34 +843,22 @@ pub fn show_disk_size(disk_num: u32) -> io::Result<()> {
             "Negative size returned from IOCTL_DISK_GET_LENGTH_INFO",
         ));
     }
-    let size = size_i64 as u64;
 
-    println!("Disk {} size: {}", disk_num, size_format(size));
-
-    Ok(())
+    Ok(size_i64 as u64)
 }
 
-pub fn list_disks(max_index: u32, system_disk_arg: Option<u32>) -> io::Result<()> {
-    let system_disk: u32 = match system_disk_arg {
-        Some(n) => {
-            println!("Using user-specified system disk: PhysicalDrive{}", n);
-            n
-        }
-        None => match detect_system_disk() {
-            Some(n) => {
-                println!("Auto-detected system disk: PhysicalDrive{}", n);
-                n
-            }
-            None => {
-                println!("Could not auto-detect system disk; defaulting to PhysicalDrive0.");
-                println!("You can override with: --system-disk <N>");
-                0
-            }
-        },
+/// With `quiet`, the system disk is still marked but resolved without narration.
+pub fn list_disks(max_index: u32, system_disk_arg: Option<u32>, quiet: bool) -> io::Result<()> {
+    let system_disk = if quiet {
+        system_disk_arg
+            .or_else(|| system_disk_from_volume().ok())
+            .unwrap_or(0)
+    } else {
+        resolve_system_disk(system_disk_arg)
     };
 
-    println!();
-    println!("Detected physical disks (0..{}):", max_index - 1);
+    say("");
+    say(format!("Detected physical disks (0..{}):", max_index - 1));
 
     let mut any = false;
 
@@ -147,7 +879,7 @@ pub fn list_disks(max_index: u32, system_disk_arg: Option<u32>) -> io::Result<()
         };
 
         let handle = match handle {
-            Ok(h) => h,
+            Ok(h) => OwnedHandle::new(h),
             Err(_) => continue,
         };
 
@@ -156,7 +888,7 @@ pub fn list_disks(max_index: u32, system_disk_arg: Option<u32>) -> io::Result<()
 
         let res = unsafe {
             DeviceIoControl(
-                handle,
+                handle.0,
                 IOCTL_DISK_GET_LENGTH_INFO,
                 None,
                 0,
@@ -167,68 +899,120 @@ pub fn list_disks(max_index: u32, system_disk_arg: Option<u32>) -> io::Result<()
             )
         };
 
-        unsafe {
-            if let Err(e) = CloseHandle(handle) {
-                eprintln!("Warning: CloseHandle failed for {}: {e}", path);
-            }
-        }
-
         if let Err(e) = res {
-            eprintln!("  [{}] {} - failed to get size: {}", i, path, e);
-            continue;
-        }
-
-        let size_i64 = length_info.Length;
-        if size_i64 <= 0 {
+            say_err(format!("  [{}] {} - failed to get size: {}", i, path, e));
             continue;
         }
 
-        let size = size_i64 as u64;
+        let size = if length_info.Length > 0 {
+            length_info.Length as u64
+        } else {
+            match zero_size_fallback(i) {
+                Some(size) => size,
+                None => {
+                    say_err(format!("  [{}] {} - reports 0 bytes, skipped", i, path));
+                    continue;
+                }
+            }
+        };
         any = true;
 
         let mark = if i == system_disk { " (SYSTEM DISK)" } else { "" };
 
-        println!("[{}] {} - {}{}", i, path, size_format(size), mark);
+        say(format!("[{}] {} - {}{}", i, path, size_format(size), mark));
     }
 
     if !any {
-        println!("No physical disks found in range 0..{}.", max_index - 1);
+        say(format!(
+            "No physical disks found in range 0..{}.",
+            max_index - 1
+        ));
     }
 
     Ok(())
 }
 
+/// A disk reporting 0 bytes: retry, then fall back to geometry, and say which worked.
+fn zero_size_fallback(disk_num: u32) -> Option<u64> {
+    let resolved = resolve_zero_size(
+        || disk_length(disk_num),
+        || geometry_size(disk_num),
+        ZERO_SIZE_RETRIES,
+        ZERO_SIZE_RETRY_DELAY,
+    )?;
+
+    match resolved {
+        (size, SizeSource::Retry(n)) => {
+            println!(
+                "[*] PhysicalDrive{} reported 0 bytes; got {} on retry {}.",
+                disk_num,
+                size_format(size),
+                n
+            );
+        }
+        (size, SizeSource::Geometry) => {
+            println!(
+                "[*] PhysicalDrive{} reported 0 bytes; using drive geometry ({}).",
+                disk_num,
+                size_format(size)
+            );
+        }
+    }
+
+    Some(resolved.0)
+}
+
+/// Every disk found is protected: say which and why, and what to try next.
+fn no_wipeable_disks_message(
+    disks: &[DiskInfo],
+    protected: &BTreeMap<u32, Vec<String>>,
+    system_disk: u32,
+) -> String {
+    let mut msg = format!(
+        "No non-system disks available to wipe. Found {} disk(s), all protected:\n",
+        disks.len()
+    );
+    for d in disks {
+        let mut reasons = protected.get(&d.index).cloned().unwrap_or_default();
+        if d.index == system_disk && reasons.is_empty() {
+            reasons.push("system disk".to_string());
+        }
+        if d.excluded {
+            reasons.push("excluded by --exclude-serial".to_string());
+        }
+        msg.push_str(&format!(
+            "  PhysicalDrive{} - {} - {}\n",
+            d.index,
+            size_format(d.size_bytes),
+            reasons.join(", ")
+        ));
+    }
+    msg.push_str(&format!(
+        "If the drive you want is connected, check it appears in Disk Management \
+         (diskmgmt.msc) and is online. If PhysicalDrive{} is not really the system \
+         disk, pass --system-disk <N> with the right index.",
+        system_disk
+    ));
+    msg
+}
+
 /// Full disk wipe flow (select disk, protect system, confirm).
-pub fn run_disk_wipe_flow(
-    mode: WipeMode,
-    passes: u32,
-    system_disk_arg: Option<u32>,
-) -> io::Result<()> {
+pub fn run_disk_wipe_flow(opts: &DiskWipeOptions) -> io::Result<()> {
     const MAX_INDEX: u32 = 16;
 
-    let system_disk = match system_disk_arg {
-        Some(n) => {
-            println!("Using user-specified system disk: PhysicalDrive{}", n);
-            n
-        }
-        None => match detect_system_disk() {
-            Some(n) => {
-                println!("Auto-detected system disk: PhysicalDrive{}", n);
-                n
-            }
-            None => {
-                println!("Could not auto-detect system disk; defaulting to PhysicalDrive0.");
-                println!("You can override with: --system-disk <N>");
-                0
-            }
-        },
-    };
+    let mode = opts.wipe.mode;
+    let passes = opts.wipe.passes;
+    let system_disk = resolve_system_disk(opts.system_disk);
+    let protected = protected_disks(system_disk);
 
     println!();
     println!("=== Disk Wipe Mode ===");
     println!("System disk       : PhysicalDrive{}", system_disk);
     println!("Wipe mode         : {:?}", mode);
     println!("Passes            : {}", passes);
+    if let Some(limit) = opts.size_limit {
+        println!("Safe mode         : disks over {} refused", size_format(limit));
+    }
     println!();
 
     // collect disks
@@ -251,7 +1035,7 @@ pub fn run_disk_wipe_flow(
         };
 
         let handle = match handle {
-            Ok(h) => h,
+            Ok(h) => OwnedHandle::new(h),
             Err(_) => continue,
         };
 
@@ -260,7 +1044,7 @@ pub fn run_disk_wipe_flow(
 
         let res = unsafe {
             DeviceIoControl(
-                handle,
+                handle.0,
                 IOCTL_DISK_GET_LENGTH_INFO,
                 None,
                 0,
@@ -271,28 +1055,36 @@ pub fn run_disk_wipe_flow(
             )
         };
 
-        unsafe {
-            if let Err(e) = CloseHandle(handle) {
-                eprintln!("Warning: CloseHandle failed for {}: {e}", path);
-            }
-        }
-
         if let Err(e) = res {
             eprintln!("  [{}] {} - failed to get size: {}", i, path, e);
             continue;
         }
 
-        let size_i64 = length_info.Length;
-        if size_i64 <= 0 {
-            continue;
-        }
-
-        let size = size_i64 as u64;
-        disks.push(DiskInfo {
-            index: i,
-            size_bytes: size,
-            is_system: i == system_disk,
-        });
+        let size = if length_info.Length > 0 {
+            length_info.Length as u64
+        } else {
+            match zero_size_fallback(i) {
+                Some(size) => size,
+                None => {
+                    eprintln!("  [{}] {} - reports 0 bytes, skipped", i, path);
+                    continue;
+                }
+            }
+        };
+        let identity = match query_identity(i) {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("  [{}] {} - could not read identity: {}", i, path, e);
+                DiskIdentity::default()
+            }
+        };
+        disks.push(DiskInfo::new(
+            i,
+            size,
+            identity,
+            &protected,
+            &opts.exclude_serials,
+        ));
     }
 
     if disks.is_empty() {
@@ -304,39 +1096,40 @@ pub fn run_disk_wipe_flow(
 
     println!("Available disks:");
     for d in &disks {
-        let mark = if d.is_system {
+        let mark = if d.index == system_disk {
             " (SYSTEM DISK - PROTECTED)"
+        } else if d.excluded {
+            " (EXCLUDED BY SERIAL - PROTECTED)"
+        } else if d.is_protected {
+            " (PROTECTED)"
         } else {
             ""
         };
         println!(
-            "  [{}] \\\\.\\PhysicalDrive{} - {}{}",
+            "  [{}] \\\\.\\PhysicalDrive{} - {} - {} S/N {}{}",
             d.index,
             d.index,
             size_format(d.size_bytes),
+            d.identity.model.as_deref().unwrap_or("unknown model"),
+            d.identity.serial.as_deref().unwrap_or("?"),
             mark
         );
     }
 
-    let non_system: Vec<&DiskInfo> = disks.iter().filter(|d| !d.is_system).collect();
+    let non_system = selectable_disks(&disks);
     if non_system.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::Other,
-            "No non-system disks available to wipe.",
+            no_wipeable_disks_message(&disks, &protected, system_disk),
         ));
     }
 
     println!();
-    println!("Enter the disk index you want to WIPE (non-system only), or just press Enter to cancel:");
-
-    use std::io::{stdin, stdout};
-
-    print!("> ");
-    stdout().flush()?;
-
-    let mut line = String::new();
-    stdin().read_line(&mut line)?;
-    let trimmed = line.trim();
+    let answer = read_answer(
+        "Enter the disk index you want to WIPE (non-system only), or just press Enter to cancel:",
+        io::stdin().lock(),
+    )?;
+    let trimmed = answer.as_str();
 
     if trimmed.is_empty() {
         println!("Aborted by user.");
@@ -357,125 +1150,2474 @@ pub fn run_disk_wipe_flow(
         }
     };
 
+    // a hot-plug while the prompt was open can renumber PhysicalDriveN
+    recheck_selected(selected, system_disk)?;
+
+    if let Some(limit) = opts.size_limit {
+        check_size_limit(selected.size_bytes, limit)?;
+    }
+
+    let sector_size = disk_sector_size(selected.index)? as u64;
+    if opts.reinit_mbr {
+        check_mbr_capacity(selected.size_bytes, sector_size, opts.force_mbr)?;
+    }
+    let mut wipe = opts.wipe.clone();
+    wipe.sector_size = sector_size as usize;
+    if opts.strict_sector_size {
+        check_strict_sector_size(
+            wipe.buffer_size as u64,
+            wipe.io_size.map(|n| n as u64),
+            opts.offset,
+            opts.length,
+            opts.units,
+            sector_size,
+        )?;
+    }
+    if let Some(io_size) = wipe.io_size {
+        check_io_size(io_size as u64, wipe.buffer_size as u64, sector_size)?;
+    }
+    let mut wipe_len = selected.size_bytes;
+    let mut partial = opts.offset.is_some() || opts.length.is_some();
+    if partial {
+        let (start, len) = resolve_range(
+            selected.size_bytes,
+            opts.offset,
+            opts.length,
+            opts.units,
+            sector_size,
+        )?;
+        check_aligned(start, len, sector_size)?;
+        wipe.offset = start;
+        wipe_len = len;
+    }
+
+    let (capped_len, capped) = limit_len(wipe_len, opts.limit_bytes, sector_size);
+    if capped {
+        wipe_len = capped_len;
+        partial = true;
+        print_limit_warning(wipe_len, &format!("PhysicalDrive{}", selected.index));
+    }
+    align_for_physical_sectors(selected.index, &mut wipe);
+
     println!();
     println!("You selected: \\\\.\\PhysicalDrive{}", selected.index);
     println!("Size:         {}", size_format(selected.size_bytes));
+    if partial {
+        println!(
+            "Range:        LBA {}..{} ({}, {}-byte sectors)",
+            wipe.offset / sector_size,
+            (wipe.offset + wipe_len) / sector_size,
+            size_format(wipe_len),
+            sector_size
+        );
+    }
     println!("Mode:         {:?}", mode);
     println!("Passes:       {}", passes);
     println!();
     println!("THIS WILL IRREVERSIBLY ERASE ALL DATA ON THIS DISK.");
     println!("It will NOT touch the system disk (PhysicalDrive{}).", system_disk);
     println!();
-    let phrase = format!("WIPE-DISK-{}", selected.index);
-    println!("Type EXACTLY: {}", phrase);
-    println!("Anything else will cancel.");
 
-    print!("> ");
-    stdout().flush()?;
+    if opts.confirm_details {
+        let details = confirm_details(
+            selected.index,
+            &selected.identity,
+            sector_size,
+            selected.size_bytes,
+        );
+        print!("{}", details);
+        println!();
+    }
+
+    let mounted = mounted_volumes_on(selected.index);
+    if !confirm_dismount_of(selected.index, &mounted)? {
+        println!("Aborted by user (volumes left mounted).");
+        return Ok(());
+    }
 
-    let mut input2 = String::new();
-    stdin().read_line(&mut input2)?;
-    if input2.trim() != phrase {
+    if !confirm_disk_phrase(
+        selected.index,
+        opts.phrase_format.as_deref(),
+        opts.confirm_checksum,
+    )? {
         println!("Aborted by user (confirmation phrase did not match).");
         return Ok(());
     }
 
     let dev = format!(r"\\.\PhysicalDrive{}", selected.index);
+
+    if opts.dry_run {
+        if !mounted.is_empty() {
+            println!("Would dismount  : {}", mounted.join(", "));
+        }
+        print_dry_run_plan(&dev, wipe_len, opts);
+        return Ok(());
+    }
+
+    let _target_lock = TargetLock::acquire(&dev)?;
+    set_title_target(&dev);
+
+    // held until the wipe is done so Windows can't remount them mid-write
+    let volume_locks = lock_and_dismount(&mounted)?;
+
     println!();
     println!("[*] Opening {} for read/write...", dev);
 
-    let disk_file = OpenOptions::new()
+    let mut disk_file = open_disk_for_wipe(&dev, opts.no_buffering)?;
+
+    println!(
+        "[*] Starting wipe: {} (mode: {:?}, passes: {})",
+        dev, mode, passes
+    );
+
+    // the wipe must cover the device exactly as the driver sees it now
+    let current_len = disk_length(selected.index)?;
+    if current_len != selected.size_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{} now reports {} bytes but {} were enumerated; aborting",
+                dev, current_len, selected.size_bytes
+            ),
+        ));
+    }
+
+    if opts.probe_addressing && partial {
+        println!("Note: --probe-addressing skipped, only part of the disk is wiped.");
+    } else if opts.probe_addressing {
+        check_interrupted()?;
+        probe_addressing(&mut disk_file, selected.size_bytes, sector_size)?;
+    }
+
+    // dropped at the end of the flow, after verify, whatever the outcome
+    let _write_cache = if opts.disable_write_cache {
+        WriteCacheGuard::disable(selected.index)?
+    } else {
+        None
+    };
+
+    let smart_before = opts.report_smart.then(|| read_smart(selected.index));
+
+    let started = unix_now();
+    // the report only says "completed" if every step below succeeded
+    let outcome = (|| -> io::Result<Option<String>> {
+        let offloaded =
+            opts.write_same && try_write_same(selected.index, wipe_len, &wipe, sector_size);
+        if !offloaded {
+            // read back in the chunks the final pass was written in
+            wipe.buffer_size = wipe_file(&mut disk_file, wipe_len, &wipe)?.buffer_size;
+        }
+
+        // explicit second write of the last sector guards against tail truncation
+        if wipe.offset + wipe_len == selected.size_bytes {
+            overwrite_tail(&mut disk_file, selected.size_bytes, sector_size, &wipe)?;
+        }
+
+        let mut post_hash = None;
+        if opts.verify {
+            post_hash = Some(verify_wipe(
+                &mut disk_file,
+                wipe_len,
+                &wipe,
+                &mut ConsoleProgress::new(),
+            )?);
+        }
+
+        if opts.pattern_verify {
+            pattern_verify(&mut disk_file, wipe_len, &wipe, &mut ConsoleProgress::new())?;
+        }
+
+        if let Some(count) = opts.verify_samples {
+            verify_samples(&mut disk_file, wipe_len, &wipe, count, sector_size)?;
+        }
+
+        if opts.trim_verify && partial {
+            println!("Note: --trim-then-verify-unreadable skipped, only part of the disk was wiped.");
+        } else if opts.trim_verify {
+            trim_then_verify(&mut disk_file, selected.index, selected.size_bytes, sector_size)?;
+        }
+
+        if opts.stamp && partial {
+            println!("Note: --stamp skipped, only part of the disk was wiped.");
+        } else if opts.stamp {
+            write_stamp(
+                &mut disk_file,
+                sector_size as usize,
+                selected.size_bytes,
+                opts.operator.as_deref(),
+            )?;
+        }
+
+        if opts.reinit_mbr && partial {
+            println!("Note: --reinit-mbr skipped, only part of the disk was wiped.");
+        } else if opts.reinit_mbr {
+            let mbr = AlignedBuf::copy_of(&empty_mbr(rand::random(), sector_size as usize))?;
+            disk_file.seek(SeekFrom::Start(0))?;
+            disk_file.write_all(&mbr)?;
+            disk_file.flush()?;
+            println!("[+] Wrote a blank MBR to sector 0.");
+        }
+
+        Ok(post_hash)
+    })();
+
+    let mut report = match &outcome {
+        Ok(post_hash) => wipe_report(
+            &dev,
+            wipe_len,
+            &wipe,
+            started,
+            post_hash.as_deref(),
+            opts.operator.as_deref(),
+        ),
+        Err(e) => failed_report(
+            &dev,
+            wipe_len,
+            &wipe,
+            started,
+            opts.operator.as_deref(),
+            &e.to_string(),
+        ),
+    };
+    if let Some(before) = smart_before {
+        match (before, read_smart(selected.index)) {
+            (Ok(before), Ok(after)) => add_smart(&mut report, &before, &after),
+            (Err(e), _) | (_, Err(e)) => report.add("SMART", format!("unavailable ({})", e)),
+        }
+    }
+    let error = outcome.as_ref().err().map(|e| e.to_string());
+    if let Some(hook) = &opts.exec_after {
+        hook.run(&dev, error.as_deref(), &mut report);
+    }
+    if let Some(report_path) = opts.report_path(&dev, started) {
+        if let Err(e) = report.write_to(&report_path) {
+            eprintln!("Could not write report: {}", e);
+        }
+    }
+    if let Some(url) = &opts.notify_url {
+        let elapsed = unix_now().saturating_sub(started);
+        let operator = opts.operator.as_deref();
+        send_notification(
+            url,
+            &Notification::new(&dev, wipe_len, elapsed, operator, error.as_deref()),
+        );
+    }
+
+    drop(volume_locks);
+    outcome?;
+
+    println!();
+    println!("[+] Disk wipe completed for {}.", dev);
+
+    Ok(())
+}
+
+/// (disk, offset, length) for every extent of a volume, in volume order.
+fn volume_wipe_ranges(extents: &[DiskExtent]) -> Vec<(u32, u64, u64)> {
+    extents
+        .iter()
+        .filter(|x| x.ExtentLength > 0)
+        .map(|x| (x.DiskNumber, x.StartingOffset as u64, x.ExtentLength as u64))
+        .collect()
+}
+
+/// Open a physical disk read/write for wiping. With --no-buffering writes skip
+/// the system cache, so every buffer and offset must be sector-aligned.
+fn open_disk_for_wipe(dev: &str, no_buffering: bool) -> io::Result<std::fs::File> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true);
+    if no_buffering {
+        options.custom_flags(FILE_FLAG_NO_BUFFERING.0);
+    }
+
+    options.open(dev).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("Failed to open {} for write: {}", dev, e),
+        )
+    })
+}
+
+/// Open a physical disk read-only, bypassing the cache, for previews and
+/// dry runs that must not hold a write handle.
+fn open_disk_for_read(dev: &str) -> io::Result<std::fs::File> {
+    OpenOptions::new()
         .read(true)
-        .write(true)
-        .open(&dev)
+        .custom_flags(FILE_FLAG_NO_BUFFERING.0)
+        .open(dev)
         .map_err(|e| {
             io::Error::new(
                 io::ErrorKind::Other,
-                format!("Failed to open {} for write: {}", dev, e),
+                format!("Failed to open {} for read: {}", dev, e),
             )
-        })?;
+        })
+}
+
+/// --wipe-volume: wipe each extent of a spanned/striped/simple volume on its
+/// own disk. Refused outright if any disk it touches is protected.
+pub fn run_volume_wipe(volume: &str, opts: &DiskWipeOptions) -> io::Result<()> {
+    let volume = format!("{}:", volume.trim().trim_end_matches(['\\', ':']));
+    let ranges = volume_wipe_ranges(&volume_extents(&volume)?);
+    if ranges.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} reports no disk extents", volume),
+        ));
+    }
+
+    let system_disk = resolve_system_disk(opts.system_disk);
+    let protected = protected_disks(system_disk);
+
+    println!();
+    println!("=== Volume Wipe: {} ===", volume);
+    for (i, (disk, start, len)) in ranges.iter().enumerate() {
+        println!(
+            "Extent {:<2}: PhysicalDrive{} bytes {}..{} ({})",
+            i + 1,
+            disk,
+            start,
+            start + len,
+            size_format(*len)
+        );
+    }
+
+    // each disk the volume spans gets the same checks as a --wipe-disk target
+    let mut disks: Vec<u32> = ranges.iter().map(|(disk, _, _)| *disk).collect();
+    disks.sort_unstable();
+    disks.dedup();
+    for disk in &disks {
+        check_disk_allowed(*disk, &protected, &opts.exclude_serials, opts.size_limit)
+            .map_err(|e| io::Error::new(e.kind(), format!("{} cannot be wiped: {}", volume, e)))?;
+    }
 
+    let total: u64 = ranges.iter().map(|(_, _, len)| len).sum();
+    let passes = effective_passes(opts.wipe.mode, opts.wipe.passes);
     println!(
-        "[*] Starting wipe: {} (mode: {:?}, passes: {})",
-        dev, mode, passes
+        "Total     : {} in {} extent(s), {:?} x {} pass(es)",
+        size_format(total),
+        ranges.len(),
+        opts.wipe.mode,
+        passes
     );
+    println!();
+    println!("!!! EVERYTHING ON {} WILL BE DESTROYED !!!", volume);
+    if !confirm_phrase(&format!("WIPE-VOLUME-{}", volume.trim_end_matches(':')))? {
+        println!("Aborted by user (confirmation phrase did not match).");
+        return Ok(());
+    }
+    for disk in &disks {
+        println!();
+        if !confirm_disk_phrase(*disk, opts.phrase_format.as_deref(), opts.confirm_checksum)? {
+            println!("Aborted by user (PhysicalDrive{} was not confirmed).", disk);
+            return Ok(());
+        }
+    }
+
+    if opts.dry_run {
+        println!();
+        println!("=== DRY RUN: nothing will be opened for write ===");
+        println!("Would dismount  : {}", volume);
+        for (disk, start, len) in &ranges {
+            println!(
+                "Would write     : PhysicalDrive{} at {} ({}) x {} pass(es)",
+                disk,
+                start,
+                size_format(*len),
+                passes
+            );
+        }
+        return Ok(());
+    }
 
-    wipe_file(disk_file, selected.size_bytes, mode, passes)?;
+    // held until every extent is done so Windows can't remount the volume
+    let volume_locks = lock_and_dismount(&[volume.clone()])?;
+    let started = unix_now();
+    let mut reports = Vec::new();
+    let mut failure = None;
+
+    for (i, (disk, start, len)) in ranges.iter().enumerate() {
+        let dev = format!(r"\\.\PhysicalDrive{}", disk);
+        println!();
+        println!("=== Extent {}/{}: {} ===", i + 1, ranges.len(), dev);
+
+        let mut wipe = opts.wipe.clone();
+        wipe.offset = *start;
+        let outcome = (|| -> io::Result<Option<String>> {
+            let _target_lock = TargetLock::acquire(&dev)?;
+            set_title_target(&dev);
+
+            let mut disk_file = open_disk_for_wipe(&dev, opts.no_buffering)?;
+            let _write_cache = if opts.disable_write_cache {
+                WriteCacheGuard::disable(*disk)?
+            } else {
+                None
+            };
+            wipe.sector_size = disk_sector_size(*disk)? as usize;
+            if opts.strict_sector_size {
+                check_strict_sector_size(
+                    wipe.buffer_size as u64,
+                    wipe.io_size.map(|n| n as u64),
+                    None,
+                    None,
+                    opts.units,
+                    wipe.sector_size as u64,
+                )?;
+            }
+            align_for_physical_sectors(*disk, &mut wipe);
+            wipe.buffer_size = wipe_file(&mut disk_file, *len, &wipe)?.buffer_size;
+
+            if !opts.verify {
+                return Ok(None);
+            }
+            let mut sink = ConsoleProgress::new();
+            Ok(Some(verify_wipe(&mut disk_file, *len, &wipe, &mut sink)?))
+        })();
+
+        let target = format!("{} extent {} ({})", volume, i + 1, dev);
+        let operator = opts.operator.as_deref();
+        match outcome {
+            Ok(post_hash) => reports.push(wipe_report(
+                &target,
+                *len,
+                &wipe,
+                started,
+                post_hash.as_deref(),
+                operator,
+            )),
+            Err(e) => {
+                let msg = e.to_string();
+                reports.push(failed_report(&target, *len, &wipe, started, operator, &msg));
+                failure = Some(e);
+                break;
+            }
+        }
+    }
+
+    drop(volume_locks);
+
+    // once for the whole volume; its outcome lands in the last extent's report
+    let error = failure.as_ref().map(|e| e.to_string());
+    if let (Some(hook), Some(report)) = (&opts.exec_after, reports.last_mut()) {
+        hook.run(&volume, error.as_deref(), report);
+    }
+    if let Some(url) = &opts.notify_url {
+        let elapsed = unix_now().saturating_sub(started);
+        let operator = opts.operator.as_deref();
+        send_notification(
+            url,
+            &Notification::new(&volume, total, elapsed, operator, error.as_deref()),
+        );
+    }
+
+    if let Some(report_path) = opts.report_path(&volume, started) {
+        if let Err(e) = write_reports(&reports, &report_path) {
+            eprintln!("Could not write report: {}", e);
+        }
+    }
+    if let Some(e) = failure {
+        return Err(e);
+    }
 
     println!();
-    println!("[+] Disk wipe completed for {}.", dev);
+    println!(
+        "[+] Volume wipe completed for {} ({} extent(s)).",
+        volume,
+        ranges.len()
+    );
 
     Ok(())
 }
 
-fn detect_system_disk() -> Option<u32> {
-    let system_drive = env::var("SYSTEMDRIVE").unwrap_or_else(|_| "C:".to_string());
-    let volume_path = format!(r"\\.\{}", system_drive);
-    println!("Attempting to auto-detect system disk via volume: {}", volume_path);
+/// --wipe-free fills the volume with files of at most this size (FAT32 caps a file at 4 GiB).
+const FREE_FILE_MAX: u64 = 2 * 1024 * 1024 * 1024;
 
-    let wide = to_pcwstr(&volume_path);
+/// Fill files are cut to a multiple of this, so unbuffered writes stay aligned.
+const FREE_FILE_ALIGN: u64 = 1024 * 1024;
 
-    let handle = unsafe {
-        CreateFileW(
-            PCWSTR(wide.as_ptr()),
-            FILE_GENERIC_READ.0,
-            FILE_SHARE_READ | FILE_SHARE_WRITE,
-            None,
-            OPEN_EXISTING,
-            FILE_ATTRIBUTE_NORMAL,
-            None,
-        )
-    };
+/// Name prefix of the fill files, so leftovers of an interrupted run can be found.
+const FREE_FILE_PREFIX: &str = "WipeCore-free-";
 
-    let handle = match handle {
-        Ok(h) => h,
-        Err(e) => {
-            eprintln!("Auto-detect: CreateFileW({}) failed: {}", volume_path, e);
-            return None;
+/// Bytes free to the caller on `root`, e.g. `C:\`.
+fn volume_free_bytes(root: &str) -> io::Result<u64> {
+    let wide = to_pcwstr(root);
+    let mut free: u64 = 0;
+    unsafe { GetDiskFreeSpaceExW(PCWSTR(wide.as_ptr()), Some(&mut free), None, None) }
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("GetDiskFreeSpaceExW: {e}")))?;
+    Ok(free)
+}
+
+/// Logical sector size of the volume at `root`; unbuffered writes must be
+/// whole multiples of it.
+fn volume_sector_size(root: &str) -> io::Result<u32> {
+    let wide = to_pcwstr(root);
+    let mut bps: u32 = 0;
+    unsafe { GetDiskFreeSpaceW(PCWSTR(wide.as_ptr()), None, Some(&mut bps), None, None) }
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("GetDiskFreeSpaceW: {e}")))?;
+    Ok(bps)
+}
+
+/// ERROR_HANDLE_DISK_FULL / ERROR_DISK_FULL: the volume has no room left.
+fn is_disk_full(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(39 | 112))
+}
+
+/// The --wipe-free fill files; deleted on drop, so an error or a panic
+/// halfway through never leaves the volume full.
+struct FreeSpaceFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl FreeSpaceFiles {
+    fn remove_all(&mut self) {
+        for path in self.paths.drain(..) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                eprintln!("Warning: could not delete '{}': {}", path.display(), e);
+            }
         }
-    };
+    }
+}
 
-    let mut info = VolumeDiskExtentsLocal {
-        NumberOfDiskExtents: 0,
-        Extents: [DiskExtent {
-            DiskNumber: 0,
-            StartingOffset: 0,
-            ExtentLength: 0,
-        }],
-    };
-    let mut br: u32 = 0;
+impl Drop for FreeSpaceFiles {
+    fn drop(&mut self) {
+        self.remove_all();
+    }
+}
 
-    let res = unsafe {
-        DeviceIoControl(
-            handle,
-            IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS,
-            None,
-            0,
-            Some(&mut info as *mut _ as *mut _),
-            std::mem::size_of::<VolumeDiskExtentsLocal>() as u32,
-            Some(&mut br),
-            None,
-        )
+/// Fill files a killed earlier run left in `root`.
+fn leftover_free_files(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        if name.is_some_and(|n| n.starts_with(FREE_FILE_PREFIX)) {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+/// --wipe-free: overwrite the free space of a volume such as C: (like
+/// `cipher /w`) by filling it with files, wiping them, then deleting them.
+/// Live files are untouched; data small enough to sit inside the MFT is not
+/// reached, and neither is the last partial MiB of free space.
+pub fn run_free_space_wipe(volume: &str, opts: &DiskWipeOptions) -> io::Result<()> {
+    let volume = format!("{}:", volume.trim().trim_end_matches(['\\', ':']));
+    let root = format!("{}\\", volume);
+    let passes = effective_passes(opts.wipe.mode, opts.wipe.passes);
+
+    // the fill files are opened unbuffered, so every write must be whole sectors
+    let sector = volume_sector_size(&root)? as u64;
+    let mut wipe = opts.wipe.clone();
+    wipe.sector_size = sector as usize;
+    if wipe.buffer_size as u64 % sector != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--buffer-size {} is not a multiple of the {}-byte sector size of {}",
+                wipe.buffer_size, sector, volume
+            ),
+        ));
+    }
+    if let Some(io_size) = wipe.io_size {
+        check_io_size(io_size as u64, wipe.buffer_size as u64, sector)?;
+    }
+
+    let mut files = FreeSpaceFiles {
+        paths: leftover_free_files(Path::new(&root))?,
     };
+    let leftovers = files.paths.len();
 
-    unsafe {
-        if let Err(e) = CloseHandle(handle) {
-            eprintln!("Auto-detect: CloseHandle({}) failed: {}", volume_path, e);
+    let free = volume_free_bytes(&root)?;
+    println!();
+    println!("=== Free Space Wipe: {} ===", volume);
+    println!("Free      : {}", size_format(free));
+    println!("Mode      : {:?} x {} pass(es)", opts.wipe.mode, passes);
+
+    if opts.dry_run {
+        println!();
+        println!("=== DRY RUN: nothing will be written ===");
+        println!(
+            "Would fill  : {} with files of up to {}",
+            root,
+            size_format(FREE_FILE_MAX)
+        );
+        if leftovers > 0 {
+            println!(
+                "Would delete: {} fill file(s) left by an interrupted run",
+                leftovers
+            );
         }
+        // keep the leftovers: a dry run deletes nothing either
+        files.paths.clear();
+        return Ok(());
     }
 
-    if let Err(e) = res {
-        eprintln!(
-            "Auto-detect: DeviceIoControl(IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS) failed: {}",
-            e
+    if leftovers > 0 {
+        println!(
+            "Note: deleting {} fill file(s) left by an interrupted run.",
+            leftovers
         );
-        return None;
+        files.remove_all();
     }
 
-    if info.NumberOfDiskExtents == 0 {
-        eprintln!("Auto-detect: volume reports 0 disk extents.");
-        return None;
+    let mut wiped: u64 = 0;
+    loop {
+        check_interrupted()?;
+        let free = volume_free_bytes(&root)?;
+        let size = free.min(FREE_FILE_MAX) / FREE_FILE_ALIGN * FREE_FILE_ALIGN;
+        if size == 0 {
+            break;
+        }
+
+        let path = PathBuf::from(format!(
+            "{}{}{}-{}.tmp",
+            root,
+            FREE_FILE_PREFIX,
+            std::process::id(),
+            files.paths.len() + 1
+        ));
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .custom_flags(FILE_FLAG_NO_BUFFERING.0)
+            .open(&path)?;
+        files.paths.push(path.clone());
+
+        println!();
+        println!("[*] Filling '{}' ({})", path.display(), size_format(size));
+        let result = f.set_len(size).and_then(|_| wipe_file(&mut f, size, &wipe));
+        // running out of room is how the fill is meant to end
+        match result {
+            Ok(_) => wiped += size,
+            Err(e) if is_disk_full(&e) => {
+                println!("Note: {} is full, stopping the fill.", volume);
+                break;
+            }
+            Err(e) => return Err(e),
+        }
     }
 
-    Some(info.Extents[0].DiskNumber)
+    let count = files.paths.len();
+    files.remove_all();
+
+    println!();
+    println!(
+        "[+] Free space wipe completed for {}: {} overwritten in {} file(s), all deleted.",
+        volume,
+        size_format(wiped),
+        count
+    );
+
+    Ok(())
+}
+
+/// Read `len` bytes at `offset` from a raw disk, going through whole sectors.
+fn read_sectors(
+    disk: &mut std::fs::File,
+    offset: u64,
+    len: u64,
+    sector: u64,
+) -> io::Result<Vec<u8>> {
+    let start = offset / sector * sector;
+    let end = (offset + len).div_ceil(sector) * sector;
+    // aligned, so this also works on a handle opened with FILE_FLAG_NO_BUFFERING
+    let mut buf = AlignedBuf::new((end - start) as usize, IO_ALIGN)?;
+    disk.seek(SeekFrom::Start(start))?;
+    disk.read_exact(&mut buf)?;
+    let skip = (offset - start) as usize;
+    Ok(buf[skip..skip + len as usize].to_vec())
+}
+
+/// Filesystem kind and metadata regions of the volume at `start`, as on disk now.
+fn read_fs_metadata(
+    disk: &mut std::fs::File,
+    start: u64,
+    volume_len: u64,
+    sector: u64,
+) -> io::Result<(FsKind, Vec<MetaRegion>)> {
+    let boot = read_sectors(disk, start, BOOT_SECTOR_LEN as u64, sector)?;
+    let layout = parse_boot_sector(&boot, volume_len)?;
+    let mut regions = layout.regions.clone();
+    if let Some((offset, len)) = layout.follow_up {
+        let data = read_sectors(disk, start + offset, len, sector)?;
+        regions.extend(follow_up_regions(&layout, &data)?);
+    }
+    Ok((layout.kind, regions))
+}
+
+/// --wipe-fs-metadata: overwrite the boot sectors, $MFT/$MFTMirr (NTFS), FATs
+/// and root directory (FAT), or FAT and allocation bitmap (exFAT) of a simple
+/// volume. File contents are left on disk.
+pub fn run_fs_metadata_wipe(
+    volume: &str,
+    system_disk_arg: Option<u32>,
+    exclude_serials: &[String],
+    dry_run: bool,
+) -> io::Result<()> {
+    let volume = format!("{}:", volume.trim().trim_end_matches(['\\', ':']));
+    let ranges = volume_wipe_ranges(&volume_extents(&volume)?);
+    let (disk_num, start, volume_len) = match ranges.as_slice() {
+        [one] => *one,
+        [] => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} reports no disk extents", volume),
+            ))
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "{} spans {} extents; only simple volumes are supported",
+                    volume,
+                    ranges.len()
+                ),
+            ))
+        }
+    };
+
+    let dev = format!(r"\\.\PhysicalDrive{}", disk_num);
+    // first, so no other WipeCore run can be writing this disk from here on
+    let _target_lock = TargetLock::acquire(&dev)?;
+
+    let system_disk = resolve_system_disk(system_disk_arg);
+    let protected = protected_disks(system_disk);
+    check_disk_allowed(disk_num, &protected, exclude_serials, None)
+        .map_err(|e| io::Error::new(e.kind(), format!("{} cannot be wiped: {}", volume, e)))?;
+
+    let sector = disk_sector_size(disk_num)? as u64;
+
+    // only a preview for the confirmation; it is read again once dismounted
+    let (kind, regions) = {
+        let mut preview = open_disk_for_read(&dev)?;
+        read_fs_metadata(&mut preview, start, volume_len, sector)?
+    };
+
+    println!();
+    println!("=== Filesystem Metadata Wipe: {} ===", volume);
+    println!("Device      : {} at offset {}", dev, start);
+    println!("Filesystem  : {:?}", kind);
+    let mut total = 0;
+    for r in &regions {
+        let (offset, len) = align_region(r, sector, volume_len);
+        total += len;
+        println!(
+            "{:<12}: bytes {}..{} ({})",
+            r.what,
+            offset,
+            offset + len,
+            size_format(len)
+        );
+    }
+    println!("Total       : {}", size_format(total));
+    println!();
+    println!("Warning: this is NOT a full data erase. The volume becomes unmountable,");
+    println!("         but file contents stay on disk and can be carved back out.");
+    if !confirm_phrase(&format!("WIPE-FSMETA-{}", volume.trim_end_matches(':')))? {
+        println!("Aborted by user (confirmation phrase did not match).");
+        return Ok(());
+    }
+    if !confirm_interlock(disk_num)? {
+        println!("Aborted by user (serial number not confirmed).");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!();
+        println!("=== DRY RUN: nothing will be written ===");
+        return Ok(());
+    }
+
+    let volume_locks = lock_and_dismount(&[volume.clone()])?;
+    let mut disk_file = open_disk_for_wipe(&dev, true)?;
+
+    // the filesystem could still change until it was dismounted
+    if read_fs_metadata(&mut disk_file, start, volume_len, sector)? != (kind, regions.clone()) {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} metadata changed; nothing was written", volume),
+        ));
+    }
+
+    let zeros = AlignedBuf::new(1024 * 1024, IO_ALIGN)?;
+    for r in &regions {
+        let (offset, len) = align_region(r, sector, volume_len);
+        disk_file.seek(SeekFrom::Start(start + offset))?;
+        let mut left = len;
+        while left > 0 {
+            check_interrupted()?;
+            let n = left.min(zeros.len() as u64) as usize;
+            disk_file.write_all(&zeros[..n])?;
+            left -= n as u64;
+        }
+    }
+    disk_file.flush()?;
+    drop(volume_locks);
+
+    println!(
+        "[+] Overwrote {} of {:?} metadata on {} ({} region(s)).",
+        size_format(total),
+        kind,
+        volume,
+        regions.len()
+    );
+
+    Ok(())
+}
+
+/// Clear the sectors between the MBR and the first partition on one disk.
+pub fn run_mbr_gap_wipe(
+    disk_num: u32,
+    system_disk_arg: Option<u32>,
+    exclude_serials: &[String],
+    dry_run: bool,
+) -> io::Result<()> {
+    let dev = format!(r"\\.\PhysicalDrive{}", disk_num);
+    // first, so no other WipeCore run can be writing this disk from here on
+    let _target_lock = TargetLock::acquire(&dev)?;
+
+    let system_disk = resolve_system_disk(system_disk_arg);
+    let protected = protected_disks(system_disk);
+    check_disk_allowed(disk_num, &protected, exclude_serials, None)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}; refusing to touch it.", e)))?;
+
+    let sector_size = disk_sector_size(disk_num)? as u64;
+    let open = |write: bool| {
+        if write {
+            open_disk_for_wipe(&dev, false)
+        } else {
+            open_disk_for_read(&dev)
+        }
+    };
+    let confirm = || -> io::Result<bool> {
+        if !confirm_phrase(&format!("WIPE-GAP-{}", disk_num))? {
+            println!("Aborted by user (confirmation phrase did not match).");
+            return Ok(false);
+        }
+        if !confirm_interlock(disk_num)? {
+            println!("Aborted by user (serial number not confirmed).");
+            return Ok(false);
+        }
+        Ok(true)
+    };
+
+    wipe_mbr_gap(&dev, sector_size, dry_run, open, confirm)
+}
+
+/// The gap wipe itself, with the disk opened through `open(write)`: only a
+/// read handle exists until the dry-run return.
+fn wipe_mbr_gap<D: Read + Write + Seek>(
+    dev: &str,
+    sector_size: u64,
+    dry_run: bool,
+    mut open: impl FnMut(bool) -> io::Result<D>,
+    confirm: impl FnOnce() -> io::Result<bool>,
+) -> io::Result<()> {
+    let mut sector0 = AlignedBuf::new((sector_size as usize).max(MBR_SIZE), IO_ALIGN)?;
+    open(false)?.read_exact(&mut sector0)?;
+
+    let parts = parse_mbr(&sector0)?;
+    let (first_lba, count) = match mbr_gap_range(&parts)? {
+        Some(r) => r,
+        None => {
+            println!("No gap between MBR and first partition on {}; nothing to wipe.", dev);
+            return Ok(());
+        }
+    };
+
+    let offset = first_lba * sector_size;
+    let len = count * sector_size;
+
+    println!();
+    println!("=== MBR Gap Wipe ===");
+    println!("Device            : {}", dev);
+    println!("Sector size       : {} bytes", sector_size);
+    println!("Gap sectors       : {}..={}", first_lba, first_lba + count - 1);
+    println!("Gap size          : {}", size_format(len));
+    println!();
+    println!("This overwrites only the gap; the MBR and partitions are left intact.");
+    if !confirm()? {
+        return Ok(());
+    }
+
+    if dry_run {
+        println!();
+        println!("=== DRY RUN: nothing will be written ===");
+        println!(
+            "Would zero      : {} at offset {} ({})",
+            dev,
+            offset,
+            size_format(len)
+        );
+        return Ok(());
+    }
+
+    let mut disk_file = open(true)?;
+
+    // the partition table could have changed while the prompt was open
+    let mut current = AlignedBuf::new(sector0.len(), IO_ALIGN)?;
+    disk_file.read_exact(&mut current)?;
+    if current[..] != sector0[..] {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} sector 0 changed; nothing was written", dev),
+        ));
+    }
+
+    disk_file.seek(SeekFrom::Start(offset))?;
+    let zeros = vec![0u8; len as usize];
+    disk_file.write_all(&zeros)?;
+    disk_file.flush()?;
+
+    println!("[+] Cleared {} sectors of MBR gap on {}.", count, dev);
+
+    Ok(())
+}
+
+/// Per-disk phrase required before any physical disk is wiped:
+/// WIPE-DISK-N, or the --confirm-phrase-format expanded for this disk.
+pub fn confirm_disk_phrase(
+    index: u32,
+    phrase_format: Option<&str>,
+    checksum: bool,
+) -> io::Result<bool> {
+    let phrase = match phrase_format {
+        Some(fmt) => {
+            let id = query_identity(index).unwrap_or_default();
+            let size = disk_length(index)?;
+            expand_phrase(fmt, index, &id, size).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("--confirm-phrase-format for PhysicalDrive{}: {}", index, e),
+                )
+            })?
+        }
+        None => format!("WIPE-DISK-{}", index),
+    };
+
+    // new every run, so a phrase pasted from habit is not enough on its own
+    let code = checksum.then(confirm_code);
+    if let Some(code) = &code {
+        println!("Confirmation code : {}", code);
+    }
+    let typed = read_answer(&phrase_prompt(&phrase), io::stdin().lock())?;
+
+    // only the default phrase carries a disk number worth pointing out
+    let other = match phrase_format {
+        None => phrase_for_other_disk(&typed, index),
+        Some(_) => None,
+    };
+    if let Some(other) = other {
+        println!(
+            "[!] You typed the phrase for disk {} but selected disk {}.",
+            other, index
+        );
+        return Ok(false);
+    }
+    if typed != phrase {
+        return Ok(false);
+    }
+
+    if let Some(code) = &code {
+        let typed = read_answer(
+            &format!("Now type the confirmation code shown for PhysicalDrive{}:", index),
+            io::stdin().lock(),
+        )?;
+        if !code_matches(&typed, code) {
+            println!("[!] Confirmation code did not match.");
+            return Ok(false);
+        }
+    }
+
+    confirm_interlock(index)
+}
+
+/// The NVMe serial interlock on its own, for flows with their own phrase.
+fn confirm_interlock(index: u32) -> io::Result<bool> {
+    let id = query_identity(index).unwrap_or_default();
+    if needs_serial_interlock(&id) {
+        return confirm_serial(index, &id);
+    }
+
+    Ok(true)
+}
+
+/// Interlock for internal NVMe disks: the full serial number, typed exactly.
+fn confirm_serial(index: u32, id: &DiskIdentity) -> io::Result<bool> {
+    let serial = match id.serial.as_deref() {
+        Some(s) if !s.trim().is_empty() => s,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "PhysicalDrive{} is an internal NVMe disk that reports no serial to confirm",
+                    index
+                ),
+            ))
+        }
+    };
+
+    println!();
+    println!(
+        "[!] PhysicalDrive{} is an internal NVMe disk ({}).",
+        index,
+        id.model.as_deref().unwrap_or("unknown model")
+    );
+    let typed = read_answer(
+        "Type its full serial number to continue:",
+        io::stdin().lock(),
+    )?;
+    if !serial_matches(&typed, serial) {
+        println!("[!] Serial number did not match.");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Disk number from a well-formed "WIPE-DISK-N" that names a different disk.
+fn phrase_for_other_disk(typed: &str, index: u32) -> Option<u32> {
+    typed
+        .trim()
+        .strip_prefix("WIPE-DISK-")
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|&n| n != index)
+}
+
+fn confirm_phrase(phrase: &str) -> io::Result<bool> {
+    confirm(&phrase_prompt(phrase), phrase, io::stdin().lock())
+}
+
+/// Parse `\\.\PhysicalDriveN` (any case) into N.
+pub fn physical_drive_index(path: &str) -> Option<u32> {
+    let lower = path.trim().to_ascii_lowercase();
+    lower
+        .strip_prefix(r"\\.\physicaldrive")
+        .and_then(|n| n.parse().ok())
+}
+
+/// Disks that may never be wiped, resolved once for a batch of targets.
+pub fn protected_indexes(system_disk_arg: Option<u32>) -> Vec<u32> {
+    let system_disk = resolve_system_disk(system_disk_arg);
+    protected_disks(system_disk).keys().copied().collect()
+}
+
+/// Size of a physical disk target, or an error if it is protected, excluded
+/// or over the --safe-mode limit.
+pub fn disk_target_size(
+    index: u32,
+    protected: &[u32],
+    exclude_serials: &[String],
+    size_limit: Option<u64>,
+) -> io::Result<u64> {
+    if protected.contains(&index) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("PhysicalDrive{} is protected (see --show-protected)", index),
+        ));
+    }
+
+    let identity = query_identity(index).unwrap_or_default();
+    if is_excluded(identity.serial.as_deref(), exclude_serials) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("PhysicalDrive{} is excluded by --exclude-serial", index),
+        ));
+    }
+
+    let size = disk_length(index)?;
+    if let Some(limit) = size_limit {
+        check_size_limit(size, limit)?;
+    }
+    Ok(size)
+}
+
+/// Re-read the chosen disk after the index prompt and abort if that index no
+/// longer holds the device that was listed, or is now protected.
+fn recheck_selected(listed: &DiskInfo, system_disk: u32) -> io::Result<()> {
+    let now = query_identity(listed.index).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "PhysicalDrive{} could not be re-read after selection ({}); was it removed?",
+                listed.index, e
+            ),
+        )
+    })?;
+
+    if let Some(change) = identity_mismatch(&listed.identity, &now) {
+        println!();
+        println!(
+            "[!] PhysicalDrive{} is not the disk that was listed: {}.",
+            listed.index, change
+        );
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "disks changed between listing and selection; run again and re-select",
+        ));
+    }
+
+    if protected_disks(system_disk).contains_key(&listed.index) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "PhysicalDrive{} became protected after it was listed; refusing it",
+                listed.index
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// What the disk wipe would do, printed instead of doing it (--dry-run).
+fn print_dry_run_plan(dev: &str, size: u64, opts: &DiskWipeOptions) {
+    let mode = opts.wipe.mode;
+    let passes = effective_passes(mode, opts.wipe.passes);
+
+    println!();
+    println!("=== DRY RUN: nothing will be opened for write ===");
+    println!("Would open      : {} (read/write)", dev);
+    println!("Would write     : {} x {} pass(es)", size_format(size), passes);
+    for pass in 1..=passes {
+        println!("  pass {}/{}     : {}", pass, passes, describe_pass(mode, pass));
+    }
+    if opts.verify {
+        println!("Would verify    : final pass read-back");
+    }
+    if opts.stamp {
+        println!("Would stamp     : sector 0 banner");
+    }
+    if opts.reinit_mbr {
+        println!("Would reinit    : blank MBR in sector 0");
+    }
+    if opts.disable_write_cache {
+        println!("Would disable   : the write cache, restored afterwards");
+    }
+    println!("[+] Dry run finished; the disk was not touched.");
+}
+
+/// --reinit-mbr on a disk MBR can't fully address strands the rest; GPT is the fix.
+fn check_mbr_capacity(size: u64, sector_size: u64, force: bool) -> io::Result<()> {
+    let beyond = match mbr_unaddressable(size, sector_size) {
+        Some(b) => b,
+        None => return Ok(()),
+    };
+
+    println!();
+    println!(
+        "Warning: MBR can only address the first {} of this {} disk; {} would be unusable.",
+        size_format(size - beyond),
+        size_format(size),
+        size_format(beyond)
+    );
+    println!("         Initialize it as GPT instead (e.g. in Disk Management).");
+    if force {
+        println!("Note: --force-mbr given, writing the MBR anyway.");
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Refusing --reinit-mbr on a disk larger than MBR can address (use --force-mbr to override).",
+    ))
+}
+
+/// Checks `run_disk_wipe_flow` applies before it offers a disk, for flows
+/// that are handed one: refused if it is protected, excluded by
+/// --exclude-serial, or over the safe mode limit.
+fn check_disk_allowed(
+    disk: u32,
+    protected: &BTreeMap<u32, Vec<String>>,
+    exclude_serials: &[String],
+    size_limit: Option<u64>,
+) -> io::Result<()> {
+    if let Some(reasons) = protected.get(&disk) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("PhysicalDrive{} is protected ({})", disk, reasons.join(", ")),
+        ));
+    }
+
+    let id = query_identity(disk).unwrap_or_default();
+    if is_excluded(id.serial.as_deref(), exclude_serials) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "PhysicalDrive{} (S/N {}) is excluded by --exclude-serial",
+                disk,
+                id.serial.as_deref().unwrap_or("?")
+            ),
+        ));
+    }
+
+    if let Some(limit) = size_limit {
+        check_size_limit(disk_length(disk)?, limit)?;
+    }
+    Ok(())
+}
+
+/// Safe mode: very large disks are more likely to be important arrays.
+fn check_size_limit(size: u64, limit: u64) -> io::Result<()> {
+    if size <= limit {
+        return Ok(());
+    }
+
+    println!();
+    println!("Safe mode is on and this disk is larger than the limit.");
+    println!("Disk size         : {}", size_format(size));
+    println!("Safe mode limit   : {}", size_format(limit));
+    println!("Re-run with --allow-large to wipe it anyway, or raise --safe-limit.");
+
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "Disk exceeds the safe mode size limit.",
+    ))
+}
+
+/// Print the protected disks and why, without touching anything.
+pub fn show_protected(system_disk_arg: Option<u32>) -> io::Result<()> {
+    let system_disk = resolve_system_disk(system_disk_arg);
+    let protected = protected_disks(system_disk);
+
+    println!();
+    println!("Protected disks (never offered for wiping):");
+    for (disk, reasons) in &protected {
+        println!("  PhysicalDrive{}", disk);
+        for r in reasons {
+            println!("    - {}", r);
+        }
+    }
+
+    Ok(())
+}
+
+/// Report pagefile/hiberfil locations and how to get rid of them.
+/// They are locked while Windows runs, so they are never opened here.
+pub fn show_pagefiles() -> io::Result<()> {
+    let system_drive = env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+
+    let mut paths = match read_paging_files() {
+        Ok(entries) => paging_file_paths(&entries, &system_drive),
+        Err(e) => {
+            eprintln!("Warning: could not read PagingFiles from the registry: {}", e);
+            vec![PathBuf::from(format!(r"{}\pagefile.sys", system_drive))]
+        }
+    };
+    paths.push(hiberfil_path(&system_drive));
+
+    println!();
+    println!("Paging / hibernation files:");
+    let mut found = 0;
+    for path in &paths {
+        match std::fs::metadata(path) {
+            Ok(m) => {
+                found += 1;
+                println!("  {} - {}", path.display(), size_format(m.len()));
+            }
+            Err(_) => println!("  {} - not present", path.display()),
+        }
+    }
+
+    if found == 0 {
+        println!("[+] Nothing to clear.");
+        return Ok(());
+    }
+
+    println!();
+    println!("These files are locked while Windows is running and cannot be wiped directly.");
+    println!("To clear them:");
+    println!("  - hiberfil.sys : run 'powercfg /h off' as administrator (deletes it)");
+    println!("  - pagefile.sys : set ClearPageFileAtShutdown=1 under");
+    println!("                   HKLM\\{}", MEMORY_MANAGEMENT_KEY);
+    println!("                   or disable the pagefile in System Properties and reboot");
+    println!("Then wipe free space on that volume to cover the old clusters.");
+
+    Ok(())
+}
+
+/// PagingFiles (REG_MULTI_SZ) entries, e.g. `C:\pagefile.sys 0 0`.
+fn read_paging_files() -> io::Result<Vec<String>> {
+    let key = to_pcwstr(MEMORY_MANAGEMENT_KEY);
+    let value = to_pcwstr("PagingFiles");
+    let mut buf = vec![0u16; 2048];
+    let mut bytes = (buf.len() * 2) as u32;
+
+    unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_MULTI_SZ,
+            None,
+            Some(buf.as_mut_ptr() as *mut _),
+            Some(&mut bytes),
+        )
+    }
+    .ok()
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("RegGetValueW failed: {e}")))?;
+
+    buf.truncate(bytes as usize / 2);
+    Ok(buf
+        .split(|&c| c == 0)
+        .filter(|s| !s.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect())
+}
+
+/// System disk plus every disk backing the boot or pagefile volumes.
+fn protected_disks(system_disk: u32) -> BTreeMap<u32, Vec<String>> {
+    let mut mappings: Vec<(String, Vec<u32>)> = Vec::new();
+
+    for (volume, reason) in protected_volumes() {
+        match volume_extents(&volume) {
+            Ok(extents) => {
+                let disks = extents.iter().map(|x| x.DiskNumber).collect();
+                mappings.push((reason, disks));
+            }
+            Err(e) => eprintln!("Warning: could not map {} to disks: {}", volume, e),
+        }
+    }
+
+    merge_protected(system_disk, &mappings)
+}
+
+/// Volumes on a disk target, locked and dismounted until this is dropped.
+pub struct DismountedVolumes {
+    _locks: Vec<OwnedHandle>,
+}
+
+/// Name the mounted volumes on PhysicalDrive`index` and require DISMOUNT,
+/// as --wipe-disk does, for a disk given as a target or in a job. True if
+/// there are none or the user agreed.
+pub fn confirm_dismount(index: u32) -> io::Result<bool> {
+    confirm_dismount_of(index, &mounted_volumes_on(index))
+}
+
+fn confirm_dismount_of(index: u32, mounted: &[String]) -> io::Result<bool> {
+    if mounted.is_empty() {
+        return Ok(true);
+    }
+    println!(
+        "[!] PhysicalDrive{} backs mounted volume(s): {}",
+        index,
+        mounted.join(", ")
+    );
+    println!("They will be locked and dismounted first; unsaved work on them is lost.");
+    let agreed = confirm_phrase("DISMOUNT")?;
+    println!();
+    Ok(agreed)
+}
+
+/// Lock and dismount every mounted volume on PhysicalDrive`index` before a
+/// disk target is opened for write.
+pub fn dismount_disk_volumes(index: u32) -> io::Result<DismountedVolumes> {
+    let locks = lock_and_dismount(&mounted_volumes_on(index))?;
+    Ok(DismountedVolumes { _locks: locks })
+}
+
+/// Mounted drive letters with at least one extent on `disk_num`.
+fn mounted_volumes_on(disk_num: u32) -> Vec<String> {
+    let mut mappings: Vec<(String, Vec<u32>)> = Vec::new();
+
+    for letter in b'C'..=b'Z' {
+        let volume = format!("{}:", letter as char);
+        if !Path::new(&format!(r"{}\", volume)).exists() {
+            continue;
+        }
+        if let Ok(extents) = volume_extents(&volume) {
+            mappings.push((volume, extents.iter().map(|x| x.DiskNumber).collect()));
+        }
+    }
+
+    volumes_on_disk(&mappings, disk_num)
+}
+
+/// Volumes from (volume, disks) mappings that live on `disk_num`.
+fn volumes_on_disk(mappings: &[(String, Vec<u32>)], disk_num: u32) -> Vec<String> {
+    mappings
+        .iter()
+        .filter(|(_, disks)| disks.contains(&disk_num))
+        .map(|(volume, _)| volume.clone())
+        .collect()
+}
+
+/// FSCTL_LOCK_VOLUME + FSCTL_DISMOUNT_VOLUME each volume; the returned
+/// handles keep the locks until they are dropped once the wipe is over.
+fn lock_and_dismount(volumes: &[String]) -> io::Result<Vec<OwnedHandle>> {
+    // a Ctrl+C after the last confirmation must not take the volumes offline
+    check_interrupted()?;
+    let mut locked = Vec::new();
+
+    for volume in volumes {
+        let path = format!(r"\\.\{}", volume);
+        let wide = to_pcwstr(&path);
+
+        let handle = match unsafe {
+            CreateFileW(
+                PCWSTR(wide.as_ptr()),
+                FILE_GENERIC_READ.0 | FILE_GENERIC_WRITE.0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                None,
+                OPEN_EXISTING,
+                FILE_ATTRIBUTE_NORMAL,
+                None,
+            )
+        } {
+            Ok(h) => OwnedHandle::new(h),
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("CreateFileW({}) failed: {e}", path),
+                ));
+            }
+        };
+        for (code, what) in [
+            (FSCTL_LOCK_VOLUME, "lock"),
+            (FSCTL_DISMOUNT_VOLUME, "dismount"),
+        ] {
+            let mut br: u32 = 0;
+            let res =
+                unsafe { DeviceIoControl(handle.0, code, None, 0, None, 0, Some(&mut br), None) };
+            if let Err(e) = res {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "could not {} {} (files still open on it?): {e}",
+                        what, volume
+                    ),
+                ));
+            }
+        }
+
+        println!("[*] Dismounted {}", volume);
+        locked.push(handle);
+    }
+
+    Ok(locked)
+}
+
+/// Combine the system disk and (reason, disks) mappings into one set.
+fn merge_protected(
+    system_disk: u32,
+    mappings: &[(String, Vec<u32>)],
+) -> BTreeMap<u32, Vec<String>> {
+    let mut out: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    out.entry(system_disk)
+        .or_default()
+        .push("system disk".to_string());
+
+    for (reason, disks) in mappings {
+        for d in disks {
+            let reasons = out.entry(*d).or_default();
+            if !reasons.contains(reason) {
+                reasons.push(reason.clone());
+            }
+        }
+    }
+
+    out
+}
+
+/// Volumes that hold Windows or its page/hibernation/swap files.
+fn protected_volumes() -> Vec<(String, String)> {
+    let mut out = Vec::new();
+
+    let windir = env::var("WINDIR").unwrap_or_else(|_| r"C:\Windows".to_string());
+    if let Some(volume) = windir.get(..2).filter(|v| v.ends_with(':')) {
+        out.push((volume.to_string(), format!("boot volume {} ({})", volume, windir)));
+    }
+
+    for letter in b'C'..=b'Z' {
+        let volume = format!("{}:", letter as char);
+        for name in ["pagefile.sys", "hiberfil.sys", "swapfile.sys"] {
+            if Path::new(&format!(r"{}\{}", volume, name)).exists() {
+                out.push((volume.clone(), format!("{} on {}", name, volume)));
+            }
+        }
+    }
+
+    out
+}
+
+fn resolve_system_disk(system_disk_arg: Option<u32>) -> u32 {
+    match system_disk_arg {
+        Some(n) => {
+            say(format!(
+                "Using user-specified system disk: PhysicalDrive{}",
+                n
+            ));
+            n
+        }
+        None => match detect_system_disk() {
+            Some(n) => {
+                say(format!("Auto-detected system disk: PhysicalDrive{}", n));
+                n
+            }
+            None => {
+                say("Could not auto-detect system disk; defaulting to PhysicalDrive0.");
+                say("You can override with: --system-disk <N>");
+                0
+            }
+        },
+    }
+}
+
+/// TRIM the whole disk, then (on DRAT + RZAT drives) check sampled sectors read as zeros.
+fn trim_then_verify(
+    disk_file: &mut std::fs::File,
+    disk_num: u32,
+    size: u64,
+    sector_size: u64,
+) -> io::Result<()> {
+    println!();
+    println!("=== TRIM + unreadable check ===");
+
+    let support = match ata_identify(disk_num).and_then(|id| decode_trim_support(&id)) {
+        Ok(s) => s,
+        Err(e) => {
+            println!("IDENTIFY DEVICE failed ({}); TRIM behaviour unknown.", e);
+            TrimSupport::default()
+        }
+    };
+    println!(
+        "TRIM: {}  DRAT: {}  RZAT: {}",
+        support.trim, support.drat, support.rzat
+    );
+
+    trim_disk(disk_num)?;
+    println!("[+] TRIM issued for the whole disk.");
+
+    if !support.verifiable() {
+        println!("Sanitization NOT verifiable: drive does not promise zeros after TRIM (DRAT + RZAT).");
+        return Ok(());
+    }
+
+    let offsets = sample_offsets(size, TRIM_VERIFY_SAMPLES, sector_size);
+    let dirty = nonzero_samples(disk_file, &offsets, sector_size as usize)?;
+
+    if dirty.is_empty() {
+        println!(
+            "[+] Sanitization verifiable: all {} sampled sectors read back as zeros.",
+            offsets.len()
+        );
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} of {} sampled sectors are not zero after TRIM (first at offset {})",
+            dirty.len(),
+            offsets.len(),
+            dirty[0]
+        ),
+    ))
+}
+
+/// --write-same: run every pass as WRITE SAME commands. Returns false when
+/// the disk can't do it, so the caller streams the zeros instead.
+fn try_write_same(disk_num: u32, len: u64, opts: &WipeOptions, sector_size: u64) -> bool {
+    let bus_type = query_identity(disk_num).map(|id| id.bus_type).unwrap_or(0);
+    if let Some(why) = write_same_blocker(opts.mode, bus_type, sector_size) {
+        println!(
+            "Note: --write-same not used, {}; streaming zeros instead.",
+            why
+        );
+        return false;
+    }
+
+    let passes = effective_passes(opts.mode, opts.passes);
+    for pass in 1..=passes {
+        println!();
+        println!("=== Starting pass {}/{} (WRITE SAME) ===", pass, passes);
+        if let Err(e) = write_same_zero(disk_num, opts.offset, len, sector_size, (pass, passes)) {
+            println!();
+            println!("[!] WRITE SAME failed ({}); streaming zeros instead.", e);
+            return false;
+        }
+        println!();
+        println!("=== Finished pass {}/{} ===", pass, passes);
+    }
+
+    true
+}
+
+/// Zero `len` bytes from `offset`: the device repeats one zero block over each range.
+fn write_same_zero(
+    disk_num: u32,
+    offset: u64,
+    len: u64,
+    sector_size: u64,
+    (pass, passes): (u32, u32),
+) -> io::Result<()> {
+    let handle = open_disk_handle(disk_num, true)?;
+
+    let mut req = Box::new(WriteSameBuffer {
+        spt: ScsiPassThroughLocal {
+            Length: std::mem::size_of::<ScsiPassThroughLocal>() as u16,
+            CdbLength: 16,
+            SenseInfoLength: SCSI_SENSE_LEN as u8,
+            DataIn: SCSI_IOCTL_DATA_OUT,
+            DataTransferLength: sector_size as u32,
+            TimeOutValue: 60,
+            DataBufferOffset: std::mem::offset_of!(WriteSameBuffer, data),
+            SenseInfoOffset: std::mem::offset_of!(WriteSameBuffer, sense) as u32,
+            ..Default::default()
+        },
+        sense: [0u8; SCSI_SENSE_LEN],
+        data: [0u8; WRITE_SAME_MAX_SECTOR],
+    });
+    let size = std::mem::size_of::<WriteSameBuffer>() as u32;
+
+    let blocks = len / sector_size;
+    let mut tracker = ProgressTracker::new();
+    let mut sink = ConsoleProgress::new();
+    let mut done: u64 = 0;
+
+    for (lba, n) in write_same_ranges(offset / sector_size, blocks) {
+        req.spt.Cdb = write_same16_cdb(lba, n);
+        let mut br: u32 = 0;
+
+        unsafe {
+            DeviceIoControl(
+                handle.0,
+                IOCTL_SCSI_PASS_THROUGH,
+                Some(&*req as *const _ as *const _),
+                size,
+                Some(&mut *req as *mut _ as *mut _),
+                size,
+                Some(&mut br),
+                None,
+            )
+        }
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("IOCTL_SCSI_PASS_THROUGH failed: {e}"),
+            )
+        })?;
+
+        if req.spt.ScsiStatus != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "device returned SCSI status 0x{:02X} (sense key 0x{:X}) at LBA {}",
+                    req.spt.ScsiStatus,
+                    req.sense[2] & 0x0F,
+                    lba
+                ),
+            ));
+        }
+
+        done += n as u64;
+        let total = blocks * sector_size;
+        tracker.update(&mut sink, "Pass", pass, passes, done * sector_size, total);
+    }
+
+    Ok(())
+}
+
+/// Open \\.\PhysicalDriveN with read (and optionally write) access.
+fn open_disk_handle(disk_num: u32, write: bool) -> io::Result<OwnedHandle> {
+    let path = format!(r"\\.\PhysicalDrive{}", disk_num);
+    let wide = to_pcwstr(&path);
+
+    let access = if write {
+        FILE_GENERIC_READ.0 | FILE_GENERIC_WRITE.0
+    } else {
+        FILE_GENERIC_READ.0
+    };
+
+    unsafe {
+        CreateFileW(
+            PCWSTR(wide.as_ptr()),
+            access,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    }
+    .map(OwnedHandle::new)
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("CreateFileW({}) failed: {e}", path)))
+}
+
+/// Raw 512-byte ATA IDENTIFY DEVICE data via IOCTL_ATA_PASS_THROUGH.
+fn ata_identify(disk_num: u32) -> io::Result<Vec<u8>> {
+    let mut task_file = [0u8; 8];
+    task_file[6] = ATA_CMD_IDENTIFY_DEVICE;
+    ata_read_data(disk_num, task_file)
+}
+
+/// Raw 512-byte SMART READ DATA page via IOCTL_ATA_PASS_THROUGH.
+fn ata_smart_data(disk_num: u32) -> io::Result<Vec<u8>> {
+    let mut task_file = [0u8; 8];
+    task_file[0] = SMART_READ_DATA;
+    task_file[3] = SMART_LBA_MID;
+    task_file[4] = SMART_LBA_HIGH;
+    task_file[6] = ATA_CMD_SMART;
+    ata_read_data(disk_num, task_file)
+}
+
+/// SMART attribute table of an ATA disk, for --report-include-smart.
+pub fn read_smart(disk_num: u32) -> io::Result<Vec<SmartAttribute>> {
+    parse_smart_attributes(&ata_smart_data(disk_num)?)
+}
+
+/// Issue one PIO data-in ATA command (`task_file` = CurrentTaskFile) and return its 512 bytes.
+fn ata_read_data(disk_num: u32, task_file: [u8; 8]) -> io::Result<Vec<u8>> {
+    let handle = open_disk_handle(disk_num, true)?;
+
+    let mut req = AtaDataBuffer {
+        apt: AtaPassThroughExLocal {
+            Length: std::mem::size_of::<AtaPassThroughExLocal>() as u16,
+            AtaFlags: ATA_FLAGS_DRDY_REQUIRED | ATA_FLAGS_DATA_IN,
+            DataTransferLength: IDENTIFY_LEN as u32,
+            TimeOutValue: 10,
+            DataBufferOffset: std::mem::size_of::<AtaPassThroughExLocal>(),
+            ..Default::default()
+        },
+        data: [0u8; IDENTIFY_LEN],
+    };
+    req.apt.CurrentTaskFile = task_file;
+
+    let mut br: u32 = 0;
+    let size = std::mem::size_of::<AtaDataBuffer>() as u32;
+
+    let res = unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_ATA_PASS_THROUGH,
+            Some(&req as *const _ as *const _),
+            size,
+            Some(&mut req as *mut _ as *mut _),
+            size,
+            Some(&mut br),
+            None,
+        )
+    };
+
+    res.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("IOCTL_ATA_PASS_THROUGH failed: {e}"),
+        )
+    })?;
+
+    Ok(req.data.to_vec())
+}
+
+/// Ask the drive to discard every block (whole-disk DSM TRIM).
+fn trim_disk(disk_num: u32) -> io::Result<()> {
+    let handle = open_disk_handle(disk_num, true)?;
+
+    let dsm = DeviceManageDataSetAttributesLocal {
+        Size: std::mem::size_of::<DeviceManageDataSetAttributesLocal>() as u32,
+        Action: DEVICE_DSM_ACTION_TRIM,
+        Flags: DEVICE_DSM_FLAG_ENTIRE_DATA_SET_RANGE,
+        ..Default::default()
+    };
+    let mut br: u32 = 0;
+
+    let res = unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES,
+            Some(&dsm as *const _ as *const _),
+            std::mem::size_of::<DeviceManageDataSetAttributesLocal>() as u32,
+            None,
+            0,
+            Some(&mut br),
+            None,
+        )
+    };
+
+    res.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("TRIM failed: {e}")))
+}
+
+/// Model / serial of a physical disk via IOCTL_STORAGE_QUERY_PROPERTY.
+fn query_identity(disk_num: u32) -> io::Result<DiskIdentity> {
+    parse_device_descriptor(&storage_query(disk_num, STORAGE_DEVICE_PROPERTY)?)
+}
+
+/// WWN / EUI-64 / NGUID identifiers of a physical disk (StorageDeviceIdProperty).
+fn query_device_ids(disk_num: u32) -> io::Result<Vec<DeviceId>> {
+    parse_device_ids(&storage_query(disk_num, STORAGE_DEVICE_ID_PROPERTY)?)
+}
+
+/// Raw IOCTL_STORAGE_QUERY_PROPERTY output for one standard property query.
+fn storage_query(disk_num: u32, property_id: u32) -> io::Result<Vec<u8>> {
+    let path = format!(r"\\.\PhysicalDrive{}", disk_num);
+    let wide = to_pcwstr(&path);
+
+    let handle = unsafe {
+        CreateFileW(
+            PCWSTR(wide.as_ptr()),
+            FILE_GENERIC_READ.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    }
+    .map(OwnedHandle::new)
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("CreateFileW failed: {e}")))?;
+
+    let query = StoragePropertyQueryLocal {
+        PropertyId: property_id,
+        QueryType: PROPERTY_STANDARD_QUERY,
+        AdditionalParameters: [0],
+    };
+    let mut out = vec![0u8; 1024];
+    let mut br: u32 = 0;
+
+    let res = unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_STORAGE_QUERY_PROPERTY,
+            Some(&query as *const _ as *const _),
+            std::mem::size_of::<StoragePropertyQueryLocal>() as u32,
+            Some(out.as_mut_ptr() as *mut _),
+            out.len() as u32,
+            Some(&mut br),
+            None,
+        )
+    };
+
+    res.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("DeviceIoControl failed: {e}")))?;
+
+    out.truncate(br as usize);
+    Ok(out)
+}
+
+/// --device-wwn / --device-guid: the `\\.\PhysicalDriveN` path that currently
+/// holds the disk with that identifier. Exactly one disk must match.
+pub fn resolve_device_id(wwn: Option<&str>, guid: Option<&str>) -> io::Result<String> {
+    let (what, id) = match (wwn, guid) {
+        (Some(w), _) => ("WWN", w),
+        (None, Some(g)) => ("GUID", g),
+        (None, None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no WWN or GUID given",
+            ))
+        }
+    };
+
+    let mut found: Vec<u32> = Vec::new();
+    for index in 0..16 {
+        // missing indexes and disks without StorageDeviceIdProperty just don't match
+        let ids = match query_device_ids(index) {
+            Ok(ids) => ids,
+            Err(_) => continue,
+        };
+        let hit = match wwn {
+            Some(w) => matches_wwn(&ids, w),
+            None => matches_guid(&ids, id),
+        };
+        if hit {
+            found.push(index);
+        }
+    }
+
+    match found.as_slice() {
+        [index] => {
+            println!("[*] {} {} is PhysicalDrive{}", what, id, index);
+            Ok(format!(r"\\.\PhysicalDrive{}", index))
+        }
+        [] => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no disk reports {} {}", what, id),
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} {} matches several disks: {:?}", what, id, found),
+        )),
+    }
+}
+
+/// Physical sector size (StorageAccessAlignmentProperty); 4096 on a 512e
+/// drive whose geometry reports 512-byte logical sectors.
+fn disk_physical_sector_size(disk_num: u32) -> io::Result<u32> {
+    parse_physical_sector_size(&storage_query(disk_num, STORAGE_ACCESS_ALIGNMENT_PROPERTY)?)
+}
+
+/// On a 512e drive, shrink the buffer and --io-size in `wipe` to whole
+/// physical sectors. Best effort: a disk that doesn't report one is left alone.
+pub fn align_for_physical_sectors(disk_num: u32, wipe: &mut WipeOptions) {
+    if let Ok(physical) = disk_physical_sector_size(disk_num) {
+        apply_physical_alignment(disk_num, physical as usize, wipe);
+    }
+}
+
+/// The alignment itself, once the physical sector size is known; nothing
+/// changes unless it is larger than the logical `wipe.sector_size`.
+fn apply_physical_alignment(disk_num: u32, physical: usize, wipe: &mut WipeOptions) {
+    if physical <= wipe.sector_size {
+        return;
+    }
+
+    say(format!(
+        "Note: PhysicalDrive{} has {}-byte physical sectors ({}-byte logical); \
+         aligning writes to {} bytes.",
+        disk_num, physical, wipe.sector_size, physical
+    ));
+    wipe.buffer_size = align_to_physical(wipe.buffer_size, physical);
+    wipe.io_size = wipe.io_size.map(|n| align_to_physical(n, physical));
+    if wipe.offset % physical as u64 != 0 {
+        say(format!(
+            "Note: offset {} is not on a physical sector boundary; expect slower writes.",
+            wipe.offset
+        ));
+    }
+}
+
+/// Logical sector size reported by IOCTL_DISK_GET_DRIVE_GEOMETRY.
+pub fn disk_sector_size(disk_num: u32) -> io::Result<u32> {
+    Ok(disk_geometry(disk_num)?.BytesPerSector)
+}
+
+/// Size implied by the drive geometry; may be slightly below the true length.
+fn geometry_size(disk_num: u32) -> io::Result<u64> {
+    let g = disk_geometry(disk_num)?;
+    Ok(g.Cylinders.max(0) as u64
+        * g.TracksPerCylinder as u64
+        * g.SectorsPerTrack as u64
+        * g.BytesPerSector as u64)
+}
+
+fn disk_geometry(disk_num: u32) -> io::Result<DISK_GEOMETRY> {
+    let path = format!(r"\\.\PhysicalDrive{}", disk_num);
+    let wide = to_pcwstr(&path);
+
+    let handle = unsafe {
+        CreateFileW(
+            PCWSTR(wide.as_ptr()),
+            FILE_GENERIC_READ.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    }
+    .map(OwnedHandle::new)
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("CreateFileW failed: {e}")))?;
+
+    let mut geometry = DISK_GEOMETRY::default();
+    let mut br: u32 = 0;
+
+    let res = unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_DISK_GET_DRIVE_GEOMETRY,
+            None,
+            0,
+            Some(&mut geometry as *mut _ as *mut _),
+            std::mem::size_of::<DISK_GEOMETRY>() as u32,
+            Some(&mut br),
+            None,
+        )
+    };
+
+    res.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("DeviceIoControl failed: {e}")))?;
+
+    if geometry.BytesPerSector == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Drive geometry reports 0 bytes per sector",
+        ));
+    }
+
+    Ok(geometry)
+}
+
+fn system_drive() -> String {
+    env::var("SYSTEMDRIVE").unwrap_or_else(|_| "C:".to_string())
+}
+
+fn detect_system_disk() -> Option<u32> {
+    say(format!(
+        "Attempting to auto-detect system disk via volume: \\\\.\\{}",
+        system_drive()
+    ));
+
+    match system_disk_from_volume() {
+        Ok(n) => Some(n),
+        Err(e) => {
+            say_err(format!("Auto-detect: {}", e));
+            None
+        }
+    }
+}
+
+/// Disk holding the first extent of %SYSTEMDRIVE%, without printing anything.
+fn system_disk_from_volume() -> io::Result<u32> {
+    match volume_extents(&system_drive())?.first() {
+        Some(x) => Ok(x.DiskNumber),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "volume reports 0 disk extents.",
+        )),
+    }
+}
+
+/// Disk extents backing a volume such as "C:".
+fn volume_extents(volume: &str) -> io::Result<Vec<DiskExtent>> {
+    let volume_path = format!(r"\\.\{}", volume);
+    let wide = to_pcwstr(&volume_path);
+
+    let handle = unsafe {
+        CreateFileW(
+            PCWSTR(wide.as_ptr()),
+            FILE_GENERIC_READ.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    }
+    .map(OwnedHandle::new)
+    .map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("CreateFileW({}) failed: {}", volume_path, e),
+        )
+    })?;
+
+    let mut info = VolumeDiskExtentsLocal {
+        NumberOfDiskExtents: 0,
+        Extents: [DiskExtent {
+            DiskNumber: 0,
+            StartingOffset: 0,
+            ExtentLength: 0,
+        }; MAX_VOLUME_EXTENTS],
+    };
+    let mut br: u32 = 0;
+
+    let res = unsafe {
+        DeviceIoControl(
+            handle.0,
+            IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS,
+            None,
+            0,
+            Some(&mut info as *mut _ as *mut _),
+            std::mem::size_of::<VolumeDiskExtentsLocal>() as u32,
+            Some(&mut br),
+            None,
+        )
+    };
+
+    res.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "DeviceIoControl(IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS) on {} failed: {}",
+                volume_path, e
+            ),
+        )
+    })?;
+
+    let n = (info.NumberOfDiskExtents as usize).min(MAX_VOLUME_EXTENTS);
+    Ok(info.Extents[..n].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::{hold_output, take_output};
+    use crate::wipe::WipeMode;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// One handle on a disk image shared by every handle opened on it.
+    struct ImageHandle {
+        image: Rc<RefCell<Vec<u8>>>,
+        pos: usize,
+    }
+
+    impl Read for ImageHandle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let image = self.image.borrow();
+            let n = buf.len().min(image.len().saturating_sub(self.pos));
+            buf[..n].copy_from_slice(&image[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    impl Write for ImageHandle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut image = self.image.borrow_mut();
+            let n = buf.len().min(image.len().saturating_sub(self.pos));
+            image[self.pos..self.pos + n].copy_from_slice(&buf[..n]);
+            self.pos += n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for ImageHandle {
+        fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+            match to {
+                SeekFrom::Start(n) => self.pos = n as usize,
+                _ => unimplemented!(),
+            }
+            Ok(self.pos as u64)
+        }
+    }
+
+    /// 4096 sectors of 0xEE with an MBR whose first partition starts at LBA 2048.
+    fn gap_disk() -> Rc<RefCell<Vec<u8>>> {
+        let mut image = vec![0xEEu8; 4096 * 512];
+        image[..512].fill(0);
+        image[446 + 4] = 0x07;
+        image[446 + 8..446 + 12].copy_from_slice(&2048u32.to_le_bytes());
+        image[446 + 12..446 + 16].copy_from_slice(&1000u32.to_le_bytes());
+        image[510] = 0x55;
+        image[511] = 0xAA;
+        Rc::new(RefCell::new(image))
+    }
+
+    /// Run the MBR gap wipe on `image`, returning the write flag of every open.
+    fn gap_wipe(image: &Rc<RefCell<Vec<u8>>>, dry_run: bool, confirmed: bool) -> Vec<bool> {
+        let mut opened = Vec::new();
+        let open = |write: bool| {
+            opened.push(write);
+            Ok(ImageHandle {
+                image: image.clone(),
+                pos: 0,
+            })
+        };
+        wipe_mbr_gap("disk", 512, dry_run, open, || Ok(confirmed)).unwrap();
+        opened
+    }
+
+    fn disk(index: u32, excluded: bool) -> DiskInfo {
+        DiskInfo {
+            index,
+            size_bytes: 500u64 << 30,
+            is_protected: true,
+            excluded,
+            identity: DiskIdentity::default(),
+        }
+    }
+
+    #[test]
+    fn no_wipeable_disks_names_each_reason_and_the_override() {
+        let disks = [disk(0, false), disk(1, false), disk(2, true)];
+        let mut protected = BTreeMap::new();
+        protected.insert(1, vec!["hosts C:".to_string()]);
+
+        let msg = no_wipeable_disks_message(&disks, &protected, 0);
+        let lines: Vec<&str> = msg.lines().collect();
+        assert!(lines[0].ends_with("Found 3 disk(s), all protected:"));
+        assert_eq!(lines[1], "  PhysicalDrive0 - 500 GB - system disk");
+        assert_eq!(lines[2], "  PhysicalDrive1 - 500 GB - hosts C:");
+        assert_eq!(
+            lines[3],
+            "  PhysicalDrive2 - 500 GB - excluded by --exclude-serial"
+        );
+        assert!(lines[4].contains("If PhysicalDrive0 is not really the system disk"));
+        assert!(lines[4].contains("pass --system-disk <N>"));
+    }
+
+    fn extent(disk: u32, start: i64, len: i64) -> DiskExtent {
+        DiskExtent {
+            DiskNumber: disk,
+            StartingOffset: start,
+            ExtentLength: len,
+        }
+    }
+
+    #[test]
+    fn volume_wipe_ranges_cover_every_extent() {
+        // a volume spanned over three disks, with one empty entry
+        let extents = [
+            extent(2, 1 << 20, 100 << 20),
+            extent(3, 1 << 20, 0),
+            extent(3, 128 << 20, 50 << 20),
+            extent(1, 1 << 20, 10 << 20),
+        ];
+        assert_eq!(
+            volume_wipe_ranges(&extents),
+            vec![
+                (2, 1 << 20, 100 << 20),
+                (3, 128 << 20, 50 << 20),
+                (1, 1 << 20, 10 << 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn mbr_gap_dry_run_opens_no_write_handle() {
+        let image = gap_disk();
+        let before = image.borrow().clone();
+        assert_eq!(gap_wipe(&image, true, true), [false]);
+        assert!(*image.borrow() == before);
+    }
+
+    #[test]
+    fn declined_mbr_gap_wipe_opens_no_write_handle() {
+        let image = gap_disk();
+        assert_eq!(gap_wipe(&image, false, false), [false]);
+    }
+
+    #[test]
+    fn mbr_gap_wipe_zeros_only_the_gap() {
+        let image = gap_disk();
+        let mbr = image.borrow()[..512].to_vec();
+        assert_eq!(gap_wipe(&image, false, true), [false, true]);
+
+        let image = image.borrow();
+        assert_eq!(&image[..512], &mbr[..]);
+        assert!(image[512..2048 * 512].iter().all(|&b| b == 0));
+        assert!(image[2048 * 512..].iter().all(|&b| b == 0xEE));
+    }
+
+    #[test]
+    fn excluded_serial_is_not_selectable() {
+        let id = |serial: &str| DiskIdentity {
+            serial: Some(serial.to_string()),
+            ..DiskIdentity::default()
+        };
+        let mut protected = BTreeMap::new();
+        protected.insert(0, vec!["system disk".to_string()]);
+        let excludes = vec!["wd-123abc".to_string()];
+        let disks = [
+            DiskInfo::new(0, 1 << 30, id("SYS"), &protected, &excludes),
+            DiskInfo::new(1, 1 << 30, id("WD-123ABC"), &protected, &excludes),
+            DiskInfo::new(2, 1 << 30, id("ST-999"), &protected, &excludes),
+        ];
+        assert!(disks[1].excluded && !disks[0].excluded);
+
+        let selectable: Vec<u32> = selectable_disks(&disks).iter().map(|d| d.index).collect();
+        assert_eq!(selectable, [2]);
+    }
+
+    #[test]
+    fn safe_mode_refuses_only_disks_over_the_limit() {
+        let limit = 4u64 << 40;
+        assert!(check_size_limit(500 << 30, limit).is_ok());
+        assert!(check_size_limit(limit, limit).is_ok());
+        let err = check_size_limit(limit + 512, limit).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn mounted_volumes_are_found_from_their_extents() {
+        let mappings = vec![
+            ("C:".to_string(), vec![0]),
+            ("D:".to_string(), vec![1]),
+            // a spanned volume backs every disk it has an extent on
+            ("E:".to_string(), vec![0, 2]),
+        ];
+
+        assert_eq!(volumes_on_disk(&mappings, 0), vec!["C:", "E:"]);
+        assert_eq!(volumes_on_disk(&mappings, 2), vec!["E:"]);
+        assert!(volumes_on_disk(&mappings, 3).is_empty());
+    }
+
+    thread_local! {
+        static CLOSED: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+    }
+
+    fn record_close(h: HANDLE) -> windows::core::Result<()> {
+        CLOSED.with(|c| c.borrow_mut().push(h.0 as usize));
+        Ok(())
+    }
+
+    #[test]
+    fn owned_handle_is_closed_once_on_every_path() {
+        let early_return = || -> io::Result<()> {
+            let _handle = OwnedHandle(HANDLE(7 as *mut _), record_close);
+            Err(io::Error::new(io::ErrorKind::Other, "IOCTL failed"))
+        };
+        assert!(early_return().is_err());
+        assert_eq!(CLOSED.with(|c| c.borrow().clone()), vec![7]);
+
+        drop(OwnedHandle(HANDLE(8 as *mut _), record_close));
+        assert_eq!(CLOSED.with(|c| c.borrow().clone()), vec![7, 8]);
+    }
+
+    #[test]
+    fn ctl_codes_match_the_sdk_headers() {
+        assert_eq!(IOCTL_STORAGE_QUERY_PROPERTY, 0x002D_1400);
+        assert_eq!(IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES, 0x002D_9404);
+        assert_eq!(IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS, 0x0056_0000);
+        assert_eq!(IOCTL_ATA_PASS_THROUGH, 0x0004_D02C);
+        assert_eq!(IOCTL_SCSI_PASS_THROUGH, 0x0004_D004);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "function must fit in 12 bits")]
+    fn ctl_code_rejects_an_out_of_range_function() {
+        let function = std::hint::black_box(0x1000);
+        ctl_code(IOCTL_STORAGE_BASE, function, 0, 0);
+    }
+
+    #[test]
+    fn second_lock_on_a_target_fails_until_the_first_is_dropped() {
+        let target = format!(r"\\.\PhysicalDrive9{}", std::process::id());
+        let first = TargetLock::acquire(&target).unwrap();
+
+        // the name ignores case, so another spelling is the same target
+        let err = TargetLock::acquire(&target.to_uppercase()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("already being wiped"));
+
+        drop(first);
+        assert!(TargetLock::acquire(&target).is_ok());
+    }
+
+    #[test]
+    fn phrase_for_another_disk_is_recognised() {
+        // pasted with the usual trailing newline
+        assert_eq!(phrase_for_other_disk("WIPE-DISK-3\r\n", 2), Some(3));
+        assert_eq!(phrase_for_other_disk("  WIPE-DISK-12", 1), Some(12));
+        // the right disk, or not the phrase at all, is left to the exact compare
+        assert_eq!(phrase_for_other_disk("WIPE-DISK-2 ", 2), None);
+        assert_eq!(phrase_for_other_disk("WIPE-DISK-", 2), None);
+        assert_eq!(phrase_for_other_disk("wipe-disk-3", 2), None);
+        assert_eq!(phrase_for_other_disk("WIPE-GAP-3", 2), None);
+    }
+
+    #[test]
+    fn protected_preview_lists_every_disk_behind_a_volume() {
+        let mappings = vec![
+            (r"boot volume C: (C:\Windows)".to_string(), vec![0]),
+            // a spanned D: backs two disks, and holds two protected files
+            ("pagefile.sys on D:".to_string(), vec![2, 3]),
+            ("hiberfil.sys on D:".to_string(), vec![2, 3]),
+            ("pagefile.sys on D:".to_string(), vec![3]),
+        ];
+        let protected = merge_protected(0, &mappings);
+
+        assert_eq!(protected.keys().copied().collect::<Vec<_>>(), [0, 2, 3]);
+        assert_eq!(
+            protected[&0],
+            ["system disk", r"boot volume C: (C:\Windows)"]
+        );
+        assert_eq!(protected[&3], ["pagefile.sys on D:", "hiberfil.sys on D:"]);
+    }
+
+    #[test]
+    fn dumped_table_carries_the_volume_extents_code() {
+        assert_eq!(ctl_code(0x56, 0, 0, 0), 0x0056_0000);
+        assert_eq!(
+            CTL_CODE_TABLE[0],
+            ("IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS", 0x0056_0000)
+        );
+        // the disk codes come from the windows crate; they must line up too
+        assert!(CTL_CODE_TABLE.contains(&("IOCTL_DISK_GET_LENGTH_INFO", 0x0007_405C)));
+        assert!(CTL_CODE_TABLE.contains(&("IOCTL_DISK_GET_DRIVE_GEOMETRY", 0x0007_0000)));
+    }
+
+    #[test]
+    fn operator_defaults_to_the_logged_in_user() {
+        let name = current_username().expect("a logged-in user");
+        // the length GetUserNameW returns counts the NUL, which must be cut off
+        assert!(!name.is_empty() && !name.contains('\0'));
+    }
+
+    #[test]
+    fn quiet_listing_leaves_out_the_detection_narration() {
+        let narration = [
+            "Using user-specified",
+            "Auto-detect",
+            "Could not auto-detect",
+        ];
+
+        hold_output();
+        let res = list_disks(16, Some(3), true);
+        let quiet = take_output();
+        res.unwrap();
+        assert!(quiet.contains("Detected physical disks (0..15):"));
+        assert!(!narration.iter().any(|n| quiet.contains(n)), "{}", quiet);
+
+        hold_output();
+        let res = list_disks(16, Some(3), false);
+        let full = take_output();
+        res.unwrap();
+        assert!(full.contains("Using user-specified system disk: PhysicalDrive3"));
+        // the disk rows are the same either way
+        assert!(full.ends_with(&quiet));
+    }
+
+    #[test]
+    fn cache_requests_only_switch_the_write_cache() {
+        let saved = DISK_CACHE_INFORMATION {
+            ParametersSavable: BOOLEAN(1),
+            ReadCacheEnabled: BOOLEAN(1),
+            WriteCacheEnabled: BOOLEAN(1),
+            DisablePrefetchTransferLength: 0xFFFF,
+            ..Default::default()
+        };
+
+        let off = write_cache_request(&saved, false);
+        assert_eq!(off.WriteCacheEnabled.0, 0);
+        assert_eq!(off.ReadCacheEnabled.0, 1);
+        assert_eq!(off.DisablePrefetchTransferLength, 0xFFFF);
+        // a temporary change must not outlive a power cycle
+        assert_eq!(off.ParametersSavable.0, 0);
+
+        // what the guard puts back on drop
+        let restored = write_cache_request(&saved, true);
+        assert_eq!(restored.WriteCacheEnabled.0, 1);
+        assert_eq!(restored.ReadCacheEnabled.0, 1);
+        assert_eq!(restored.ParametersSavable.0, 0);
+    }
+
+    #[test]
+    fn low_priority_lowers_the_class_and_puts_it_back() {
+        let process = unsafe { GetCurrentProcess() };
+        let before = unsafe { GetPriorityClass(process) };
+
+        hold_output();
+        let low = LowPriority::enter().unwrap();
+        let during = unsafe { GetPriorityClass(process) };
+        drop(low);
+        take_output();
+        assert_eq!(during, BELOW_NORMAL_PRIORITY_CLASS.0);
+        assert_eq!(unsafe { GetPriorityClass(process) }, before);
+
+        // a class SetPriorityClass refuses only costs a warning
+        hold_output();
+        drop(LowPriority { saved_class: 1 });
+        let out = take_output();
+        assert!(out.contains("Warning: could not restore the priority"));
+        assert_eq!(unsafe { GetPriorityClass(process) }, before);
+    }
+
+    #[test]
+    fn writes_align_to_4k_physical_sectors_on_512e() {
+        let mut wipe = WipeOptions::new(WipeMode::Zeros, 1);
+        wipe.sector_size = 512;
+        wipe.buffer_size = (1 << 20) + 512;
+        wipe.io_size = Some(1536);
+
+        hold_output();
+        apply_physical_alignment(2, 4096, &mut wipe);
+        let out = take_output();
+        assert_eq!(wipe.buffer_size, 1 << 20);
+        // never below one physical sector
+        assert_eq!(wipe.io_size, Some(4096));
+        assert!(out.contains("4096-byte physical sectors (512-byte logical)"));
+
+        // 512n and 4Kn drives are left as they are
+        let mut native = WipeOptions::new(WipeMode::Zeros, 1);
+        native.sector_size = 4096;
+        native.io_size = Some(8192 + 4096);
+        let before = native.buffer_size;
+        apply_physical_alignment(2, 4096, &mut native);
+        assert_eq!((native.buffer_size, native.io_size), (before, Some(12288)));
+    }
 }
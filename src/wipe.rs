@@ -1,147 +1,2966 @@
 use std::fs::File;
-use std::io::{self, Seek, SeekFrom, Write};
-use std::path::Path;
-use std::time::Instant;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
 
 use clap::ValueEnum;
-use rand::RngCore;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use sha2::{Digest, Sha256};
 
-use crate::util::format_eta;
+use crate::aligned::{AlignedBuf, IO_ALIGN};
+use crate::checkpoint::CheckpointFile;
+use crate::progress::{
+    is_compact, say, ConsoleProgress, FnProgress, Progress, ProgressSink, ProgressTracker,
+    RateWindow,
+};
+use crate::util::{
+    check_interrupted, confirm, format_eta, format_utc, hex_string, is_interrupted, phrase_prompt,
+    size_format, unix_now,
+};
+
+/// Default size of one write / read chunk.
+pub const DEFAULT_BUFFER: usize = 8 * 1024 * 1024;
+
+/// --buffer-size may use at most this fraction (1/N) of free physical memory.
+const MAX_BUFFER_RAM_FRACTION: u64 = 4;
+
+/// --stamp text stays clear of the MBR partition table (offset 446) and signature.
+const STAMP_MAX_TEXT: usize = 440;
+
+/// --min-throughput: sustained rate is measured over this trailing window.
+const THROUGHPUT_WINDOW_SECS: f64 = 10.0;
+
+/// Windows error some devices return for a write they will only take in smaller pieces.
+const ERROR_INVALID_PARAMETER: i32 = 87;
+
+/// Smallest chunk the automatic buffer downshift will go to.
+const MIN_BUFFER: usize = 64 * 1024;
+
+/// Gutmann passes 5-31; passes 1-4 and 32-35 are random.
+const GUTMANN_FIXED: [&[u8]; 27] = [
+    &[0x55],
+    &[0xAA],
+    &[0x92, 0x49, 0x24],
+    &[0x49, 0x24, 0x92],
+    &[0x24, 0x92, 0x49],
+    &[0x00],
+    &[0x11],
+    &[0x22],
+    &[0x33],
+    &[0x44],
+    &[0x55],
+    &[0x66],
+    &[0x77],
+    &[0x88],
+    &[0x99],
+    &[0xAA],
+    &[0xBB],
+    &[0xCC],
+    &[0xDD],
+    &[0xEE],
+    &[0xFF],
+    &[0x92, 0x49, 0x24],
+    &[0x49, 0x24, 0x92],
+    &[0x24, 0x92, 0x49],
+    &[0x6D, 0xB6, 0xDB],
+    &[0xB6, 0xDB, 0x6D],
+    &[0xDB, 0x6D, 0xB6],
+];
+
+/// Block size checked for a single repeated byte after a random final pass.
+const UNIFORM_BLOCK: usize = 512;
+
+/// --estimate times the random generator for this long.
+const RNG_PROBE: Duration = Duration::from_secs(1);
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum WipeMode {
+    /// Every pass writes 0x00
     Zeros,
+    /// Every pass writes fresh random bytes
     Random,
+    /// Alternates 0x00 / 0xFF; always at least 2 passes
     Secureflip,
+    /// Random pass, then its exact bitwise complement; always at least 2 passes
+    RandomComplement,
+    /// Random pass, then the complement of what is read back; no seed needed
+    RandomInvert,
+    /// DoD 5220.22-M: 0x00, then 0xFF, then random; always exactly 3 passes
+    #[value(name = "dod522022m")]
+    Dod522022M,
+    /// Gutmann: 4 random, 27 fixed patterns, 4 random; always exactly 35 passes
+    Gutmann,
+    /// Every pass writes a keystream derived from a run-unique nonce (see the report)
+    Nonce,
+}
+
+/// One row of the --list-modes table.
+pub struct ModeInfo {
+    pub passes: &'static str,
+    pub recommended: &'static str,
+    pub speed: &'static str,
+}
+
+/// Description for every mode; the match keeps it in sync with `WipeMode`.
+pub fn mode_info(mode: WipeMode) -> ModeInfo {
+    match mode {
+        WipeMode::Zeros => ModeInfo {
+            passes: "each pass writes 0x00",
+            recommended: "SSD / HDD quick clean, easy to verify",
+            speed: "fastest (disk bound)",
+        },
+        WipeMode::Random => ModeInfo {
+            passes: "each pass writes fresh random bytes",
+            recommended: "HDD, or when zeroed media must not be recognisable",
+            speed: "fast, can be CPU bound on slow machines",
+        },
+        WipeMode::Secureflip => ModeInfo {
+            passes: "odd passes 0x00, even passes 0xFF (at least 2 passes)",
+            recommended: "HDD, flips every bit at least once",
+            speed: "2x zeros at minimum",
+        },
+        WipeMode::RandomComplement => ModeInfo {
+            passes: "odd passes random, even passes invert the pass before (at least 2)",
+            recommended: "legacy magnetic media, maximum flux transitions",
+            speed: "like random, CPU bound on slow machines",
+        },
+        WipeMode::RandomInvert => ModeInfo {
+            passes: "odd passes random, even passes read back and invert the media (at least 2)",
+            recommended: "like random-complement when no seed can be kept",
+            speed: "inverting passes also read the whole target, about 2x a write pass",
+        },
+        WipeMode::Dod522022M => ModeInfo {
+            passes: "exactly 3: 0x00, 0xFF, random (--passes is ignored)",
+            recommended: "when a policy names DoD 5220.22-M; add --verify for the read-back",
+            speed: "3 passes, the last one like random",
+        },
+        WipeMode::Gutmann => ModeInfo {
+            passes: "exactly 35: 4 random, 27 fixed (incl. 3-byte) patterns, 4 random",
+            recommended: "old MFM/RLL drives; pointless on modern media beyond a policy",
+            speed: "35x a single pass",
+        },
+        WipeMode::Nonce => ModeInfo {
+            passes: "each pass writes a 4 KiB block derived from this run's nonce, repeating",
+            recommended: "audits: the nonce in the report shows the media holds this wipe",
+            speed: "like zeros, the block is computed once",
+        },
+    }
+}
+
+/// Print the --list-modes table.
+pub fn list_modes() {
+    println!("Available wipe modes:");
+    for mode in WipeMode::value_variants() {
+        let name = mode
+            .to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_else(|| format!("{:?}", mode));
+        let info = mode_info(*mode);
+
+        println!();
+        println!("  {}", name);
+        println!("    Passes      : {}", info.passes);
+        println!("    Recommended : {}", info.recommended);
+        println!("    Speed       : {}", info.speed);
+    }
+}
+
+/// What to do when a pass fails --verify-each.
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum OnVerifyFail {
+    /// Keep going (the final pass must still verify)
+    Continue,
+    /// Stop the wipe with an error
+    Abort,
+    /// Write the failed pass again once, then abort if it still fails
+    Rewipe,
+}
+
+/// How --offset / --length are interpreted.
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum Units {
+    Bytes,
+    /// Multiply by the device's logical sector size
+    Sectors,
+}
+
+/// Per-wipe settings passed down to the chunk loop.
+#[derive(Clone, Debug)]
+pub struct WipeOptions {
+    pub mode: WipeMode,
+    pub passes: u32,
+    /// Byte offset where the wiped range starts (0 = whole target)
+    pub offset: u64,
+    /// Bytes per write / read chunk
+    pub buffer_size: usize,
+    /// Split each chunk into device writes of this size (None = one write per chunk)
+    pub io_size: Option<usize>,
+    /// Logical sector size; a rejected chunk is retried in writes of this size
+    pub sector_size: usize,
+    /// Force data to media every N bytes within a pass (None = only at pass end)
+    pub sync_every: Option<u64>,
+    /// Verify every fixed-pattern pass right after writing it
+    pub verify_each: bool,
+    pub on_verify_fail: OnVerifyFail,
+    /// Abort if sustained MB/s falls below this after the grace period
+    pub min_throughput: Option<f64>,
+    /// Ignore the first part of a pass (spin-up, caches) for --min-throughput
+    pub throughput_grace: Duration,
+    /// Compute the ETA from the last this-many bytes written instead of the pass average
+    pub eta_window: Option<u64>,
+    /// Print a timestamped absolute offset this often (for audit logs)
+    pub checkpoint_every: Option<Duration>,
+    /// Save progress here every `save_every` so an interrupted file wipe can resume
+    pub checkpoint: Option<CheckpointFile>,
+    /// Base seed for random passes; pass N uses `pass_seed(seed, N)`
+    pub seed: Option<u64>,
+    /// Run-unique nonce the `Nonce` mode keystream is derived from
+    pub nonce: Option<u64>,
+    /// Write each pass from the last chunk back toward the first
+    pub reverse: bool,
+}
+
+impl WipeOptions {
+    pub fn new(mode: WipeMode, passes: u32) -> Self {
+        WipeOptions {
+            mode,
+            passes,
+            offset: 0,
+            buffer_size: DEFAULT_BUFFER,
+            io_size: None,
+            sector_size: 512,
+            sync_every: None,
+            verify_each: false,
+            on_verify_fail: OnVerifyFail::Abort,
+            min_throughput: None,
+            throughput_grace: Duration::from_secs(30),
+            eta_window: None,
+            checkpoint_every: None,
+            checkpoint: None,
+            seed: None,
+            nonce: None,
+            reverse: false,
+        }
+    }
 }
 
-/// Ask user before wiping a file (not used for disk wipe flow).
-pub fn confirm_wipe(path: &Path) -> io::Result<()> {
-    use std::io::{stdin, stdout};
+/// One summary listing every file target, so a single confirmation covers them all.
+pub fn build_summary(targets: &[(String, u64)], mode: WipeMode, passes: u32) -> String {
+    let passes = effective_passes(mode, passes);
+    let total: u64 = targets.iter().map(|(_, size)| *size).sum();
+
+    let mut out = format!("Targets ({}):\n", targets.len());
+    for (i, (path, size)) in targets.iter().enumerate() {
+        out.push_str(&format!("  [{}] {}  -  {}\n", i + 1, path, size_format(*size)));
+    }
+    out.push_str(&format!("Mode :   {:?}\n", mode));
+    out.push_str(&format!("Passes : {}\n", passes));
+    out.push_str(&format!(
+        "Total :  {} ({} written over all passes)\n",
+        size_format(total),
+        size_format(total.saturating_mul(passes as u64))
+    ));
+    out
+}
 
+/// Ask user before wiping the listed files (not used for disk wipe flow).
+pub fn confirm_wipe(count: usize) -> io::Result<bool> {
     println!();
-    println!("This will overwrite the file:");
-    println!("  {}", path.display());
+    println!("This will overwrite the {} file(s) listed above.", count);
     println!("This CANNOT be undone.");
     println!();
-    println!("Type 'YES' to continue:");
 
-    print!("> ");
-    stdout().flush()?; // make sure the prompt shows
+    confirm(&phrase_prompt("YES"), "YES", io::stdin().lock())
+}
 
-    let mut input = String::new();
-    stdin().read_line(&mut input)?;
+/// What the write loop needs from a target. `File` is the real one; the
+/// hidden --simulate-bad-sectors adapter wraps a `File` to inject failures.
+pub trait WipeTarget: Read + Write + Seek {
+    fn sync_data(&self) -> io::Result<()>;
+}
 
-    if input.trim().to_lowercase() != "YES" {
-        println!("Aborted by user.");
-        std::process::exit(0);
+impl WipeTarget for File {
+    fn sync_data(&self) -> io::Result<()> {
+        File::sync_data(self)
     }
-
-    Ok(())
 }
 
 /// Core wipe logic. Works for both files and physical drives.
-pub fn wipe_file(
-    mut file: File,
+pub fn wipe_file<T: WipeTarget>(
+    file: &mut T,
+    size: u64,
+    opts: &WipeOptions,
+) -> io::Result<WipeReport> {
+    wipe_file_with_sink(file, size, opts, &mut ConsoleProgress::new())
+}
+
+/// Same as `wipe_file`, but progress goes to the given sink.
+pub fn wipe_file_with_sink<T: WipeTarget>(
+    file: &mut T,
+    size: u64,
+    opts: &WipeOptions,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<WipeReport> {
+    let report = wipe_file_reported(file, size, opts, sink)?;
+    say(format!(
+        "[+] {} pass(es) in {} at {:.2} MB/s on average.",
+        report.passes,
+        format_eta(report.elapsed.as_secs()),
+        report.average_mib_s
+    ));
+    Ok(report)
+}
+
+/// Wipe with `mode` and `passes` (default settings otherwise) for frontends
+/// that draw their own UI: every progress snapshot goes to `on_progress`
+/// instead of the console line, and no summary is printed.
+pub fn wipe_file_with_progress<T: WipeTarget>(
+    file: &mut T,
     size: u64,
     mode: WipeMode,
-    mut passes: u32,
-) -> io::Result<()> {
-    use std::io::stdout;
+    passes: u32,
+    mut on_progress: impl FnMut(Progress),
+) -> io::Result<WipeReport> {
+    let opts = WipeOptions::new(mode, passes);
+    let mut sink = FnProgress(|p: &Progress| on_progress(*p));
+    wipe_file_reported(file, size, &opts, &mut sink)
+}
+
+/// What a finished wipe did, for callers that build their own summary or
+/// certificate instead of relying on the console output.
+#[derive(Clone, Debug)]
+pub struct WipeReport {
+    pub mode: WipeMode,
+    /// Passes completed by this call (fewer than the mode's count after a resume)
+    pub passes: u32,
+    pub bytes_written: u64,
+    /// Wall time of each completed pass, in order
+    pub pass_durations: Vec<Duration>,
+    pub elapsed: Duration,
+    /// MB/s (MiB, like the progress line) over the whole wipe
+    pub average_mib_s: f64,
+    /// Chunk size the final pass was written with: smaller than requested
+    /// after a downshift, and what regenerating a reverse pass needs
+    pub buffer_size: usize,
+}
 
-    const CHUNK: usize = 8 * 1024 * 1024;
-    let mut buf = vec![0u8; CHUNK];
-    let mut rng = rand::thread_rng();
+/// Same as `wipe_file_with_sink`, but returns a `WipeReport` and prints no summary.
+pub fn wipe_file_reported<T: WipeTarget>(
+    file: &mut T,
+    size: u64,
+    opts: &WipeOptions,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<WipeReport> {
+    let mode = opts.mode;
+    let passes = effective_passes(mode, opts.passes);
+
+    if matches!(mode, WipeMode::RandomComplement) && opts.seed.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "RandomComplement needs a seed to regenerate the pass it inverts",
+        ));
+    }
 
-    // SecureFlip should always be at least 2 passes
-    if let WipeMode::Secureflip = mode {
-        if passes < 2 {
-            println!(
-                "As you are using 'SecureFlip', passes changed from {} to 2",
-                passes
-            );
-            passes = 2;
+    if matches!(mode, WipeMode::Nonce) {
+        match opts.nonce {
+            Some(nonce) => say(format!("[*] Nonce for this wipe: {:016x}", nonce)),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Nonce mode needs a nonce to derive its pattern from",
+                ));
+            }
         }
     }
 
-    for pass in 1..=passes {
-        println!();
-        println!("=== Starting pass {}/{} ===", pass, passes);
-
-        file.seek(SeekFrom::Start(0))?;
-        let start = Instant::now();
-        let mut written: u64 = 0;
-
-        // ---- pre-fill buffer ONCE per pass when pattern is fixed ----
-        let static_pattern: Option<u8> = match mode {
-            WipeMode::Secureflip => {
-                // odd pass -> zeros, even pass -> ones
-                if pass % 2 == 1 {
-                    Some(0x00)
-                } else {
-                    Some(0xFF)
+    let mut buf = alloc_buffer(opts.buffer_size)?;
+
+    if passes != opts.passes {
+        say(format!(
+            "Note: {:?} {}; running {} instead of {}.",
+            mode,
+            pass_count_reason(mode),
+            passes,
+            opts.passes
+        ));
+    }
+
+    let (mut pass, mut resume_at) = resume_point(opts, size, passes)?;
+    let mut rewiped = false;
+    let mut expected_total: u64 = 0;
+    let mut written_total: u64 = 0;
+    // (expected, written) of the latest pass, taken back out if it is rewiped
+    let mut last_pass = (0, 0);
+    let mut pass_durations: Vec<Duration> = Vec::new();
+    let start = Instant::now();
+
+    while pass <= passes {
+        let pass_start = Instant::now();
+        match write_pass(file, size, opts, pass, resume_at, &mut buf, sink) {
+            Ok(n) => {
+                last_pass = (size - resume_at, n);
+                expected_total += last_pass.0;
+                written_total += last_pass.1;
+                pass_durations.push(pass_start.elapsed());
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::Interrupted {
+                    say(format!(
+                        "[!] {} of {} pass(es) completed before that ({} written).",
+                        pass - 1,
+                        passes,
+                        size_format(written_total)
+                    ));
+                }
+                if !is_buffer_limit_error(&e) || buf.len() / 2 < MIN_BUFFER {
+                    return Err(e);
                 }
+
+                let smaller = buf.len() / 2;
+                say("");
+                say(format!(
+                    "[!] Write with a {} buffer failed ({}); retrying pass {}/{} with {}.",
+                    size_format(buf.len() as u64),
+                    e,
+                    pass,
+                    passes,
+                    size_format(smaller as u64)
+                ));
+                // free the old buffer first; memory may be what ran out
+                drop(buf);
+                buf = AlignedBuf::new(smaller, IO_ALIGN)?;
+                continue;
             }
-            WipeMode::Zeros => Some(0x00),
-            WipeMode::Random => None,
-        };
+        }
+        resume_at = 0;
+
+        if opts.verify_each {
+            if let Some(expected) = pass_pattern(mode, pass) {
+                if let Err(e) = verify_pattern(file, opts.offset, size, buf.len(), expected, sink) {
+                    say("");
+                    say(format!(
+                        "[!] Pass {}/{} failed verification: {}",
+                        pass, passes, e
+                    ));
 
-        if let Some(byte) = static_pattern {
-            buf.fill(byte);
+                    match opts.on_verify_fail {
+                        OnVerifyFail::Rewipe if !rewiped => {
+                            say(format!("[*] Rewiping pass {}/{} once.", pass, passes));
+                            rewiped = true;
+                            // the rewrite's time and bytes replace the failed attempt's
+                            pass_durations.pop();
+                            expected_total -= last_pass.0;
+                            written_total -= last_pass.1;
+                            continue;
+                        }
+                        // the last pass is what stays on the media, it must verify
+                        OnVerifyFail::Continue if pass < passes => {
+                            say("[*] Continuing with the next pass.");
+                        }
+                        _ => return Err(e),
+                    }
+                }
+            }
+        }
+
+        rewiped = false;
+        pass += 1;
+
+        if let Some(cp) = &opts.checkpoint {
+            cp.save(pass, opts.offset)?;
+        }
+    }
+
+    check_written(expected_total, written_total)?;
+
+    if let Some(cp) = &opts.checkpoint {
+        cp.clear()?;
+    }
+
+    let elapsed = start.elapsed();
+    Ok(WipeReport {
+        mode,
+        passes: pass_durations.len() as u32,
+        bytes_written: written_total,
+        pass_durations,
+        elapsed,
+        average_mib_s: written_total as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64().max(1e-6),
+        buffer_size: buf.len(),
+    })
+}
+
+/// Compare bytes handed to the device against what the passes should have written.
+fn check_written(expected: u64, actual: u64) -> io::Result<()> {
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            format!("wrote {} bytes but {} were expected", actual, expected),
+        ));
+    }
+
+    say(format!(
+        "[+] Bytes written: {} ({}), as expected.",
+        actual,
+        size_format(actual)
+    ));
+    Ok(())
+}
+
+/// Allocate the chunk buffer, halving the size (down to MIN_BUFFER) if memory is short.
+/// It is page-aligned so it also works on handles opened with --no-buffering.
+fn alloc_buffer(size: usize) -> io::Result<AlignedBuf> {
+    let mut size = size;
+    loop {
+        match AlignedBuf::new(size, IO_ALIGN) {
+            Ok(buf) => return Ok(buf),
+            Err(_) if size / 2 >= MIN_BUFFER => {
+                say(format!(
+                    "[!] Could not allocate a {} buffer; trying {}.",
+                    size_format(size as u64),
+                    size_format((size / 2) as u64)
+                ));
+                size /= 2;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Errors a smaller write might get past: out of memory or driver/device buffer limits.
+fn is_buffer_limit_error(e: &io::Error) -> bool {
+    // ERROR_NOT_ENOUGH_MEMORY, ERROR_OUTOFMEMORY, ERROR_NO_SYSTEM_RESOURCES,
+    // ERROR_WORKING_SET_QUOTA
+    e.kind() == io::ErrorKind::OutOfMemory || matches!(e.raw_os_error(), Some(8 | 14 | 1450 | 1453))
+}
+
+/// Pass and bytes already written in it, from a saved --checkpoint-file.
+fn resume_point(opts: &WipeOptions, size: u64, passes: u32) -> io::Result<(u32, u64)> {
+    let cp = match &opts.checkpoint {
+        Some(cp) => cp,
+        None => return Ok((1, 0)),
+    };
+
+    match cp.load()? {
+        Some((pass, offset))
+            if pass >= 1
+                && pass <= passes
+                && offset >= opts.offset
+                && offset <= opts.offset + size =>
+        {
+            say(format!(
+                "[*] Resuming from checkpoint: pass {}/{} at offset {}",
+                pass, passes, offset
+            ));
+            Ok((pass, offset - opts.offset))
         }
-        // --------------------------------------------------------------
+        Some((pass, offset)) => {
+            say(format!(
+                "Warning: checkpoint (pass {} offset {}) does not fit this wipe; starting over.",
+                pass, offset
+            ));
+            Ok((1, 0))
+        }
+        None => Ok((1, 0)),
+    }
+}
+
+/// Seed for one pass, so a single base seed reproduces the whole run.
+pub fn pass_seed(base: u64, pass: u32) -> u64 {
+    base.wrapping_add(pass as u64)
+}
+
+/// Generator of a seeded pass. ChaCha12 is what `StdRng` is in rand 0.8;
+/// naming it keeps the stream fixed and lets a rewrite seek into it.
+fn seeded_rng(base: u64, pass: u32) -> ChaCha12Rng {
+    ChaCha12Rng::seed_from_u64(pass_seed(base, pass))
+}
+
+/// Random source for a pass: seeded when --seed was given, otherwise the OS-seeded thread RNG.
+fn pass_rng(seed: Option<u64>, pass: u32) -> Box<dyn RngCore> {
+    match seed {
+        Some(base) => Box::new(seeded_rng(base, pass)),
+        None => Box::new(rand::thread_rng()),
+    }
+}
 
-        // NEW: throttle progress output
-        let mut last_print = Instant::now();
+/// MB/s (MiB, like --rate) of random data the generator a pass would use
+/// can produce here, filling `buf_len`-byte chunks for about a second.
+pub fn measure_rng_rate(seeded: bool, buf_len: usize) -> f64 {
+    let mut rng = pass_rng(seeded.then_some(0), 1);
+    let mut buf = vec![0u8; buf_len.max(1)];
+
+    let start = Instant::now();
+    let mut made: u64 = 0;
+    while made == 0 || start.elapsed() < RNG_PROBE {
+        rng.fill_bytes(&mut buf);
+        made += buf.len() as u64;
+    }
+
+    made as f64 / start.elapsed().as_secs_f64() / (1024.0 * 1024.0)
+}
 
-        while written < size {
-            let left = size - written;
-            let to_write = if left < CHUNK as u64 {
-                left as usize
+/// Seconds for every pass over `size` bytes: fixed patterns at `io_rate`,
+/// random passes at the slower of `io_rate` and `rng_rate`, and read-back
+/// passes at half `io_rate` since they read the target as well.
+pub fn estimate_wipe_seconds(
+    size: u64,
+    mode: WipeMode,
+    passes: u32,
+    io_rate: f64,
+    rng_rate: f64,
+) -> u64 {
+    let secs: f64 = (1..=effective_passes(mode, passes))
+        .map(|pass| {
+            let rate = if is_readback_pass(mode, pass) {
+                io_rate / 2.0
+            } else if is_random_pass(mode, pass) {
+                io_rate.min(rng_rate)
             } else {
-                CHUNK
+                io_rate
             };
+            size as f64 / (rate * 1024.0 * 1024.0)
+        })
+        .sum();
+    secs.ceil() as u64
+}
+
+/// Write one full pass over the target; returns the bytes written by this call.
+fn write_pass<T: WipeTarget>(
+    file: &mut T,
+    size: u64,
+    opts: &WipeOptions,
+    pass: u32,
+    resume_at: u64,
+    buf: &mut [u8],
+    sink: &mut dyn ProgressSink,
+) -> io::Result<u64> {
+    let mode = opts.mode;
+    let passes = effective_passes(mode, opts.passes);
+    let readback = is_readback_pass(mode, pass);
+    let random = is_random_pass(mode, pass);
+    let nonce_block = match mode {
+        WipeMode::Nonce => opts.nonce.map(nonce_keystream),
+        _ => None,
+    };
+    let sequence = pass_sequence(mode, pass)
+        .filter(|seq| seq.len() > 1)
+        .or(nonce_block.as_deref());
+    let complement = is_complement_pass(mode, pass);
+    // a complement pass regenerates the previous pass's stream and inverts it
+    let stream_pass = if complement { pass - 1 } else { pass };
+    let mut rng = pass_rng(opts.seed, stream_pass);
+
+    // --compact keeps every pass on the one progress line
+    let banners = !is_compact();
+    if banners {
+        say("");
+        say(format!(
+            "=== Starting pass {}/{} ({}) ===",
+            pass,
+            passes,
+            describe_pass(mode, pass)
+        ));
+    }
+
+    // a seeded stream has to be replayed up to the resume point
+    if opts.seed.is_some() && random {
+        let mut skipped = 0;
+        while skipped < resume_at {
+            let n = (resume_at - skipped).min(buf.len() as u64) as usize;
+            rng.fill_bytes(&mut buf[..n]);
+            skipped += n as u64;
+        }
+    }
+
+    file.seek(SeekFrom::Start(opts.offset + resume_at))?;
+    let mut written: u64 = resume_at;
+    let mut last_sync: u64 = resume_at;
+
+    // ---- pre-fill buffer ONCE per pass when pattern is fixed ----
+    if let Some(byte) = pass_pattern(mode, pass) {
+        buf.fill(byte);
+    }
+    // --------------------------------------------------------------
+
+    let mut tracker = ProgressTracker::with_eta_window(opts.eta_window);
+    let mut bad_sectors: Vec<u64> = Vec::new();
+    let start = Instant::now();
+    let mut window = RateWindow::new(THROUGHPUT_WINDOW_SECS);
+    let mut last_checkpoint = Instant::now();
+    let mut last_save = Instant::now();
+
+    // where the stream should end up: the range end, or its start in reverse
+    let mut chunk_end = opts.offset + size;
+
+    while written < size {
+        if is_interrupted() {
+            file.sync_data()?;
+            if let Some(cp) = &opts.checkpoint {
+                cp.save(pass, opts.offset + written)?;
+            }
+            say("");
+            say(format!(
+                "[!] Interrupted: pass {}/{} stopped after {} of {}, flushed to disk.",
+                pass,
+                passes,
+                size_format(written),
+                size_format(size)
+            ));
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "wipe interrupted by Ctrl+C",
+            ));
+        }
+
+        let to_write = chunk_len(size - written, buf.len());
+        let at = if opts.reverse {
+            opts.offset + size - written - to_write as u64
+        } else {
+            opts.offset + written
+        };
+        if opts.reverse {
+            file.seek(SeekFrom::Start(at))?;
+            chunk_end = at + to_write as u64;
+        }
+
+        // multi-byte patterns follow the chunk's position in the range
+        if let Some(seq) = sequence {
+            let phase = ((at - opts.offset) % seq.len() as u64) as usize;
+            fill_sequence(&mut buf[..to_write], seq, phase);
+        }
 
-            // For Random mode, we still need fresh random data per chunk
-            if matches!(mode, WipeMode::Random) {
-                rng.fill_bytes(&mut buf[..to_write]);
+        // For random passes, we still need fresh random data per chunk
+        if random {
+            rng.fill_bytes(&mut buf[..to_write]);
+            if complement {
+                buf[..to_write].iter_mut().for_each(|b| *b = !*b);
             }
+        }
 
-            // write the chunk
-            file.write_all(&buf[..to_write])?;
-            written += to_write as u64;
+        // invert whatever the previous pass left on the media, then write it back
+        if readback {
+            file.seek(SeekFrom::Start(at))?;
+            file.read_exact(&mut buf[..to_write])?;
+            buf[..to_write].iter_mut().for_each(|b| *b = !*b);
+            file.seek(SeekFrom::Start(at))?;
+        }
+
+        // write the chunk
+        let bad = write_chunk(file, &buf[..to_write], opts.io_size, opts.sector_size)?;
+        written += to_write as u64;
+        bad_sectors.extend(bad);
 
-            // Only update progress every ~200ms or on completion
-            if last_print.elapsed().as_millis() >= 200 || written == size {
-                let elapsed = start.elapsed();
-                let secs = elapsed.as_secs_f64().max(0.000_001);
+        // periodic FlushFileBuffers so a crash loses less of the pass
+        if let Some(every) = opts.sync_every {
+            if written - last_sync >= every && written < size {
+                file.sync_data()?;
+                last_sync = written;
+            }
+        }
 
-                let percent = (written as f64 / size as f64) * 100.0;
-                let written_mib = written as f64 / (1024.0 * 1024.0);
-                let speed_mib_s = written_mib / secs;
+        if let Some(min) = opts.min_throughput {
+            let secs = start.elapsed().as_secs_f64();
+            window.push(secs, written);
+            check_throughput(&window, secs, opts.throughput_grace, min)?;
+        }
 
-                let remain_bytes = size - written;
-                let eta_secs = if speed_mib_s > 0.0 {
-                    (remain_bytes as f64 / (1024.0 * 1024.0) / speed_mib_s)
-                        .max(0.0) as u64
+        if let Some(every) = opts.checkpoint_every {
+            if last_checkpoint.elapsed() >= every && written < size {
+                say("");
+                let pos = if opts.reverse {
+                    at
                 } else {
-                    0
+                    opts.offset + written
                 };
-                let eta_str = format_eta(eta_secs);
+                say(checkpoint_line(unix_now(), pass, passes, pos));
+                last_checkpoint = Instant::now();
+            }
+        }
 
-                print!(
-                    "\rPass {}/{}:  {:6.2}%  |  {:7.2} MB/s  |   ETA {}",
-                    pass, passes, percent, speed_mib_s, eta_str
-                );
-                stdout().flush().ok();
-                last_print = Instant::now();
+        if let Some(cp) = &opts.checkpoint {
+            if last_save.elapsed() >= cp.save_every && written < size {
+                // the saved offset must already be on the media
+                file.sync_data()?;
+                cp.save(pass, opts.offset + written)?;
+                last_save = Instant::now();
             }
         }
 
-        file.flush()?;
-        println!();
-        println!("=== Finished pass {}/{} ===", pass, passes);
+        tracker.update(sink, "Pass", pass, passes, written, size);
+    }
+
+    file.flush()?;
+
+    let end = file.stream_position()?;
+    if end != chunk_end {
+        say("");
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            format!(
+                "pass {} ended at offset {} but should have reached {}",
+                pass, end, chunk_end
+            ),
+        ));
+    }
+
+    if banners || pass == passes {
+        say("");
+    }
+    if opts.checkpoint_every.is_some() {
+        let done_at = if opts.reverse {
+            opts.offset
+        } else {
+            opts.offset + written
+        };
+        say(checkpoint_line(unix_now(), pass, passes, done_at));
+    }
+    if banners {
+        say(format!("=== Finished pass {}/{} ===", pass, passes));
+    }
+
+    // unwritten sectors are left out of the count so the pass total check fails
+    let skipped = bad_sectors.len() as u64 * opts.sector_size as u64;
+    if let Some(first) = bad_sectors.first() {
+        say(format!(
+            "[!] Pass {}/{}: {} sector(s) could not be overwritten (first at offset {}).",
+            pass,
+            passes,
+            bad_sectors.len(),
+            first
+        ));
+    }
+
+    Ok((written - resume_at).saturating_sub(skipped))
+}
+
+/// Bytes in the next chunk: a full `chunk`, or whatever is `left` at the tail.
+/// Write and read-back loops share this so they always agree on the last chunk.
+fn chunk_len(left: u64, chunk: usize) -> usize {
+    left.min(chunk as u64) as usize
+}
+
+/// Hand one buffer to the device, in --io-size pieces when that is set.
+/// Some devices reject a large aligned write with ERROR_INVALID_PARAMETER;
+/// that chunk is retried one sector at a time. Returns the offsets of
+/// sectors that still could not be written.
+fn write_chunk<W: Write + Seek>(
+    file: &mut W,
+    data: &[u8],
+    io_size: Option<usize>,
+    sector_size: usize,
+) -> io::Result<Vec<u64>> {
+    let start = file.stream_position()?;
+    let res = match io_size {
+        Some(n) => data.chunks(n).try_for_each(|piece| file.write_all(piece)),
+        None => file.write_all(data),
+    };
+
+    match res {
+        Ok(()) => Ok(Vec::new()),
+        Err(e) if e.raw_os_error() == Some(ERROR_INVALID_PARAMETER) && data.len() > sector_size => {
+            say("");
+            say(format!(
+                "[!] {} write at offset {} was rejected ({}); retrying sector by sector.",
+                size_format(data.len() as u64),
+                start,
+                e
+            ));
+            write_sectors(file, start, data, sector_size)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Write `data` at `start` one sector per call, skipping sectors the device refuses.
+fn write_sectors<W: Write + Seek>(
+    file: &mut W,
+    start: u64,
+    data: &[u8],
+    sector_size: usize,
+) -> io::Result<Vec<u64>> {
+    let mut bad = Vec::new();
+    for (i, sector) in data.chunks(sector_size).enumerate() {
+        let at = start + (i * sector_size) as u64;
+        file.seek(SeekFrom::Start(at))?;
+        if let Err(e) = file.write_all(sector) {
+            say(format!(
+                "[!] Sector at offset {} could not be written: {}",
+                at, e
+            ));
+            bad.push(at);
+        }
+    }
+
+    // leave the position where the whole chunk would have
+    file.seek(SeekFrom::Start(start + data.len() as u64))?;
+    Ok(bad)
+}
+
+/// Apply the hidden --limit-bytes test cap, kept sector aligned.
+/// Returns the (possibly shorter) length to write and whether it was capped.
+pub fn limit_len(len: u64, limit: Option<u64>, sector_size: u64) -> (u64, bool) {
+    match limit {
+        Some(l) if l < len => (l - l % sector_size, true),
+        _ => (len, false),
+    }
+}
+
+/// Loud reminder that a --limit-bytes run is a test, not a wipe.
+pub fn print_limit_warning(written: u64, target: &str) {
+    say("");
+    say(format!(
+        "!!! --limit-bytes: only the first {} of {} will be written.",
+        size_format(written),
+        target
+    ));
+    say("!!! THIS DOES NOT WIPE THE TARGET. Use it for testing only.");
+    say("");
+}
+
+/// Position in a pass's generator stream of the byte `rel` bytes into the
+/// range. A reverse pass draws from the stream chunk by chunk from the end.
+fn stream_offset(opts: &WipeOptions, size: u64, rel: u64) -> u64 {
+    if !opts.reverse {
+        return rel;
+    }
+    let chunk = opts.buffer_size as u64;
+    let k = (size - rel - 1) / chunk;
+    let chunk_start = size.saturating_sub((k + 1) * chunk);
+    k * chunk + (rel - chunk_start)
+}
+
+/// Regenerate into `buf` what `pass` wrote at offset `at` of a range of
+/// `size` bytes. False when that can't be done: unseeded random data, or a
+/// read-back complement.
+fn pass_bytes(opts: &WipeOptions, size: u64, pass: u32, at: u64, buf: &mut [u8]) -> bool {
+    let mode = opts.mode;
+    let rel = at - opts.offset;
+
+    if let Some(b) = pass_pattern(mode, pass) {
+        buf.fill(b);
+        return true;
+    }
+
+    let nonce_block = match mode {
+        WipeMode::Nonce => opts.nonce.map(nonce_keystream),
+        _ => None,
+    };
+    if let Some(seq) = pass_sequence(mode, pass).or(nonce_block.as_deref()) {
+        fill_sequence(buf, seq, (rel % seq.len() as u64) as usize);
+        return true;
+    }
+
+    match opts.seed {
+        Some(base) if is_random_pass(mode, pass) => {
+            let complement = is_complement_pass(mode, pass);
+            let mut rng = seeded_rng(base, if complement { pass - 1 } else { pass });
+            let chunk = opts.buffer_size as u64;
+            let mut done = 0;
+            while done < buf.len() {
+                let r = rel + done as u64;
+                // a reverse pass jumps in the stream at every chunk boundary
+                let left = if opts.reverse {
+                    ((size - r - 1) % chunk + 1) as usize
+                } else {
+                    buf.len() - done
+                };
+                let n = left.min(buf.len() - done);
+                // chunks are whole sectors, so every chunk starts on a word
+                rng.set_word_pos((stream_offset(opts, size, r) / 4) as u128);
+                rng.fill_bytes(&mut buf[done..done + n]);
+                done += n;
+            }
+            if complement {
+                buf.iter_mut().for_each(|b| *b = !*b);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Rewrite the last `tail` bytes before `end` (the end of the wiped range)
+/// with exactly what the final pass wrote there.
+pub fn overwrite_tail(file: &mut File, end: u64, tail: u64, opts: &WipeOptions) -> io::Result<()> {
+    let tail = tail.min(end - opts.offset);
+    let last_pass = effective_passes(opts.mode, opts.passes);
+
+    let mut buf = AlignedBuf::new(tail as usize, IO_ALIGN)?;
+    if !pass_bytes(opts, end - opts.offset, last_pass, end - tail, &mut buf) {
+        if is_readback_pass(opts.mode, last_pass) {
+            // the media already holds the complement; write back exactly that
+            file.seek(SeekFrom::Start(end - tail))?;
+            file.read_exact(&mut buf)?;
+        } else {
+            // unseeded random: fresh random bytes are as good as the pass's own
+            rand::thread_rng().fill_bytes(&mut buf);
+        }
+    }
+
+    file.seek(SeekFrom::Start(end - tail))?;
+    file.write_all(&buf)?;
+    file.flush()?;
+
+    if file.stream_position()? != end {
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            format!("tail overwrite did not reach the end of the target ({} bytes)", end),
+        ));
+    }
+
+    say(format!(
+        "[+] Last {} bytes (up to offset {}) overwritten again.",
+        tail, end
+    ));
+
+    Ok(())
+}
+
+/// One --checkpoint-every line: "<UTC time> pass=1/3 offset=12884901888".
+fn checkpoint_line(unix_secs: u64, pass: u32, passes: u32, offset: u64) -> String {
+    format!(
+        "{} pass={}/{} offset={}",
+        format_utc(unix_secs),
+        pass,
+        passes,
+        offset
+    )
+}
+
+/// Fail with "drive too slow" once the sustained rate drops below `min` MB/s.
+fn check_throughput(window: &RateWindow, secs: f64, grace: Duration, min: f64) -> io::Result<()> {
+    if secs < grace.as_secs_f64() {
+        return Ok(());
+    }
+
+    match window.rate_mib_s() {
+        Some(rate) if rate < min => {
+            say("");
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Drive too slow, likely failing: {:.2} MB/s sustained over the last {}s (minimum {:.2} MB/s)",
+                    rate, THROUGHPUT_WINDOW_SECS, min
+                ),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Turn --offset / --length into a byte (offset, length) inside `total` bytes.
+pub fn resolve_range(
+    total: u64,
+    offset: Option<u64>,
+    length: Option<u64>,
+    units: Units,
+    sector_size: u64,
+) -> io::Result<(u64, u64)> {
+    let scale = match units {
+        Units::Bytes => 1,
+        Units::Sectors => sector_size,
+    };
+
+    let to_bytes = |v: u64, what: &str| {
+        v.checked_mul(scale).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("--{} is too large", what))
+        })
+    };
+
+    let start = to_bytes(offset.unwrap_or(0), "offset")?;
+    if start >= total {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--offset {} is past the end of the target ({} bytes)", start, total),
+        ));
+    }
+
+    let len = match length {
+        Some(l) => to_bytes(l, "length")?,
+        None => total - start,
+    };
+    if len == 0 || len > total - start {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--length {} does not fit: {} bytes available after offset {}",
+                len,
+                total - start,
+                start
+            ),
+        ));
+    }
+
+    Ok((start, len))
+}
+
+/// Raw devices only accept sector-aligned I/O.
+pub fn check_aligned(start: u64, len: u64, sector_size: u64) -> io::Result<()> {
+    if start % sector_size != 0 || len % sector_size != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "range {}+{} is not aligned to the {}-byte sector size",
+                start, len, sector_size
+            ),
+        ));
     }
+    Ok(())
+}
 
+/// --io-size must be a whole number of sectors and no larger than --buffer-size.
+pub fn check_io_size(io_size: u64, buffer: u64, sector_size: u64) -> io::Result<()> {
+    if io_size == 0 || io_size % sector_size != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--io-size {} is not a multiple of the {}-byte sector size",
+                io_size, sector_size
+            ),
+        ));
+    }
+    if io_size > buffer {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--io-size {} is larger than --buffer-size {}",
+                size_format(io_size),
+                size_format(buffer)
+            ),
+        ));
+    }
     Ok(())
 }
+
+/// --strict-sector-size: refuse a raw-device wipe unless --buffer-size,
+/// --io-size, --offset and --length are all whole `sector_size` sectors,
+/// naming every value that is not. Offsets given in sectors always are.
+pub fn check_strict_sector_size(
+    buffer: u64,
+    io_size: Option<u64>,
+    offset: Option<u64>,
+    length: Option<u64>,
+    units: Units,
+    sector_size: u64,
+) -> io::Result<()> {
+    let in_bytes = matches!(units, Units::Bytes);
+    let sizes = [
+        ("--buffer-size", Some(buffer)),
+        ("--io-size", io_size),
+        ("--offset", offset.filter(|_| in_bytes)),
+        ("--length", length.filter(|_| in_bytes)),
+    ];
+    let bad: Vec<String> = sizes
+        .iter()
+        .filter_map(|(name, n)| n.filter(|n| n % sector_size != 0).map(|n| format!("{name} {n}")))
+        .collect();
+    if bad.is_empty() {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "--strict-sector-size: detected sector size is {} bytes, not a multiple of it: {}",
+            sector_size,
+            bad.join(", ")
+        ),
+    ))
+}
+
+/// Refuse buffers that would eat too much of the free RAM; the default is always fine.
+pub fn check_buffer_size(buffer: u64, free_ram: u64) -> io::Result<()> {
+    if buffer == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--buffer-size must be greater than 0",
+        ));
+    }
+
+    if buffer <= DEFAULT_BUFFER as u64 || buffer <= free_ram / MAX_BUFFER_RAM_FRACTION {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::OutOfMemory,
+        format!(
+            "--buffer-size {} is too large: only {} of RAM is free (limit is 1/{} of it, {}). Try a smaller size such as 64M.",
+            size_format(buffer),
+            size_format(free_ram),
+            MAX_BUFFER_RAM_FRACTION,
+            size_format(free_ram / MAX_BUFFER_RAM_FRACTION)
+        ),
+    ))
+}
+
+/// Fewest passes a mode may run. The CLI and the engine both go through
+/// `effective_passes`, so they can't disagree.
+pub fn min_passes(mode: WipeMode) -> u32 {
+    match mode {
+        WipeMode::Secureflip | WipeMode::RandomComplement | WipeMode::RandomInvert => 2,
+        WipeMode::Dod522022M => 3,
+        WipeMode::Gutmann => 35,
+        _ => 1,
+    }
+}
+
+/// Round a write size down to whole physical sectors (at least one), so a
+/// 512e drive never has to read-modify-write a partly written 4K sector.
+pub fn align_to_physical(len: usize, physical: usize) -> usize {
+    if physical == 0 {
+        return len;
+    }
+    (len / physical).max(1) * physical
+}
+
+/// Pass count of a mode defined by a standard, which --passes can't change.
+pub fn fixed_passes(mode: WipeMode) -> Option<u32> {
+    match mode {
+        WipeMode::Dod522022M => Some(3),
+        WipeMode::Gutmann => Some(35),
+        _ => None,
+    }
+}
+
+/// Pass count actually run: the mode's fixed count if it has one, otherwise
+/// the requested count raised to the mode's minimum.
+pub fn effective_passes(mode: WipeMode, passes: u32) -> u32 {
+    fixed_passes(mode).unwrap_or_else(|| passes.max(min_passes(mode)))
+}
+
+/// Why `effective_passes` differs from the requested count, for the Note printed then.
+pub fn pass_count_reason(mode: WipeMode) -> String {
+    match fixed_passes(mode) {
+        Some(n) => format!("always runs exactly {} passes", n),
+        None => format!("needs at least {} passes", min_passes(mode)),
+    }
+}
+
+/// Human readable pattern for a pass, e.g. "0x00" or "random".
+pub fn describe_pass(mode: WipeMode, pass: u32) -> String {
+    if let Some(seq) = pass_sequence(mode, pass).filter(|seq| seq.len() > 1) {
+        let bytes: Vec<String> = seq.iter().map(|b| format!("0x{:02X}", b)).collect();
+        return format!("{} repeating", bytes.join(" "));
+    }
+
+    match pass_pattern(mode, pass) {
+        Some(b) => format!("0x{:02X}", b),
+        None if is_complement_pass(mode, pass) => format!("complement of pass {}", pass - 1),
+        None if is_readback_pass(mode, pass) => {
+            format!("read-back complement of pass {}", pass - 1)
+        }
+        None if matches!(mode, WipeMode::Nonce) => "nonce keystream".to_string(),
+        None => "random".to_string(),
+    }
+}
+
+/// True for the even passes of RandomComplement, which invert the pass before.
+pub fn is_complement_pass(mode: WipeMode, pass: u32) -> bool {
+    matches!(mode, WipeMode::RandomComplement) && pass % 2 == 0
+}
+
+/// True for the even passes of RandomInvert, which read each chunk back and
+/// write its complement, so they cost a full read on top of the write.
+pub fn is_readback_pass(mode: WipeMode, pass: u32) -> bool {
+    matches!(mode, WipeMode::RandomInvert) && pass % 2 == 0
+}
+
+/// Fixed byte written by the given pass, or None for random data.
+pub fn pass_pattern(mode: WipeMode, pass: u32) -> Option<u8> {
+    match mode {
+        WipeMode::Secureflip => {
+            // odd pass -> zeros, even pass -> ones
+            if pass % 2 == 1 {
+                Some(0x00)
+            } else {
+                Some(0xFF)
+            }
+        }
+        WipeMode::Zeros => Some(0x00),
+        WipeMode::Dod522022M => match pass {
+            1 => Some(0x00),
+            2 => Some(0xFF),
+            _ => None,
+        },
+        // the single-byte Gutmann passes; see `pass_sequence` for the rest
+        WipeMode::Gutmann => match pass_sequence(mode, pass) {
+            Some([b]) => Some(*b),
+            _ => None,
+        },
+        WipeMode::Random | WipeMode::RandomComplement | WipeMode::RandomInvert => None,
+        WipeMode::Nonce => None,
+    }
+}
+
+/// Repeating byte sequence written by a pass of a mode with multi-byte
+/// patterns (Gutmann), or None. The sequence restarts at the range offset.
+pub fn pass_sequence(mode: WipeMode, pass: u32) -> Option<&'static [u8]> {
+    match (mode, pass) {
+        (WipeMode::Gutmann, 5..=31) => Some(GUTMANN_FIXED[(pass - 5) as usize]),
+        _ => None,
+    }
+}
+
+/// True for passes that write generator output (complement passes included).
+pub fn is_random_pass(mode: WipeMode, pass: u32) -> bool {
+    pass_pattern(mode, pass).is_none()
+        && pass_sequence(mode, pass).is_none()
+        && !is_readback_pass(mode, pass)
+        && !matches!(mode, WipeMode::Nonce)
+}
+
+/// Length of the block the `Nonce` mode repeats over the range.
+pub const NONCE_BLOCK: usize = 4096;
+
+/// The `Nonce` mode block: SHA-256(nonce || counter) for counter 0, 1, ...
+/// (both little-endian u64) concatenated. SHA-256 rather than the RNG so the
+/// bytes never change between builds and can be recomputed from the report.
+pub fn nonce_keystream(nonce: u64) -> Vec<u8> {
+    let mut block = Vec::with_capacity(NONCE_BLOCK);
+    let mut counter: u64 = 0;
+    while block.len() < NONCE_BLOCK {
+        let mut hasher = Sha256::new();
+        hasher.update(nonce.to_le_bytes());
+        hasher.update(counter.to_le_bytes());
+        block.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    block
+}
+
+/// Fill `buf` with `seq` repeated, starting `phase` bytes into it, so a
+/// pattern stays continuous across chunks of any size.
+fn fill_sequence(buf: &mut [u8], seq: &[u8], phase: usize) {
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = seq[(phase + i) % seq.len()];
+    }
+}
+
+/// Read the target back and check the final pass pattern is on disk.
+/// Returns the SHA-256 (hex) of everything read, computed in the same pass.
+pub fn verify_wipe(
+    file: &mut File,
+    size: u64,
+    opts: &WipeOptions,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<String> {
+    let last_pass = effective_passes(opts.mode, opts.passes);
+    let hash = match pass_pattern(opts.mode, last_pass) {
+        Some(b) => verify_pattern(file, opts.offset, size, opts.buffer_size, b, sink)?,
+        None => verify_stream(file, size, opts, sink)?,
+    };
+    say("=== Verification passed ===");
+    say(format!("Post-wipe SHA-256 : {}", hash));
+
+    Ok(hash)
+}
+
+/// Mismatching byte ranges listed by --pattern-verify before it stops listing.
+const MAX_REPORTED_RANGES: usize = 32;
+
+/// Outcome of a full --pattern-verify read.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatternReport {
+    /// Bytes that differ from the expected pattern
+    pub mismatches: u64,
+    /// First few [start, end) byte ranges of mismatches, absolute offsets
+    pub ranges: Vec<(u64, u64)>,
+}
+
+/// --pattern-verify: read the whole range and count every byte that differs
+/// from what the final pass wrote, instead of stopping at the first one.
+pub fn pattern_verify<R: Read + Seek>(
+    file: &mut R,
+    size: u64,
+    opts: &WipeOptions,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<()> {
+    let last_pass = effective_passes(opts.mode, opts.passes);
+    if !can_regenerate(opts, last_pass) {
+        say("Note: --pattern-verify skipped, unseeded random data can't be regenerated.");
+        return Ok(());
+    }
+    let want = expected_name(opts.mode, last_pass);
+
+    say("");
+    say(format!(
+        "=== Pattern verify (every byte must match {}) ===",
+        want
+    ));
+
+    let report = scan_pattern(file, size, opts, last_pass, sink)?;
+    if report.mismatches == 0 {
+        say(format!("[+] All {} bytes match {}.", size, want));
+        return Ok(());
+    }
+
+    for (start, end) in &report.ranges {
+        say(format!(
+            "  bad bytes {}..{} ({} bytes)",
+            start,
+            end,
+            end - start
+        ));
+    }
+    if report.ranges.len() == MAX_REPORTED_RANGES {
+        say("  ... (further ranges not listed)");
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} of {} bytes do not match {}",
+            report.mismatches, size, want
+        ),
+    ))
+}
+
+/// True if `pass_bytes` can regenerate what `pass` wrote: a fixed byte or
+/// sequence, the Nonce keystream, or a seeded random pass.
+pub fn can_regenerate(opts: &WipeOptions, pass: u32) -> bool {
+    let mode = opts.mode;
+    pass_pattern(mode, pass).is_some()
+        || pass_sequence(mode, pass).is_some()
+        || (matches!(mode, WipeMode::Nonce) && opts.nonce.is_some())
+        || (opts.seed.is_some() && is_random_pass(mode, pass))
+}
+
+/// What a pass is checked against, for verify messages: "0x00", or the
+/// pass's description for data that has to be regenerated.
+fn expected_name(mode: WipeMode, pass: u32) -> String {
+    match pass_pattern(mode, pass) {
+        Some(b) => format!("0x{:02X}", b),
+        None => format!("pass {} ({})", pass, describe_pass(mode, pass)),
+    }
+}
+
+/// Stream the range in `opts.buffer_size` reads and collect every byte that
+/// differs from what `pass` wrote there.
+fn scan_pattern<R: Read + Seek>(
+    file: &mut R,
+    size: u64,
+    opts: &WipeOptions,
+    pass: u32,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<PatternReport> {
+    let chunk = opts.buffer_size;
+    file.seek(SeekFrom::Start(opts.offset))?;
+    let mut buf = AlignedBuf::new(chunk, IO_ALIGN)?;
+    let mut want = vec![0u8; chunk];
+    let mut checked: u64 = 0;
+    let mut tracker = ProgressTracker::new();
+    let mut report = PatternReport::default();
+
+    while checked < size {
+        check_interrupted()?;
+        let to_read = chunk_len(size - checked, chunk);
+        let start = opts.offset + checked;
+        file.read_exact(&mut buf[..to_read])?;
+        pass_bytes(opts, size, pass, start, &mut want[..to_read]);
+
+        for (i, (&b, &w)) in buf[..to_read].iter().zip(&want[..to_read]).enumerate() {
+            if b == w {
+                continue;
+            }
+            let at = start + i as u64;
+            report.mismatches += 1;
+
+            // extend the last range if this byte continues it, even across chunks
+            if let Some(last) = report.ranges.last_mut().filter(|r| r.1 == at) {
+                last.1 = at + 1;
+            } else if report.ranges.len() < MAX_REPORTED_RANGES {
+                report.ranges.push((at, at + 1));
+            }
+        }
+
+        checked += to_read as u64;
+        tracker.update(sink, "Verify", 1, 1, checked, size);
+    }
+    say("");
+
+    Ok(report)
+}
+
+/// Probe positions: sector 0, every power-of-two offset below `size` (where
+/// fake-capacity drives wrap around) and the last sector.
+fn probe_offsets(size: u64, sector_size: u64) -> Vec<u64> {
+    let last = (size - size % sector_size).saturating_sub(sector_size);
+    let mut offsets = vec![0];
+    let mut at = sector_size;
+    while at < last {
+        offsets.push(at);
+        at *= 2;
+    }
+    if last > 0 {
+        offsets.push(last);
+    }
+    offsets
+}
+
+/// Sector tagged with its own offset and a per-run nonce.
+fn probe_sector(offset: u64, nonce: u64, sector_size: usize) -> Vec<u8> {
+    let mut sector = vec![0u8; sector_size];
+    let text = format!("WIPECORE PROBE {:016x} {:016x}", nonce, offset);
+    let n = text.len().min(sector_size);
+    sector[..n].copy_from_slice(&text.as_bytes()[..n]);
+    sector
+}
+
+/// Write a tagged sector at each probe offset, re-reading it and every earlier
+/// probe after each write. Returns the first offset whose write didn't land
+/// where it should, or None if the whole address space held.
+fn first_unaddressable<T: WipeTarget>(
+    dev: &mut T,
+    offsets: &[u64],
+    sector_size: u64,
+) -> io::Result<Option<u64>> {
+    let nonce = rand::random::<u64>();
+    let mut buf = AlignedBuf::new(sector_size as usize, IO_ALIGN)?;
+
+    for (i, &off) in offsets.iter().enumerate() {
+        let sector = AlignedBuf::copy_of(&probe_sector(off, nonce, sector_size as usize))?;
+        dev.seek(SeekFrom::Start(off))?;
+        if dev.write_all(&sector).is_err() {
+            return Ok(Some(off));
+        }
+        dev.flush()?;
+        dev.sync_data()?;
+
+        for &earlier in &offsets[..=i] {
+            dev.seek(SeekFrom::Start(earlier))?;
+            let intact = dev.read_exact(&mut buf).is_ok()
+                && buf[..] == probe_sector(earlier, nonce, sector_size as usize)[..];
+            if !intact {
+                return Ok(Some(off));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// --probe-addressing: fail before the wipe if the device can't really store
+/// data out to its last sector (fake-capacity USB drives wrap around).
+pub fn probe_addressing<T: WipeTarget>(dev: &mut T, size: u64, sector_size: u64) -> io::Result<()> {
+    let offsets = probe_offsets(size, sector_size);
+    say("");
+    say(format!(
+        "[*] Probing {} sectors up to the last one...",
+        offsets.len()
+    ));
+
+    match first_unaddressable(dev, &offsets, sector_size)? {
+        None => {
+            say(format!(
+                "[+] All {} probe sectors, including the last, are addressable.",
+                offsets.len()
+            ));
+            Ok(())
+        }
+        Some(bad) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "device reports {} but data written at offset {} does not stay there; \
+                 the real capacity is at most {} (fake capacity or defective drive)",
+                size_format(size),
+                bad,
+                size_format(bad)
+            ),
+        )),
+    }
+}
+
+/// Spot-check `count` sectors of the final pass (--verify-samples).
+pub fn verify_samples<R: Read + Seek>(
+    file: &mut R,
+    size: u64,
+    opts: &WipeOptions,
+    count: u64,
+    sector_size: u64,
+) -> io::Result<()> {
+    let last_pass = effective_passes(opts.mode, opts.passes);
+    if !can_regenerate(opts, last_pass) {
+        say("Note: --verify-samples skipped, unseeded random data can't be regenerated.");
+        return Ok(());
+    }
+    let want_name = expected_name(opts.mode, last_pass);
+
+    let offsets = stratified_offsets(size, count, sector_size, opts.seed);
+    let mut buf = AlignedBuf::new(sector_size as usize, IO_ALIGN)?;
+    let mut want = vec![0u8; sector_size as usize];
+    let mut bad = Vec::new();
+
+    for off in &offsets {
+        check_interrupted()?;
+        let at = opts.offset + off;
+        let n = (size - off).min(sector_size) as usize;
+        file.seek(SeekFrom::Start(at))?;
+        file.read_exact(&mut buf[..n])?;
+        pass_bytes(opts, size, last_pass, at, &mut want[..n]);
+        if buf[..n] != want[..n] {
+            bad.push(at);
+        }
+    }
+
+    if let Some(first) = bad.first() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} of {} sampled sectors do not match {} (first at offset {})",
+                bad.len(),
+                offsets.len(),
+                want_name,
+                first
+            ),
+        ));
+    }
+
+    say(format!(
+        "[+] {} sampled sectors all match {}.",
+        offsets.len(),
+        want_name
+    ));
+    Ok(())
+}
+
+/// Up to `count` distinct sector-aligned offsets below `size`: one random pick
+/// inside each of `count` equal strata, so samples cover the whole range.
+pub fn stratified_offsets(size: u64, count: u64, sector_size: u64, seed: Option<u64>) -> Vec<u64> {
+    let sectors = size / sector_size;
+    if sectors == 0 || count == 0 {
+        return Vec::new();
+    }
+
+    let count = count.min(sectors);
+    let mut rng: Box<dyn RngCore> = match seed {
+        Some(s) => Box::new(StdRng::seed_from_u64(s)),
+        None => Box::new(rand::thread_rng()),
+    };
+
+    (0..count)
+        .map(|i| {
+            let first = i * sectors / count;
+            let end = (i + 1) * sectors / count;
+            (first + rng.next_u64() % (end - first)) * sector_size
+        })
+        .collect()
+}
+
+/// Check every byte of the target equals `expected` and hash what was read.
+/// The error names the first bad offset.
+fn verify_pattern<R: Read + Seek>(
+    file: &mut R,
+    offset: u64,
+    size: u64,
+    chunk: usize,
+    expected: u8,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<String> {
+    say("");
+    say(format!("=== Verifying (expecting 0x{:02X}) ===", expected));
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = AlignedBuf::new(chunk, IO_ALIGN)?;
+    let mut checked: u64 = 0;
+    let mut tracker = ProgressTracker::new();
+    let mut hasher = Sha256::new();
+
+    while checked < size {
+        check_interrupted()?;
+        // the final chunk is exactly as long as the final write was
+        let to_read = chunk_len(size - checked, chunk);
+
+        file.read_exact(&mut buf[..to_read])?;
+        hasher.update(&buf[..to_read]);
+
+        if let Some(pos) = buf[..to_read].iter().position(|&b| b != expected) {
+            say("");
+            return Err(mismatch(offset + checked + pos as u64, expected, buf[pos]));
+        }
+
+        checked += to_read as u64;
+        tracker.update(sink, "Verify", 1, 1, checked, size);
+    }
+
+    say("");
+
+    Ok(hex_string(&hasher.finalize()))
+}
+
+/// Read back a final pass without a fixed byte and hash it. Data that can be
+/// regenerated (Gutmann sequences, Nonce, seeded random) must match exactly;
+/// otherwise no 512-byte block may be one repeated byte or still hold what
+/// the pass before wrote there.
+fn verify_stream<R: Read + Seek>(
+    file: &mut R,
+    size: u64,
+    opts: &WipeOptions,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<String> {
+    let last_pass = effective_passes(opts.mode, opts.passes);
+    say("");
+    say("=== Reading back ===");
+
+    file.seek(SeekFrom::Start(opts.offset))?;
+    let chunk = opts.buffer_size;
+    let mut buf = AlignedBuf::new(chunk, IO_ALIGN)?;
+    let mut want = vec![0u8; chunk];
+    let mut checked: u64 = 0;
+    let mut tracker = ProgressTracker::new();
+    let mut hasher = Sha256::new();
+
+    while checked < size {
+        check_interrupted()?;
+        let to_read = chunk_len(size - checked, chunk);
+        let at = opts.offset + checked;
+
+        file.read_exact(&mut buf[..to_read])?;
+        hasher.update(&buf[..to_read]);
+
+        let (got, want) = (&buf[..to_read], &mut want[..to_read]);
+        if pass_bytes(opts, size, last_pass, at, want) {
+            if let Some(pos) = got.iter().zip(want.iter()).position(|(a, b)| a != b) {
+                say("");
+                return Err(mismatch(at + pos as u64, want[pos], got[pos]));
+            }
+        } else {
+            check_not_uniform(got, at)?;
+            if last_pass > 1 && pass_bytes(opts, size, last_pass - 1, at, want) {
+                check_not_previous(got, want, at, last_pass - 1)?;
+            }
+        }
+
+        checked += to_read as u64;
+        tracker.update(sink, "Verify", 1, 1, checked, size);
+    }
+
+    say("");
+
+    Ok(hex_string(&hasher.finalize()))
+}
+
+/// The error for the first byte that does not read back as written.
+fn mismatch(at: u64, want: u8, found: u8) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "Verification failed at offset {}: expected 0x{:02X}, found 0x{:02X}",
+            at, want, found
+        ),
+    )
+}
+
+/// Fail on the first 512-byte block of `data` that still holds what pass
+/// `pass` wrote there (`previous`), i.e. the final pass never reached it.
+fn check_not_previous(data: &[u8], previous: &[u8], at: u64, pass: u32) -> io::Result<()> {
+    let old_blocks = previous.chunks(UNIFORM_BLOCK);
+    for (i, (got, old)) in data.chunks(UNIFORM_BLOCK).zip(old_blocks).enumerate() {
+        if got == old {
+            say("");
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Verification failed at offset {}: {} bytes still hold pass {}'s data",
+                    at + (i * UNIFORM_BLOCK) as u64,
+                    got.len(),
+                    pass
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// After a random final pass no 512-byte block can plausibly be a single
+/// repeated byte; one that is still holds an earlier pass (or was never written).
+fn check_not_uniform(data: &[u8], at: u64) -> io::Result<()> {
+    for (i, block) in data.chunks(UNIFORM_BLOCK).enumerate() {
+        if block.len() == UNIFORM_BLOCK && block.iter().all(|&b| b == block[0]) {
+            say("");
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Verification failed at offset {}: {} bytes all read 0x{:02X}, not random data",
+                    at + (i * UNIFORM_BLOCK) as u64,
+                    UNIFORM_BLOCK,
+                    block[0]
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// --checksum-before-after: SHA-256 (hex) of `size` bytes from `offset`,
+/// streamed in `chunk`-sized reads, and whether every byte read was 0x00.
+pub fn content_hash<R: Read + Seek>(
+    file: &mut R,
+    offset: u64,
+    size: u64,
+    chunk: usize,
+) -> io::Result<(String, bool)> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = AlignedBuf::new(chunk, IO_ALIGN)?;
+    let mut hasher = Sha256::new();
+    let mut all_zero = true;
+    let mut done: u64 = 0;
+
+    while done < size {
+        check_interrupted()?;
+        let to_read = chunk_len(size - done, chunk);
+        file.read_exact(&mut buf[..to_read])?;
+        hasher.update(&buf[..to_read]);
+        all_zero = all_zero && buf[..to_read].iter().all(|&b| b == 0);
+        done += to_read as u64;
+    }
+
+    Ok((hex_string(&hasher.finalize()), all_zero))
+}
+
+/// SHA-256 (hex) of `size` bytes of `byte`: what the post-wipe hash of a
+/// verified fixed-pattern wipe has to be.
+pub fn pattern_hash(byte: u8, size: u64) -> String {
+    let block = [byte; 64 * 1024];
+    let mut hasher = Sha256::new();
+    let mut done: u64 = 0;
+    while done < size {
+        let n = chunk_len(size - done, block.len());
+        hasher.update(&block[..n]);
+        done += n as u64;
+    }
+    hex_string(&hasher.finalize())
+}
+
+/// Result of a --read-bench run.
+#[derive(Clone, Copy, Debug)]
+pub struct BenchStats {
+    pub bytes: u64,
+    pub secs: f64,
+    pub mib_s: f64,
+}
+
+/// Read (never write) up to `amount` bytes in `chunk`-sized reads and time it.
+pub fn read_bench<R: Read>(
+    reader: &mut R,
+    amount: u64,
+    chunk: usize,
+    sink: &mut dyn ProgressSink,
+) -> io::Result<BenchStats> {
+    let mut buf = vec![0u8; chunk];
+    let mut done: u64 = 0;
+    let mut tracker = ProgressTracker::new();
+    let start = Instant::now();
+
+    while done < amount {
+        let to_read = chunk_len(amount - done, chunk);
+
+        let n = reader.read(&mut buf[..to_read])?;
+        if n == 0 {
+            break;
+        }
+        done += n as u64;
+        tracker.update(sink, "Read", 1, 1, done, amount);
+    }
+    say("");
+
+    let secs = start.elapsed().as_secs_f64().max(0.000_001);
+    Ok(BenchStats {
+        bytes: done,
+        secs,
+        mib_s: done as f64 / (1024.0 * 1024.0) / secs,
+    })
+}
+
+/// Sector 0 contents for --stamp: plain text, zero padded, no 0x55AA signature.
+pub fn build_stamp(sector_size: usize, operator: Option<&str>, unix_secs: u64) -> Vec<u8> {
+    let mut text = format!(
+        "WIPED BY WIPECORE v{}\r\nDate: {}\r\n",
+        env!("CARGO_PKG_VERSION"),
+        format_utc(unix_secs)
+    );
+    if let Some(op) = operator {
+        text.push_str(&format!("Operator: {}\r\n", op));
+    }
+
+    let mut sector = vec![0u8; sector_size];
+    let n = text.len().min(STAMP_MAX_TEXT).min(sector_size);
+    sector[..n].copy_from_slice(&text.as_bytes()[..n]);
+    sector
+}
+
+/// Write the --stamp banner over the start of an already wiped target.
+pub fn write_stamp<W: Write + Seek>(
+    file: &mut W,
+    sector_size: usize,
+    size: u64,
+    operator: Option<&str>,
+) -> io::Result<()> {
+    let sector = AlignedBuf::copy_of(&build_stamp(sector_size, operator, unix_now()))?;
+    let n = (sector.len() as u64).min(size) as usize;
+
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&sector[..n])?;
+    file.flush()?;
+
+    say("[+] Wipe stamp written to the first sector.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::io::Cursor;
+
+    /// In-memory target that counts `sync_data` calls, keeps a copy of its
+    /// contents at every flush (the end of each pass) and can have a
+    /// defective byte at `stuck.0` that reads back as `stuck.1`.
+    struct MemTarget {
+        data: Cursor<Vec<u8>>,
+        syncs: Cell<u32>,
+        flushed: Vec<Vec<u8>>,
+        stuck: Option<(u64, u8)>,
+        /// Reads of the stuck byte that still see the fault
+        stuck_reads: u32,
+        /// Offset of every write, in the order they happened
+        writes: Vec<u64>,
+    }
+
+    impl MemTarget {
+        fn new(size: usize) -> Self {
+            MemTarget {
+                data: Cursor::new(vec![0u8; size]),
+                syncs: Cell::new(0),
+                flushed: Vec::new(),
+                stuck: None,
+                stuck_reads: 0,
+                writes: Vec::new(),
+            }
+        }
+
+        fn with_stuck_byte(size: usize, at: u64, value: u8, reads: u32) -> Self {
+            let mut target = MemTarget::new(size);
+            target.stuck = Some((at, value));
+            target.stuck_reads = reads;
+            target
+        }
+    }
+
+    impl Read for MemTarget {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let at = self.data.position();
+            let n = self.data.read(buf)?;
+            if let Some((off, value)) = self.stuck {
+                if self.stuck_reads > 0 && (at..at + n as u64).contains(&off) {
+                    buf[(off - at) as usize] = value;
+                    self.stuck_reads -= 1;
+                }
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for MemTarget {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes.push(self.data.position());
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed.push(self.data.get_ref().clone());
+            Ok(())
+        }
+    }
+
+    impl Seek for MemTarget {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.data.seek(pos)
+        }
+    }
+
+    impl WipeTarget for MemTarget {
+        fn sync_data(&self) -> io::Result<()> {
+            self.syncs.set(self.syncs.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn small_opts(mode: WipeMode, passes: u32) -> WipeOptions {
+        let mut opts = WipeOptions::new(mode, passes);
+        opts.buffer_size = 4096;
+        opts
+    }
+
+    /// Wipe all of `target` with progress going nowhere.
+    fn wipe(target: &mut MemTarget, opts: &WipeOptions) -> io::Result<WipeReport> {
+        let size = target.data.get_ref().len() as u64;
+        wipe_file_reported(target, size, opts, &mut RecordingSink::default())
+    }
+
+    /// Keeps every snapshot as (label, done, total, percent).
+    #[derive(Default)]
+    struct RecordingSink(Vec<(String, u64, u64, f64)>);
+
+    impl ProgressSink for RecordingSink {
+        fn report(&mut self, p: &Progress) {
+            self.0
+                .push((p.label.to_string(), p.done, p.total, p.percent()));
+        }
+    }
+
+    #[test]
+    fn verify_progress_reaches_100_percent() {
+        let size = 10 * 4096 + 100;
+        let mut target = Cursor::new(vec![0u8; size]);
+        let mut sink = RecordingSink::default();
+
+        verify_pattern(&mut target, 0, size as u64, 4096, 0x00, &mut sink).unwrap();
+
+        let (label, done, total, percent) = sink.0.last().unwrap().clone();
+        assert_eq!(label, "Verify");
+        assert_eq!((done, total), (size as u64, size as u64));
+        assert_eq!(percent, 100.0);
+        assert!(sink.0.iter().all(|(l, ..)| l == "Verify"));
+    }
+
+    #[test]
+    fn sync_every_flushes_at_each_interval_within_a_pass() {
+        let size = 10 * 4096;
+        let mut target = MemTarget::new(size);
+        let mut opts = small_opts(WipeMode::Zeros, 1);
+        opts.sync_every = Some(8192);
+
+        wipe(&mut target, &opts).unwrap();
+
+        // at 8, 16, 24 and 32 KiB; the end of the pass is not an interval flush
+        assert_eq!(target.syncs.get(), 4);
+    }
+
+    #[test]
+    fn no_sync_every_means_no_flush_within_a_pass() {
+        let size = 10 * 4096;
+        let mut target = MemTarget::new(size);
+        let opts = small_opts(WipeMode::Zeros, 1);
+
+        wipe(&mut target, &opts).unwrap();
+
+        assert_eq!(target.syncs.get(), 0);
+    }
+
+    fn verify_each(on_fail: OnVerifyFail) -> WipeOptions {
+        // pass 1 writes 0x00, pass 2 writes 0xFF
+        let mut opts = small_opts(WipeMode::Secureflip, 2);
+        opts.verify_each = true;
+        opts.on_verify_fail = on_fail;
+        opts
+    }
+
+    #[test]
+    fn on_verify_fail_continue_goes_on_to_the_next_pass() {
+        let mut target = MemTarget::with_stuck_byte(8192, 100, 0xFF, u32::MAX);
+        let report = wipe(&mut target, &verify_each(OnVerifyFail::Continue)).unwrap();
+        assert_eq!(report.passes, 2);
+    }
+
+    #[test]
+    fn on_verify_fail_continue_still_fails_the_last_pass() {
+        let mut target = MemTarget::with_stuck_byte(8192, 100, 0x00, u32::MAX);
+        assert!(wipe(&mut target, &verify_each(OnVerifyFail::Continue)).is_err());
+    }
+
+    #[test]
+    fn on_verify_fail_abort_stops_before_the_next_pass() {
+        let mut target = MemTarget::with_stuck_byte(8192, 100, 0xFF, u32::MAX);
+        let err = wipe(&mut target, &verify_each(OnVerifyFail::Abort)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // pass 2 (0xFF) was never written
+        assert!(target.data.get_ref().iter().all(|&b| b == 0x00));
+    }
+
+    #[test]
+    fn on_verify_fail_rewipe_retries_the_pass_once() {
+        let size = 8192;
+        let mut target = MemTarget::with_stuck_byte(size, 100, 0xFF, 1);
+        let report = wipe(&mut target, &verify_each(OnVerifyFail::Rewipe)).unwrap();
+        assert_eq!(report.passes, 2);
+        // the failed first attempt is not counted
+        assert_eq!(report.bytes_written, 2 * size as u64);
+    }
+
+    #[test]
+    fn on_verify_fail_rewipe_gives_up_when_the_retry_fails() {
+        let mut target = MemTarget::with_stuck_byte(8192, 100, 0xFF, u32::MAX);
+        assert!(wipe(&mut target, &verify_each(OnVerifyFail::Rewipe)).is_err());
+    }
+
+    #[test]
+    fn resolve_range_in_bytes_and_sectors() {
+        let total: u64 = 1 << 20;
+        assert_eq!(
+            resolve_range(total, None, None, Units::Bytes, 512).unwrap(),
+            (0, total)
+        );
+        assert_eq!(
+            resolve_range(total, Some(4096), None, Units::Bytes, 512).unwrap(),
+            (4096, total - 4096)
+        );
+        assert_eq!(
+            resolve_range(total, Some(8), Some(16), Units::Sectors, 4096).unwrap(),
+            (8 * 4096, 16 * 4096)
+        );
+    }
+
+    #[test]
+    fn resolve_range_rejects_ranges_outside_the_target() {
+        let total: u64 = 1 << 20;
+        let range = |offset, length| resolve_range(total, offset, length, Units::Bytes, 512);
+        assert!(range(Some(total), None).is_err());
+        assert!(range(Some(0), Some(0)).is_err());
+        assert!(range(Some(4096), Some(total)).is_err());
+        assert!(resolve_range(total, Some(u64::MAX), None, Units::Sectors, 512).is_err());
+    }
+
+    #[test]
+    fn stratified_offsets_cover_every_stratum() {
+        let (size, sector) = (1000 * 512, 512);
+        let offsets = stratified_offsets(size, 10, sector, Some(7));
+
+        assert_eq!(offsets.len(), 10);
+        for (i, off) in offsets.iter().enumerate() {
+            assert_eq!(off % sector, 0);
+            // stratum i is sectors 100*i .. 100*(i+1)
+            assert!((i as u64 * 100..(i as u64 + 1) * 100).contains(&(off / sector)));
+        }
+    }
+
+    #[test]
+    fn stratified_offsets_are_reproducible_with_a_seed() {
+        let a = stratified_offsets(1 << 30, 64, 4096, Some(42));
+        let b = stratified_offsets(1 << 30, 64, 4096, Some(42));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn stratified_offsets_never_exceed_the_sector_count() {
+        let offsets = stratified_offsets(4 * 512, 100, 512, None);
+        assert_eq!(offsets, vec![0, 512, 1024, 1536]);
+        assert!(stratified_offsets(100, 10, 512, None).is_empty());
+        assert!(stratified_offsets(4096, 0, 512, None).is_empty());
+    }
+
+    #[test]
+    fn complement_pass_inverts_the_random_pass() {
+        for reverse in [false, true] {
+            let mut target = MemTarget::new(3 * 4096 + 512);
+            let mut opts = small_opts(WipeMode::RandomComplement, 2);
+            opts.seed = Some(9);
+            opts.reverse = reverse;
+
+            wipe(&mut target, &opts).unwrap();
+
+            assert_eq!(target.flushed.len(), 2);
+            let (pass1, pass2) = (&target.flushed[0], &target.flushed[1]);
+            assert!(pass1.iter().zip(pass2).all(|(a, b)| *b == !*a));
+            check_not_uniform(pass1, 0).unwrap();
+        }
+    }
+
+    #[test]
+    fn complement_needs_a_seed() {
+        let mut target = MemTarget::new(4096);
+        let opts = small_opts(WipeMode::RandomComplement, 2);
+        assert!(wipe(&mut target, &opts).is_err());
+    }
+
+    #[test]
+    fn gutmann_table_matches_the_paper() {
+        let seq = |pass| pass_sequence(WipeMode::Gutmann, pass);
+        assert_eq!(effective_passes(WipeMode::Gutmann, 1), 35);
+        assert_eq!(seq(5), Some(&[0x55][..]));
+        assert_eq!(seq(6), Some(&[0xAA][..]));
+        assert_eq!(seq(7), Some(&[0x92, 0x49, 0x24][..]));
+        assert_eq!(seq(9), Some(&[0x24, 0x92, 0x49][..]));
+        // passes 10-25 step through 0x00, 0x11, ... 0xFF
+        for (pass, b) in (10..=25).zip((0..=0xFFu8).step_by(0x11)) {
+            assert_eq!(pass_pattern(WipeMode::Gutmann, pass), Some(b));
+        }
+        assert_eq!(seq(29), Some(&[0x6D, 0xB6, 0xDB][..]));
+        assert_eq!(seq(31), Some(&[0xDB, 0x6D, 0xB6][..]));
+        for pass in (1..=4).chain(32..=35) {
+            assert_eq!(seq(pass), None);
+            assert!(is_random_pass(WipeMode::Gutmann, pass));
+        }
+        assert!(!is_random_pass(WipeMode::Gutmann, 7));
+        assert_eq!(pass_pattern(WipeMode::Gutmann, 7), None);
+    }
+
+    #[test]
+    fn sequences_stay_continuous_across_chunks() {
+        let seq = [0x92, 0x49, 0x24];
+        let mut whole = [0u8; 10];
+        fill_sequence(&mut whole, &seq, 0);
+        let (mut a, mut b) = ([0u8; 4], [0u8; 6]);
+        fill_sequence(&mut a, &seq, 0);
+        fill_sequence(&mut b, &seq, 4);
+        assert_eq!([&a[..], &b[..]].concat(), whole);
+        assert_eq!(&whole[..4], &[0x92, 0x49, 0x24, 0x92]);
+    }
+
+    #[test]
+    fn nonce_keystream_is_sha256_of_nonce_and_counter() {
+        let block = nonce_keystream(7);
+        assert_eq!(block.len(), NONCE_BLOCK);
+        assert_eq!(block, nonce_keystream(7));
+        assert_ne!(block, nonce_keystream(8));
+        // SHA-256(7u64 LE || 0u64 LE) starts 76 10 8f 84
+        assert_eq!(&block[..4], &[0x76, 0x10, 0x8f, 0x84]);
+        for (counter, chunk) in block.chunks(32).enumerate() {
+            let mut hasher = Sha256::new();
+            hasher.update(7u64.to_le_bytes());
+            hasher.update((counter as u64).to_le_bytes());
+            assert_eq!(chunk, &hasher.finalize()[..]);
+        }
+    }
+
+    #[test]
+    fn strict_sector_size_accepts_whole_sectors() {
+        let buffer = 1u64 << 20;
+        let ok =
+            check_strict_sector_size(buffer, Some(64 << 10), Some(8192), None, Units::Bytes, 4096);
+        assert!(ok.is_ok());
+        // offsets in sectors are whole sectors by definition
+        let ok = check_strict_sector_size(buffer, None, Some(3), Some(5), Units::Sectors, 4096);
+        assert!(ok.is_ok());
+    }
+
+    #[test]
+    fn strict_sector_size_names_every_misaligned_value() {
+        let err = check_strict_sector_size(
+            1 << 20,
+            Some(1000),
+            Some(512),
+            Some(4096),
+            Units::Bytes,
+            4096,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let msg = err.to_string();
+        assert!(msg.contains("sector size is 4096 bytes"), "{msg}");
+        assert!(msg.contains("--io-size 1000"), "{msg}");
+        assert!(msg.contains("--offset 512"), "{msg}");
+        assert!(!msg.contains("--buffer-size"), "{msg}");
+        assert!(!msg.contains("--length"), "{msg}");
+    }
+
+    #[test]
+    fn uniform_block_fails_a_random_verify() {
+        let mut data: Vec<u8> = (0..2048u32).map(|i| (i * 7 + i / 3) as u8).collect();
+        assert!(check_not_uniform(&data, 0).is_ok());
+        data[1024..1536].fill(0xAA);
+        let err = check_not_uniform(&data, 4096).unwrap_err();
+        assert!(err.to_string().contains("offset 5120"), "{err}");
+        assert!(err.to_string().contains("0xAA"), "{err}");
+        // a short tail block is too small to judge
+        assert!(check_not_uniform(&[0u8; 100], 0).is_ok());
+    }
+
+    #[test]
+    fn block_left_from_the_previous_pass_fails() {
+        let previous: Vec<u8> = (0..1024u32).map(|i| i as u8).collect();
+        let mut data: Vec<u8> = previous.iter().map(|b| b ^ 0x5A).collect();
+        assert!(check_not_previous(&data, &previous, 0, 1).is_ok());
+        data[512..].copy_from_slice(&previous[512..]);
+        let err = check_not_previous(&data, &previous, 0, 1).unwrap_err();
+        assert!(err.to_string().contains("offset 512"), "{err}");
+        assert!(err.to_string().contains("pass 1's data"), "{err}");
+    }
+
+    #[test]
+    fn nonce_wipe_writes_the_repeated_keystream() {
+        let size = 3 * NONCE_BLOCK + 100;
+        let mut opts = small_opts(WipeMode::Nonce, 1);
+        opts.nonce = Some(7);
+        let mut target = MemTarget::new(size);
+        wipe(&mut target, &opts).unwrap();
+
+        let block = nonce_keystream(7);
+        let expected: Vec<u8> = block.iter().cycle().take(size).copied().collect();
+        assert_eq!(target.data.get_ref(), &expected);
+
+        opts.nonce = Some(8);
+        let mut other = MemTarget::new(size);
+        wipe(&mut other, &opts).unwrap();
+        assert_ne!(other.data.get_ref(), target.data.get_ref());
+    }
+
+    #[test]
+    fn pattern_verify_regenerates_a_nonce_final_pass() {
+        let size = 2 * NONCE_BLOCK as u64;
+        let mut opts = small_opts(WipeMode::Nonce, 1);
+        opts.nonce = Some(7);
+        let mut target = MemTarget::new(size as usize);
+        wipe(&mut target, &opts).unwrap();
+        let mut image = Cursor::new(target.data.into_inner());
+        let mut sink = RecordingSink::default();
+
+        assert!(pattern_verify(&mut image, size, &opts, &mut sink).is_ok());
+        assert!(verify_samples(&mut image, size, &opts, 4, 512).is_ok());
+
+        // the wrong nonce doesn't match anywhere
+        opts.nonce = Some(8);
+        let err = pattern_verify(&mut image, size, &opts, &mut sink).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(verify_samples(&mut image, size, &opts, 4, 512).is_err());
+    }
+
+    #[test]
+    fn only_unseeded_random_data_cannot_be_regenerated() {
+        let mut opts = small_opts(WipeMode::Nonce, 1);
+        assert!(!can_regenerate(&opts, 1));
+        opts.nonce = Some(1);
+        assert!(can_regenerate(&opts, 1));
+
+        let mut opts = small_opts(WipeMode::Random, 1);
+        assert!(!can_regenerate(&opts, 1));
+        opts.seed = Some(1);
+        assert!(can_regenerate(&opts, 1));
+
+        assert!(can_regenerate(&small_opts(WipeMode::Zeros, 1), 1));
+        assert!(can_regenerate(&small_opts(WipeMode::Gutmann, 35), 10));
+    }
+
+    #[test]
+    fn pattern_scan_reports_a_flipped_byte_with_its_offset() {
+        let size = 3 * 4096;
+        let opts = small_opts(WipeMode::Zeros, 1);
+        let mut image = Cursor::new(vec![0u8; size]);
+        let mut sink = RecordingSink::default();
+
+        let clean = scan_pattern(&mut image, size as u64, &opts, 1, &mut sink).unwrap();
+        assert_eq!(clean.mismatches, 0);
+        assert!(clean.ranges.is_empty());
+
+        image.get_mut()[5000] = 0x01;
+        let dirty = scan_pattern(&mut image, size as u64, &opts, 1, &mut sink).unwrap();
+        assert_eq!(dirty.mismatches, 1);
+        assert_eq!(dirty.ranges, vec![(5000, 5001)]);
+    }
+
+    #[test]
+    fn verify_only_reports_clean_and_dirty_regions() {
+        let size = 2 * 4096;
+        let opts = small_opts(WipeMode::Zeros, 1);
+        let mut image = Cursor::new(vec![0u8; size]);
+        let mut sink = RecordingSink::default();
+
+        crate::progress::hold_output();
+        assert!(pattern_verify(&mut image, size as u64, &opts, &mut sink).is_ok());
+        assert!(crate::progress::take_output().contains("All 8192 bytes match 0x00"));
+
+        image.get_mut()[100..104].fill(0xAA);
+        crate::progress::hold_output();
+        let err = pattern_verify(&mut image, size as u64, &opts, &mut sink).unwrap_err();
+        let listed = crate::progress::take_output();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "4 of 8192 bytes do not match 0x00");
+        assert!(listed.contains("bad bytes 100..104 (4 bytes)"));
+    }
+
+    /// A --checkpoint-file for a Secureflip x2 wipe of a `size`-byte target.
+    fn checkpoint(name: &str, size: u64) -> CheckpointFile {
+        CheckpointFile {
+            path: std::env::temp_dir().join(format!("wipecore-{}-{}", name, std::process::id())),
+            target: "image.bin".to_string(),
+            size,
+            mode: "Secureflip".to_string(),
+            passes: 2,
+            seed: None,
+            nonce: None,
+            save_every: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn file_wipe_resumes_from_the_recorded_offset() {
+        let size = 4 * 4096;
+        let cp = checkpoint("resume", size as u64);
+        // pass 1 (0x00) finished and pass 2 (0xFF) got 8192 bytes in
+        cp.save(2, 8192).unwrap();
+        let mut image = vec![0u8; size];
+        image[..8192].fill(0xFF);
+
+        let mut opts = small_opts(WipeMode::Secureflip, 2);
+        opts.checkpoint = Some(cp.clone());
+        let mut target = MemTarget::new(size);
+        target.data = Cursor::new(image);
+        let report = wipe(&mut target, &opts).unwrap();
+
+        assert!(target.data.get_ref().iter().all(|&b| b == 0xFF));
+        assert_eq!(report.bytes_written, (size - 8192) as u64);
+        // a finished wipe leaves nothing to resume
+        assert!(!cp.path.exists());
+    }
+
+    #[test]
+    fn checkpoint_from_another_run_starts_over() {
+        let size = 2 * 4096;
+        let mut other = checkpoint("other-run", size as u64);
+        other.seed = Some(1);
+        other.save(2, 4096).unwrap();
+
+        let mut opts = small_opts(WipeMode::Secureflip, 2);
+        opts.checkpoint = Some(CheckpointFile {
+            seed: None,
+            ..other.clone()
+        });
+        let report = wipe(&mut MemTarget::new(size), &opts).unwrap();
+
+        assert_eq!(report.bytes_written, 2 * size as u64);
+        assert!(!other.path.exists());
+    }
+
+    /// Target whose driver refuses writes above `limit` bytes, the way some
+    /// USB bridges fail large transfers.
+    struct LimitedWrites {
+        inner: MemTarget,
+        limit: usize,
+    }
+
+    impl Read for LimitedWrites {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for LimitedWrites {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.limit {
+                // ERROR_NO_SYSTEM_RESOURCES
+                return Err(io::Error::from_raw_os_error(1450));
+            }
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for LimitedWrites {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl WipeTarget for LimitedWrites {
+        fn sync_data(&self) -> io::Result<()> {
+            self.inner.sync_data()
+        }
+    }
+
+    #[test]
+    fn wipe_downshifts_the_buffer_until_writes_fit() {
+        let size = 512 << 10;
+        let mut opts = small_opts(WipeMode::Random, 1);
+        opts.buffer_size = 256 << 10;
+        opts.seed = Some(3);
+        opts.reverse = true;
+        let mut target = LimitedWrites {
+            inner: MemTarget::new(size),
+            limit: 64 << 10,
+        };
+
+        let mut sink = RecordingSink::default();
+
+        let report = wipe_file_reported(&mut target, size as u64, &opts, &mut sink).unwrap();
+        assert_eq!(report.buffer_size, 64 << 10);
+        assert_eq!(report.bytes_written, size as u64);
+
+        // a reverse stream is laid out per chunk, so only the size used regenerates it
+        let mut image = Cursor::new(target.inner.data.into_inner());
+        assert!(verify_stream(&mut image, size as u64, &opts, &mut sink).is_err());
+        opts.buffer_size = report.buffer_size;
+        assert!(verify_stream(&mut image, size as u64, &opts, &mut sink).is_ok());
+    }
+
+    #[test]
+    fn zero_region_hashes_to_the_known_value() {
+        // SHA-256 of 8192 zero bytes
+        let known = "9f1dcbc35c350d6027f98be0f5c8b43b42ca52b7604459c0c42be3aa88913d47";
+        let mut image = Cursor::new(vec![0u8; 8192]);
+        let mut sink = RecordingSink::default();
+
+        let post = verify_pattern(&mut image, 0, 8192, 4096, 0x00, &mut sink).unwrap();
+        assert_eq!(post, known);
+        assert_eq!(pattern_hash(0x00, 8192), known);
+        assert_ne!(pattern_hash(0xFF, 8192), known);
+    }
+
+    #[test]
+    fn stamp_lands_in_sector_0_without_a_boot_signature() {
+        let mut target = MemTarget::new(4 * 512);
+        target.data.get_mut().fill(0xFF);
+        write_stamp(&mut target, 512, 4 * 512, Some("alice")).unwrap();
+
+        let disk = target.data.get_ref();
+        let text = String::from_utf8_lossy(&disk[..512]);
+        assert!(text.starts_with(&format!(
+            "WIPED BY WIPECORE v{}\r\nDate: ",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(text.contains("Operator: alice\r\n"));
+        assert_ne!(&disk[510..512], &[0x55, 0xAA]);
+        // only sector 0 is touched
+        assert!(disk[512..].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn progress_callback_sees_every_pass_to_the_end() {
+        let size: u64 = 64 << 10;
+        let mut target = MemTarget::new(size as usize);
+        let mut seen: Vec<(u32, u64, u64)> = Vec::new();
+
+        let on_progress = |p: Progress| seen.push((p.pass, p.done, p.total));
+        wipe_file_with_progress(&mut target, size, WipeMode::Secureflip, 2, on_progress).unwrap();
+
+        assert_eq!(seen.first().map(|s| s.0), Some(1));
+        assert_eq!(seen.last(), Some(&(2, size, size)));
+        assert!(target.data.get_ref().iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn engine_raises_secureflip_to_the_shared_minimum() {
+        let size: u64 = 64 << 10;
+        let mut target = MemTarget::new(size as usize);
+        let report =
+            wipe_file_with_progress(&mut target, size, WipeMode::Secureflip, 1, |_| {}).unwrap();
+
+        assert_eq!(min_passes(WipeMode::Secureflip), 2);
+        assert_eq!(report.passes, effective_passes(WipeMode::Secureflip, 1));
+        assert!(target.data.get_ref().iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn reverse_wipe_writes_the_last_chunk_first() {
+        let size = 3 * 4096 + 512;
+        let mut target = MemTarget::new(size);
+        target.data.get_mut().fill(0xAA);
+        let mut opts = small_opts(WipeMode::Zeros, 1);
+        opts.reverse = true;
+
+        let mut sink = RecordingSink::default();
+        wipe_file_reported(&mut target, size as u64, &opts, &mut sink).unwrap();
+
+        assert_eq!(target.writes, vec![8704, 4608, 512, 0]);
+        assert!(target.data.get_ref().iter().all(|&b| b == 0));
+        // progress still counts up to the full size
+        let last = sink.0.last().unwrap();
+        assert_eq!((last.1, last.2), (size as u64, size as u64));
+    }
+
+    /// Takes `delay` for every write, like a failing drive.
+    struct ThrottledWrites {
+        inner: MemTarget,
+        delay: Duration,
+    }
+
+    impl Read for ThrottledWrites {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for ThrottledWrites {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            std::thread::sleep(self.delay);
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for ThrottledWrites {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl WipeTarget for ThrottledWrites {
+        fn sync_data(&self) -> io::Result<()> {
+            self.inner.sync_data()
+        }
+    }
+
+    #[test]
+    fn slow_drive_is_aborted_after_the_grace_period() {
+        let size = 256 << 10;
+        let mut target = ThrottledWrites {
+            inner: MemTarget::new(size),
+            delay: Duration::from_millis(2),
+        };
+        // 4 KiB every 2 ms is about 2 MB/s
+        let mut opts = small_opts(WipeMode::Random, 1);
+        opts.min_throughput = Some(100.0);
+        opts.throughput_grace = Duration::ZERO;
+
+        let mut sink = RecordingSink::default();
+        let res = wipe_file_reported(&mut target, size as u64, &opts, &mut sink);
+        let err = res.unwrap_err().to_string();
+
+        assert!(err.starts_with("Drive too slow"), "{}", err);
+        assert!(target.inner.writes.len() < size / 4096);
+    }
+
+    #[test]
+    fn checkpoint_offsets_climb_to_the_end_of_the_range() {
+        let mut target = MemTarget::new(4 * 4096);
+        let mut opts = small_opts(WipeMode::Zeros, 1);
+        opts.checkpoint_every = Some(Duration::ZERO);
+
+        crate::progress::hold_output();
+        let res = wipe(&mut target, &opts);
+        let out = crate::progress::take_output();
+        res.unwrap();
+
+        let offsets: Vec<u64> = out
+            .lines()
+            .filter_map(|l| l.split_once(" offset="))
+            .map(|(_, offset)| offset.parse().unwrap())
+            .collect();
+        assert_eq!(offsets, [4096, 8192, 12288, 16384]);
+    }
+
+    #[test]
+    fn last_byte_of_the_target_is_overwritten() {
+        let path = std::env::temp_dir().join(format!("wipecore-tail-{}", std::process::id()));
+        let size = 3 * 4096 + 100;
+        std::fs::write(&path, vec![0xAAu8; size]).unwrap();
+        let mut file = File::options().read(true).write(true).open(&path).unwrap();
+        let opts = small_opts(WipeMode::Zeros, 1);
+
+        let res = wipe_file_reported(&mut file, size as u64, &opts, &mut RecordingSink::default());
+        res.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap()[size - 1], 0);
+
+        // a tail left behind is rewritten up to the very last byte
+        file.seek(SeekFrom::Start(size as u64 - 512)).unwrap();
+        file.write_all(&[0xAA; 512]).unwrap();
+        crate::progress::hold_output();
+        let res = overwrite_tail(&mut file, size as u64, 512, &opts);
+        crate::progress::take_output();
+        res.unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(data.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn every_mode_has_a_listed_description() {
+        for mode in WipeMode::value_variants() {
+            assert!(mode.to_possible_value().is_some(), "{:?} has no name", mode);
+            let info = mode_info(*mode);
+            for text in [info.passes, info.recommended, info.speed] {
+                assert!(!text.is_empty(), "{:?} has an empty description", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_size_is_held_to_a_fraction_of_free_ram() {
+        let free = 4u64 << 30;
+        let limit = free / MAX_BUFFER_RAM_FRACTION;
+        assert!(check_buffer_size(limit, free).is_ok());
+        let err = check_buffer_size(limit + 1, free).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+
+        // the default fits even when almost nothing is free
+        assert!(check_buffer_size(DEFAULT_BUFFER as u64, 1 << 20).is_ok());
+        assert!(check_buffer_size(0, free).is_err());
+    }
+
+    #[test]
+    fn summary_lists_every_target_and_the_total() {
+        let targets = [
+            (r"D:\a.img".to_string(), 1u64 << 30),
+            (r"D:\b.bin".to_string(), 512u64 << 20),
+        ];
+        let summary = build_summary(&targets, WipeMode::Secureflip, 1);
+        assert_eq!(
+            summary,
+            "Targets (2):\n  [1] D:\\a.img  -  1.00 GB\n  [2] D:\\b.bin  -  512 MB\n\
+             Mode :   Secureflip\nPasses : 2\nTotal :  1.50 GB (3.00 GB written over all passes)\n"
+        );
+    }
+
+    #[test]
+    fn read_bench_fills_in_its_stats_for_a_file() {
+        let path = std::env::temp_dir().join(format!("wipecore-bench-{}", std::process::id()));
+        std::fs::write(&path, vec![0x11u8; 40_000]).unwrap();
+        let mut file = File::open(&path).unwrap();
+
+        crate::progress::hold_output();
+        let stats = read_bench(&mut file, 1 << 20, 4096, &mut RecordingSink::default());
+        crate::progress::take_output();
+        std::fs::remove_file(&path).ok();
+
+        // a file shorter than the amount is read to its end
+        let stats = stats.unwrap();
+        assert_eq!(stats.bytes, 40_000);
+        assert!(stats.secs > 0.0 && stats.mib_s > 0.0);
+    }
+
+    #[test]
+    fn each_pass_regenerates_from_its_derived_seed() {
+        assert_eq!(pass_seed(7, 1), pass_seed(7, 1));
+        assert_ne!(pass_seed(7, 1), pass_seed(7, 2));
+
+        let mut target = MemTarget::new(3 * 4096);
+        let mut opts = small_opts(WipeMode::Random, 2);
+        opts.seed = Some(7);
+        wipe(&mut target, &opts).unwrap();
+
+        for (i, written) in target.flushed.iter().enumerate() {
+            let mut expected = vec![0u8; written.len()];
+            seeded_rng(7, i as u32 + 1).fill_bytes(&mut expected);
+            assert!(written == &expected, "pass {} differs from its seed", i + 1);
+        }
+        assert_ne!(target.flushed[0], target.flushed[1]);
+    }
+
+    #[test]
+    fn limit_bytes_stops_the_write_loop_at_the_cap() {
+        assert_eq!(limit_len(16384, Some(5000), 512), (4608, true));
+        assert_eq!(limit_len(16384, Some(1 << 20), 512), (16384, false));
+        assert_eq!(limit_len(16384, None, 512), (16384, false));
+
+        let mut target = MemTarget::new(16384);
+        target.data.get_mut().fill(0xAA);
+        let opts = small_opts(WipeMode::Zeros, 1);
+        let (capped, _) = limit_len(16384, Some(5000), 512);
+        wipe_file_reported(&mut target, capped, &opts, &mut RecordingSink::default()).unwrap();
+
+        let data = target.data.get_ref();
+        assert!(data[..4608].iter().all(|&b| b == 0));
+        assert!(data[4608..].iter().all(|&b| b == 0xAA));
+    }
+
+    /// Rejects multi-sector writes like some USB bridges, and never takes
+    /// the sector at `bad`.
+    struct SectorWrites {
+        inner: MemTarget,
+        bad: u64,
+    }
+
+    impl Read for SectorWrites {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for SectorWrites {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > 512 || self.inner.data.position() == self.bad {
+                return Err(io::Error::from_raw_os_error(ERROR_INVALID_PARAMETER));
+            }
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for SectorWrites {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl WipeTarget for SectorWrites {
+        fn sync_data(&self) -> io::Result<()> {
+            self.inner.sync_data()
+        }
+    }
+
+    #[test]
+    fn short_write_total_fails_the_wipe() {
+        let mut target = SectorWrites {
+            inner: MemTarget::new(4096),
+            bad: 1024,
+        };
+        let opts = small_opts(WipeMode::Zeros, 2);
+
+        crate::progress::hold_output();
+        let res = wipe_file_reported(&mut target, 4096, &opts, &mut RecordingSink::default());
+        crate::progress::take_output();
+
+        let err = res.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(err.to_string(), "wrote 7168 bytes but 8192 were expected");
+    }
+
+    #[test]
+    fn io_size_splits_each_buffer_into_device_writes() {
+        const MIB: u64 = 1 << 20;
+        let mut target = MemTarget::new(8 << 20);
+        let mut opts = WipeOptions::new(WipeMode::Zeros, 1);
+        opts.buffer_size = 4 << 20;
+        opts.io_size = Some(1 << 20);
+        wipe(&mut target, &opts).unwrap();
+
+        // two 4 MiB buffers, four 1 MiB writes each
+        let expected: Vec<u64> = (0..8).map(|i| i * MIB).collect();
+        assert_eq!(target.writes, expected);
+    }
+
+    #[test]
+    fn verify_reads_exactly_the_tail_of_an_odd_sized_file() {
+        let path = std::env::temp_dir().join(format!("wipecore-odd-{}", std::process::id()));
+        let size = 3 * 4096 + 100;
+        std::fs::write(&path, vec![0xAAu8; size]).unwrap();
+        let mut file = File::options().read(true).write(true).open(&path).unwrap();
+        let mut sink = RecordingSink::default();
+
+        crate::progress::hold_output();
+        for mode in [WipeMode::Zeros, WipeMode::Random] {
+            let mut opts = small_opts(mode, 1);
+            opts.seed = Some(3);
+            wipe_file_reported(&mut file, size as u64, &opts, &mut sink).unwrap();
+            let hash = verify_wipe(&mut file, size as u64, &opts, &mut sink).unwrap();
+            if let WipeMode::Zeros = mode {
+                assert_eq!(hash, pattern_hash(0, size as u64));
+            }
+        }
+
+        // a bad last byte is still caught
+        let opts = small_opts(WipeMode::Zeros, 1);
+        wipe_file_reported(&mut file, size as u64, &opts, &mut sink).unwrap();
+        file.seek(SeekFrom::Start(size as u64 - 1)).unwrap();
+        file.write_all(&[1]).unwrap();
+        let res = verify_wipe(&mut file, size as u64, &opts, &mut sink);
+        crate::progress::take_output();
+        std::fs::remove_file(&path).ok();
+
+        let err = res.unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Verification failed at offset 12387: expected 0x00, found 0x01"
+        );
+    }
+
+    #[test]
+    fn rejected_chunks_are_written_sector_by_sector() {
+        let mut target = SectorWrites {
+            inner: MemTarget::new(3 * 4096),
+            bad: u64::MAX,
+        };
+        target.inner.data.get_mut().fill(0xAA);
+        let opts = small_opts(WipeMode::Zeros, 1);
+
+        crate::progress::hold_output();
+        let res = wipe_file_reported(&mut target, 3 * 4096, &opts, &mut RecordingSink::default());
+        let out = crate::progress::take_output();
+
+        assert_eq!(res.unwrap().bytes_written, 3 * 4096);
+        assert!(target.inner.data.get_ref().iter().all(|&b| b == 0));
+        // every chunk fell back to 512-byte writes
+        assert_eq!(target.inner.writes.len(), 3 * 8);
+        assert!(out.contains("retrying sector by sector"));
+    }
+
+    /// A fake-capacity drive: offsets past `real` bytes wrap back to the start.
+    struct WrappingDevice {
+        inner: MemTarget,
+        real: u64,
+        pos: u64,
+    }
+
+    impl Read for WrappingDevice {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.data.set_position(self.pos % self.real);
+            let n = self.inner.read(buf)?;
+            self.pos += n as u64;
+            Ok(n)
+        }
+    }
+
+    impl Write for WrappingDevice {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.inner.data.set_position(self.pos % self.real);
+            let n = self.inner.write(buf)?;
+            self.pos += n as u64;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for WrappingDevice {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            if let SeekFrom::Start(at) = pos {
+                self.pos = at;
+            }
+            Ok(self.pos)
+        }
+    }
+
+    impl WipeTarget for WrappingDevice {
+        fn sync_data(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn probe_finds_a_capacity_that_wraps_around() {
+        let mut fake = WrappingDevice {
+            inner: MemTarget::new(256 << 10),
+            real: 256 << 10,
+            pos: 0,
+        };
+        let mut honest = MemTarget::new(1 << 20);
+
+        crate::progress::hold_output();
+        let wrapped = probe_addressing(&mut fake, 1 << 20, 512);
+        let full = probe_addressing(&mut honest, 1 << 20, 512);
+        crate::progress::take_output();
+
+        let err = wrapped.unwrap_err().to_string();
+        assert!(err.contains("offset 262144"), "{}", err);
+        assert!(err.contains("capacity is at most 256 KB"), "{}", err);
+        full.unwrap();
+    }
+
+    #[test]
+    fn invert_pass_complements_what_the_random_pass_left() {
+        // unseeded: the inversion works from what is on the media, not the stream
+        let mut target = MemTarget::new(3 * 4096 + 512);
+        let opts = small_opts(WipeMode::RandomInvert, 2);
+
+        wipe(&mut target, &opts).unwrap();
+
+        assert_eq!(target.flushed.len(), 2);
+        let (pass1, pass2) = (&target.flushed[0], &target.flushed[1]);
+        assert!(pass1.iter().zip(pass2).all(|(a, b)| *b == !*a));
+        check_not_uniform(pass1, 0).unwrap();
+    }
+
+    #[test]
+    fn estimate_takes_the_slower_of_disk_and_rng() {
+        let one_gib = |mode, io, rng| estimate_wipe_seconds(1 << 30, mode, 1, io, rng);
+        // 1024 MiB at 100 MB/s, whichever side is the slow one
+        assert_eq!(one_gib(WipeMode::Random, 200.0, 100.0), 11);
+        assert_eq!(one_gib(WipeMode::Random, 100.0, 200.0), 11);
+        // fixed patterns never wait on the generator
+        assert_eq!(one_gib(WipeMode::Zeros, 200.0, 10.0), 6);
+
+        assert!(measure_rng_rate(true, 64 << 10) > 0.0);
+    }
+}